@@ -0,0 +1,451 @@
+//! The changelog module.
+//!
+//! Groups the commits of a release into changes (from their Conventional Commits prefix, when they have one),
+//! suggests the semver bump, and renders (and prepends) a [Keep a Changelog](https://keepachangelog.com) section.
+
+use std::{str::FromStr, sync::OnceLock};
+
+use regex::Regex;
+
+use super::types::CommitInfo;
+
+// Statics.
+
+static HEADER: &str = r#"# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/), and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+"#;
+
+fn conventional_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^(\w+)(?:\(([^()]+)\))?(!)?:\s*(.+)$").unwrap())
+}
+
+fn version_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^(?:.*/)?v?(\d+)\.(\d+)\.(\d+)$").unwrap())
+}
+
+// Types.
+
+/// What kind of change a commit makes, in the order that they are listed in a release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    /// A change that breaks compatibility (e.g., `feat!:`, or a `BREAKING CHANGE:` footer).
+    Breaking,
+    /// A new feature (`feat:`).
+    Feature,
+    /// A bug fix (`fix:`).
+    Fix,
+    /// Any other user-facing change (e.g., `perf:`, `refactor:`, or `revert:`).
+    Changed,
+    /// A change that is left out of the changelog (e.g., `docs:`, `test:`, `ci:`, or `chore:`).
+    Internal,
+}
+
+impl FromStr for ChangeKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "breaking" => Ok(ChangeKind::Breaking),
+            "feature" | "features" | "feat" => Ok(ChangeKind::Feature),
+            "fix" | "fixes" => Ok(ChangeKind::Fix),
+            "changed" | "change" | "changes" => Ok(ChangeKind::Changed),
+            "internal" => Ok(ChangeKind::Internal),
+            _ => Err(anyhow::Error::msg("Invalid change kind specified.")),
+        }
+    }
+}
+
+impl ChangeKind {
+    /// The heading of the changelog section that lists the changes.
+    pub fn heading(&self) -> &'static str {
+        match self {
+            ChangeKind::Breaking => "Breaking Changes",
+            ChangeKind::Feature => "Added",
+            ChangeKind::Fix => "Fixed",
+            ChangeKind::Changed => "Changed",
+            ChangeKind::Internal => "Internal",
+        }
+    }
+}
+
+/// A single change in a release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub scope: Option<String>,
+    pub description: String,
+    pub short_sha: String,
+}
+
+/// The semver component to bump for a release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl std::fmt::Display for Bump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bump::Patch => write!(f, "patch"),
+            Bump::Minor => write!(f, "minor"),
+            Bump::Major => write!(f, "major"),
+        }
+    }
+}
+
+/// A `major.minor.patch` version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// Parses the version from a tag (e.g., `v1.2.0`, `1.2.0`, or `refs/tags/v1.2.0`).
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let captures = version_regex().captures(tag.trim())?;
+
+        Some(Self {
+            major: captures[1].parse().ok()?,
+            minor: captures[2].parse().ok()?,
+            patch: captures[3].parse().ok()?,
+        })
+    }
+
+    /// The version after the bump.
+    ///
+    /// Before `1.0.0`, breaking changes bump the minor version (and everything else bumps the patch version).
+    pub fn bump(&self, bump: Bump) -> Self {
+        match (bump, self.major) {
+            (Bump::Major, 0) => Self { minor: self.minor + 1, patch: 0, ..*self },
+            (Bump::Minor, 0) | (Bump::Patch, _) => Self { patch: self.patch + 1, ..*self },
+            (Bump::Major, _) => Self { major: self.major + 1, minor: 0, patch: 0 },
+            (Bump::Minor, _) => Self { minor: self.minor + 1, patch: 0, ..*self },
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// Classification.
+
+/// Classifies the commit from its Conventional Commits prefix (or returns `None` if it does not have one).
+pub fn classify(commit: &CommitInfo) -> Option<Change> {
+    let captures = conventional_regex().captures(commit.subject.trim())?;
+
+    let breaking = captures.get(3).is_some() || commit.message.lines().any(|l| l.starts_with("BREAKING CHANGE:") || l.starts_with("BREAKING-CHANGE:"));
+
+    let kind = match captures[1].to_ascii_lowercase().as_str() {
+        "feat" | "feature" => ChangeKind::Feature,
+        "fix" | "bugfix" => ChangeKind::Fix,
+        "perf" | "refactor" | "revert" => ChangeKind::Changed,
+        "docs" | "style" | "test" | "tests" | "build" | "ci" | "chore" => ChangeKind::Internal,
+        _ => return None,
+    };
+
+    let kind = if breaking { ChangeKind::Breaking } else { kind };
+
+    Some(Change {
+        kind,
+        scope: captures.get(2).map(|s| s.as_str().to_string()),
+        description: captures[4].trim().to_string(),
+        short_sha: commit.short_sha.clone(),
+    })
+}
+
+/// The change for a commit that was classified some other way (e.g., by the model).
+pub fn change(commit: &CommitInfo, kind: ChangeKind) -> Change {
+    Change {
+        kind,
+        scope: None,
+        description: commit.subject.trim().to_string(),
+        short_sha: commit.short_sha.clone(),
+    }
+}
+
+/// Suggests the semver bump for the changes.
+pub fn suggest_bump(changes: &[Change]) -> Bump {
+    let bump_of = |c: &Change| match c.kind {
+        ChangeKind::Breaking => Bump::Major,
+        ChangeKind::Feature => Bump::Minor,
+        _ => Bump::Patch,
+    };
+
+    changes.iter().map(bump_of).max().unwrap_or(Bump::Patch)
+}
+
+// Rendering.
+
+/// Renders the changes (other than the internal ones) as a Keep a Changelog release section (or as the
+/// `[Unreleased]` section, if there is no version).
+pub fn render(version: Option<&str>, date: &str, changes: &[Change]) -> String {
+    let mut text = match version {
+        Some(version) => format!("## [{}] - {}\n", version, date),
+        None => "## [Unreleased]\n".to_string(),
+    };
+
+    for kind in [ChangeKind::Breaking, ChangeKind::Feature, ChangeKind::Fix, ChangeKind::Changed] {
+        let entries = changes.iter().filter(|c| c.kind == kind).collect::<Vec<_>>();
+
+        if entries.is_empty() {
+            continue;
+        }
+
+        text.push_str(&format!("\n### {}\n\n", kind.heading()));
+
+        for change in entries {
+            match &change.scope {
+                Some(scope) => text.push_str(&format!("- **{}:** {} ({})\n", scope, capitalize(&change.description), change.short_sha)),
+                None => text.push_str(&format!("- {} ({})\n", capitalize(&change.description), change.short_sha)),
+            }
+        }
+    }
+
+    text
+}
+
+/// Prepends the release section to an existing changelog (below its `[Unreleased]` section, if any), or starts a
+/// new changelog if there is none; an `[Unreleased]` section is merged into the existing one instead.
+pub fn prepend(existing: &str, section: &str) -> String {
+    if existing.trim().is_empty() {
+        return format!("{}\n{}", HEADER, section);
+    }
+
+    let lines = existing.split_inclusive('\n').collect::<Vec<_>>();
+
+    if section.lines().next().is_some_and(is_unreleased) {
+        if let Some(start) = lines.iter().position(|l| is_unreleased(l)) {
+            let end = lines[start + 1..].iter().position(|l| l.starts_with("## ")).map_or(lines.len(), |k| start + 1 + k);
+            let merged = merge(&lines[start..end].concat(), section);
+
+            return match lines[end..].concat() {
+                rest if rest.is_empty() => format!("{}{}", lines[..start].concat(), merged),
+                rest => format!("{}{}\n{}", lines[..start].concat(), merged, rest),
+            };
+        }
+    }
+
+    // Find the first release heading (i.e., the first `## ` heading other than `[Unreleased]`).
+    let mut offset = 0;
+
+    for line in &lines {
+        if line.starts_with("## ") && !is_unreleased(line) {
+            return format!("{}{}\n{}", &existing[..offset], section, &existing[offset..]);
+        }
+
+        offset += line.len();
+    }
+
+    format!("{}\n\n{}", existing.trim_end(), section)
+}
+
+// Helpers.
+
+fn is_unreleased(line: &str) -> bool {
+    line.starts_with("## ") && line.to_ascii_lowercase().contains("unreleased")
+}
+
+/// Merges the entries of the section into the existing one, under the same `### ` headings (which are added at the
+/// end if the existing section does not have them), skipping the entries that it already lists.
+fn merge(existing: &str, section: &str) -> String {
+    let mut merged = groups(existing);
+
+    for (heading, entries) in groups(section).into_iter().skip(1) {
+        let index = match merged.iter().position(|(h, _)| h.trim() == heading.trim()) {
+            Some(index) => index,
+            None => {
+                merged.push((heading, Vec::new()));
+                merged.len() - 1
+            },
+        };
+
+        for entry in entries {
+            if !merged[index].1.contains(&entry) {
+                merged[index].1.push(entry);
+            }
+        }
+    }
+
+    let mut text = String::new();
+
+    for (k, (heading, lines)) in merged.iter().enumerate() {
+        if k > 0 {
+            text.push('\n');
+        }
+
+        text.push_str(heading);
+        text.push('\n');
+
+        if !lines.is_empty() {
+            text.push('\n');
+        }
+
+        for line in lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// Groups the (non-blank) lines of a section under their headings (the `## ` one, and then each `### ` one).
+fn groups(text: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match groups.last_mut() {
+            Some(group) if !line.starts_with("### ") => group.1.push(line),
+            _ => groups.push((line, Vec::new())),
+        }
+    }
+
+    groups
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(short_sha: &str, message: &str) -> CommitInfo {
+        CommitInfo {
+            sha: short_sha.repeat(5),
+            short_sha: short_sha.to_string(),
+            subject: message.lines().next().unwrap_or_default().to_string(),
+            message: message.to_string(),
+            date: "2024-01-01".to_string(),
+        }
+    }
+
+    fn kind(message: &str) -> Option<ChangeKind> {
+        classify(&commit("abc1234", message)).map(|c| c.kind)
+    }
+
+    #[test]
+    fn classifies_conventional_commits() {
+        assert_eq!(kind("feat: add a flag"), Some(ChangeKind::Feature));
+        assert_eq!(kind("Fix: handle empty input"), Some(ChangeKind::Fix));
+        assert_eq!(kind("perf(diff): parse faster"), Some(ChangeKind::Changed));
+        assert_eq!(kind("docs: fix a typo"), Some(ChangeKind::Internal));
+        assert_eq!(kind("Add a flag"), None);
+        assert_eq!(kind("wip: something"), None);
+
+        let change = classify(&commit("abc1234", "fix(cli): handle empty input")).unwrap();
+        assert_eq!(change.scope.as_deref(), Some("cli"));
+        assert_eq!(change.description, "handle empty input");
+        assert_eq!(change.short_sha, "abc1234");
+    }
+
+    #[test]
+    fn classifies_breaking_changes() {
+        assert_eq!(kind("feat!: drop the old flag"), Some(ChangeKind::Breaking));
+        assert_eq!(kind("refactor(api)!: rename the endpoints"), Some(ChangeKind::Breaking));
+        assert_eq!(kind("feat: new config\n\nBREAKING CHANGE: the old config is gone."), Some(ChangeKind::Breaking));
+        assert_eq!(kind("fix: x\n\nBREAKING-CHANGE: y"), Some(ChangeKind::Breaking));
+
+        // Only a footer counts, not a mention in the body.
+        assert_eq!(kind("fix: x\n\nThis is not a BREAKING CHANGE: really."), Some(ChangeKind::Fix));
+    }
+
+    #[test]
+    fn parses_and_bumps_versions() {
+        let version = |major, minor, patch| Version { major, minor, patch };
+
+        assert_eq!(Version::from_tag("v1.2.3"), Some(version(1, 2, 3)));
+        assert_eq!(Version::from_tag("1.2.3"), Some(version(1, 2, 3)));
+        assert_eq!(Version::from_tag("refs/tags/v1.2.3"), Some(version(1, 2, 3)));
+        assert_eq!(Version::from_tag("v1.2"), None);
+        assert_eq!(Version::from_tag("HEAD"), None);
+
+        assert_eq!(version(1, 2, 3).bump(Bump::Major).to_string(), "2.0.0");
+        assert_eq!(version(1, 2, 3).bump(Bump::Minor).to_string(), "1.3.0");
+        assert_eq!(version(1, 2, 3).bump(Bump::Patch).to_string(), "1.2.4");
+
+        // Before 1.0.0, breaking changes bump the minor version, and features the patch version.
+        assert_eq!(version(0, 4, 2).bump(Bump::Major).to_string(), "0.5.0");
+        assert_eq!(version(0, 4, 2).bump(Bump::Minor).to_string(), "0.4.3");
+        assert_eq!(version(0, 4, 2).bump(Bump::Patch).to_string(), "0.4.3");
+    }
+
+    #[test]
+    fn suggests_the_largest_bump() {
+        let changes = |messages: &[&str]| messages.iter().filter_map(|m| classify(&commit("abc1234", m))).collect::<Vec<_>>();
+
+        assert_eq!(suggest_bump(&[]), Bump::Patch);
+        assert_eq!(suggest_bump(&changes(&["fix: a", "docs: b"])), Bump::Patch);
+        assert_eq!(suggest_bump(&changes(&["fix: a", "feat: b"])), Bump::Minor);
+        assert_eq!(suggest_bump(&changes(&["feat: a", "fix!: b"])), Bump::Major);
+    }
+
+    #[test]
+    fn renders_release_sections() {
+        let changes = ["feat(cli): add a flag", "docs: typo", "fix: handle empty input", "feat!: drop the old flag"].iter().enumerate().filter_map(|(k, m)| classify(&commit(&format!("{}000000", k), m))).collect::<Vec<_>>();
+
+        assert_eq!(render(Some("1.3.0"), "2024-01-01", &changes), "\
+## [1.3.0] - 2024-01-01
+
+### Breaking Changes
+
+- Drop the old flag (3000000)
+
+### Added
+
+- **cli:** Add a flag (0000000)
+
+### Fixed
+
+- Handle empty input (2000000)
+");
+    }
+
+    #[test]
+    fn prepends_releases_below_the_unreleased_section() {
+        assert!(prepend("", "## [1.0.0] - 2024-01-01\n").starts_with("# Changelog\n"));
+        assert!(prepend("", "## [1.0.0] - 2024-01-01\n").ends_with("\n\n## [1.0.0] - 2024-01-01\n"));
+
+        let existing = "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Work in progress\n\n## [0.9.0] - 2023-01-01\n\n### Fixed\n\n- Old fix\n";
+
+        assert_eq!(prepend(existing, "## [1.0.0] - 2024-01-01\n\n### Added\n\n- New (abc1234)\n"), "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Work in progress\n\n## [1.0.0] - 2024-01-01\n\n### Added\n\n- New (abc1234)\n\n## [0.9.0] - 2023-01-01\n\n### Fixed\n\n- Old fix\n");
+
+        // A changelog without releases gets the section at the end.
+        assert_eq!(prepend("# Changelog\n", "## [1.0.0] - 2024-01-01\n"), "# Changelog\n\n## [1.0.0] - 2024-01-01\n");
+    }
+
+    #[test]
+    fn merges_into_the_existing_unreleased_section() {
+        let existing = "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Work in progress\n\n## [0.9.0] - 2023-01-01\n\n### Fixed\n\n- Old fix\n";
+        let section = "## [Unreleased]\n\n### Added\n\n- Work in progress\n- New (abc1234)\n\n### Fixed\n\n- A fix (def5678)\n";
+
+        let merged = prepend(existing, section);
+
+        assert_eq!(merged, "# Changelog\n\n## [Unreleased]\n\n### Added\n\n- Work in progress\n- New (abc1234)\n\n### Fixed\n\n- A fix (def5678)\n\n## [0.9.0] - 2023-01-01\n\n### Fixed\n\n- Old fix\n");
+        assert_eq!(merged.matches("[Unreleased]").count(), 1);
+
+        // Merging again changes nothing, and an `[Unreleased]` section at the end stays there.
+        assert_eq!(prepend(&merged, section), merged);
+        assert_eq!(prepend("# Changelog\n\n## [Unreleased]\n", section), format!("# Changelog\n\n{}", section));
+    }
+}
@@ -0,0 +1,334 @@
+//! The citation module.
+//!
+//! Finds `path/file.rs:30` style citations in a review, and checks them against the hunks of the reviewed diff.
+//!
+//! A `name.ext:30` token is only a citation if it has a directory (e.g., `src/main.rs:30`), or names a file of the
+//! diff (e.g., `main.rs:30` when `src/main.rs` changed), so that `host.com:8080` and the like are left alone.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{diff::Diff, finding::Review};
+
+// Statics.
+
+static FLAG: &str = " (⚠️ outside of the diff)";
+
+fn citation_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"([A-Za-z0-9_.\-/]+\.[A-Za-z0-9_]+):(\d+)(?:-(\d+))?").unwrap())
+}
+
+// Types.
+
+/// What to do with citations that do not point into a changed hunk.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CitationPolicy {
+    /// Leave them as they are.
+    Ignore,
+    /// Annotate them with a warning.
+    #[default]
+    Flag,
+    /// Remove the lines that contain them.
+    Drop,
+}
+
+/// A single `file:line` (or `file:start-end`) citation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub path: String,
+    pub line: u32,
+    pub end_line: Option<u32>,
+    /// The byte range of the citation within the text.
+    pub span: std::ops::Range<usize>,
+}
+
+impl Citation {
+    /// Whether any line of the citation falls within one of the diff's hunks.
+    pub fn is_in(&self, diff: &Diff) -> bool {
+        (self.line..=self.end_line.unwrap_or(self.line).max(self.line)).any(|n| diff.contains_line(&self.path, n))
+    }
+}
+
+// Parsing.
+
+/// Finds the citations with a directory (e.g., `src/main.rs:30`) in the specified text.
+pub fn find(text: &str) -> Vec<Citation> {
+    candidates(text).filter(|c| is_path_like(text, c)).collect()
+}
+
+/// Finds the citations in the specified text: those with a directory, and those that name a file of the diff.
+pub fn find_in(text: &str, diff: &Diff) -> Vec<Citation> {
+    candidates(text).filter(|c| is_path_like(text, c) || diff.find_file(&c.path).is_some()).collect()
+}
+
+fn candidates(text: &str) -> impl Iterator<Item = Citation> + '_ {
+    citation_regex().captures_iter(text).filter_map(|c| {
+        let span = c.get(0)?.range();
+        let path = c.get(1)?.as_str().trim_start_matches("./").to_string();
+        let line = c.get(2)?.as_str().parse().ok()?;
+        let end_line = c.get(3).and_then(|m| m.as_str().parse().ok());
+
+        Some(Citation { path, line, end_line, span })
+    })
+}
+
+/// Whether the citation has a directory, and is not part of a URL (`https://host.com/a.rs:80`) or of an address
+/// (`git@host.com:org/a.rs`).
+fn is_path_like(text: &str, citation: &Citation) -> bool {
+    let preceded_by = text[..citation.span.start].chars().next_back();
+
+    citation.path.contains('/') && !citation.path.starts_with("//") && !matches!(preceded_by, Some(':' | '@'))
+}
+
+// Checking.
+
+/// Applies the [`CitationPolicy`] to the citations in the text that do not point into a changed hunk.
+///
+/// Returns the updated text along with the number of such citations.
+pub fn check(text: &str, diff: &Diff, policy: CitationPolicy) -> (String, usize) {
+    let invalid = find_in(text, diff).into_iter().filter(|c| !c.is_in(diff)).collect::<Vec<_>>();
+
+    let result = match policy {
+        _ if invalid.is_empty() => text.to_string(),
+        CitationPolicy::Ignore => text.to_string(),
+        CitationPolicy::Flag => flag(text, &invalid),
+        CitationPolicy::Drop => drop_lines(text, &invalid),
+    };
+
+    (result, invalid.len())
+}
+
+/// Applies the [`CitationPolicy`] to the findings whose location (or whose message's citations) do not point into a
+/// changed hunk.
+///
+/// Returns the updated review along with the number of such citations.
+pub fn check_review(review: Review, diff: &Diff, policy: CitationPolicy) -> (Review, usize) {
+    let mut invalid = 0;
+    let mut findings = Vec::new();
+
+    for mut finding in review.findings {
+        let location = match (&finding.file, finding.line_range) {
+            (Some(path), Some(range)) => Some(Citation { path: path.clone(), line: range.start, end_line: Some(range.end), span: 0..0 }),
+            _ => None,
+        };
+
+        let location_is_invalid = location.is_some_and(|c| !c.is_in(diff));
+        let (message, message_invalid) = check(&finding.message, diff, policy);
+
+        invalid += usize::from(location_is_invalid) + message_invalid;
+
+        match policy {
+            CitationPolicy::Drop if location_is_invalid => continue,
+            CitationPolicy::Drop if message.trim().is_empty() => continue,
+            CitationPolicy::Flag if location_is_invalid => finding.message = format!("{}{}", message, FLAG),
+            _ => finding.message = message,
+        }
+
+        findings.push(finding);
+    }
+
+    (Review { findings, ..review }, invalid)
+}
+
+// Helpers.
+
+fn flag(text: &str, citations: &[Citation]) -> String {
+    let mut result = text.to_string();
+
+    // Insert from the back, so that the earlier spans remain valid.
+    for citation in citations.iter().rev() {
+        let mut end = citation.span.end;
+
+        // Keep the flag outside of inline code.
+        if text[end..].starts_with('`') {
+            end += 1;
+        }
+
+        result.insert_str(end, FLAG);
+    }
+
+    result
+}
+
+fn drop_lines(text: &str, citations: &[Citation]) -> String {
+    let mut offset = 0;
+    let mut lines = Vec::new();
+
+    for line in text.split_inclusive('\n') {
+        let range = offset..offset + line.len();
+        offset += line.len();
+
+        if citations.iter().any(|c| range.contains(&c.span.start)) {
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    lines.concat()
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::base::{finding::{Finding, LineRange}, severity::Severity};
+
+    use super::*;
+
+    static DIFF: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -10,2 +10,3 @@ fn main() {
+ let a = 1;
++let b = 2;
+ let c = 3;
+";
+
+    fn paths(citations: &[Citation]) -> Vec<String> {
+        citations.iter().map(|c| format!("{}:{}", c.path, c.line)).collect()
+    }
+
+    #[test]
+    fn ignores_hosts_and_urls() {
+        let text = "Connects to host.com:8080 (see https://example.com/docs/api.md:12 and user@example.com:22), in ./src/lib.rs:4-6.";
+
+        assert_eq!(paths(&find(text)), vec!["src/lib.rs:4"]);
+        assert_eq!(find(text)[0].end_line, Some(6));
+    }
+
+    #[test]
+    fn accepts_bare_names_of_changed_files() {
+        let diff = Diff::parse(DIFF).unwrap();
+        let text = "`main.rs:11` shadows `b`, unlike config.toml:3 and host.com:8080.";
+
+        assert!(find(text).is_empty());
+        assert_eq!(paths(&find_in(text, &diff)), vec!["main.rs:11"]);
+    }
+
+    #[test]
+    fn flags_citations_outside_of_the_diff() {
+        let diff = Diff::parse(DIFF).unwrap();
+        let text = "- src/main.rs:11 is fine.\n- src/main.rs:40 is not.\n- host.com:8080 is not a citation.\n";
+
+        let (flagged, invalid) = check(text, &diff, CitationPolicy::Flag);
+        assert_eq!(invalid, 1);
+        assert_eq!(flagged, "- src/main.rs:11 is fine.\n- src/main.rs:40 (⚠️ outside of the diff) is not.\n- host.com:8080 is not a citation.\n");
+
+        let (dropped, _) = check(text, &diff, CitationPolicy::Drop);
+        assert_eq!(dropped, "- src/main.rs:11 is fine.\n- host.com:8080 is not a citation.\n");
+    }
+
+    static CHANGES: &str = "\
+diff --git a/src/old.rs b/src/new.rs
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+--- a/src/old.rs
++++ b/src/new.rs
+@@ -3,4 +3,3 @@ fn moved() {
+ let a = 1;
+-let b = 2;
+-let c = 3;
++let bc = 5;
+ let d = 4;
+diff --git a/src/gone.rs b/src/gone.rs
+deleted file mode 100644
+--- a/src/gone.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-fn gone() {}
+-fn also_gone() {}
+";
+
+    fn finding(file: &str, lines: (u32, u32), message: &str) -> Finding {
+        Finding {
+            file: Some(file.to_string()),
+            line_range: Some(LineRange { start: lines.0, end: lines.1 }),
+            severity: Severity::Logic,
+            category: "general".to_string(),
+            message: message.to_string(),
+            suggested_fix: None,
+            guideline: None,
+            pass: None,
+        }
+    }
+
+    #[test]
+    fn checks_renamed_and_deleted_files() {
+        let diff = Diff::parse(CHANGES).unwrap();
+
+        // Citations use the new path, and the line numbers of the new file.
+        assert!(find("src/new.rs:4")[0].is_in(&diff));
+        assert!(find("src/new.rs:5-9")[0].is_in(&diff));
+        assert!(!find("src/new.rs:6")[0].is_in(&diff));
+        assert!(!find("src/old.rs:4")[0].is_in(&diff));
+
+        // A deleted file has no lines left to cite (e.g., its removed `also_gone` at line 2).
+        assert!(!find("src/gone.rs:2")[0].is_in(&diff));
+        assert_eq!(check("See new.rs:4 and gone.rs:2.", &diff, CitationPolicy::Flag), ("See new.rs:4 and gone.rs:2 (⚠️ outside of the diff).".to_string(), 1));
+    }
+
+    #[test]
+    fn flags_or_drops_findings_outside_of_the_diff() {
+        let diff = Diff::parse(CHANGES).unwrap();
+
+        let review = Review {
+            summary: Some("Two issues.".to_string()),
+            findings: vec![
+                finding("src/new.rs", (4, 4), "`bc` is misnamed (unlike `src/new.rs:5`)."),
+                finding("src/new.rs", (20, 22), "Out of the hunk."),
+                finding("src/new.rs", (3, 3), "See `src/new.rs:40`."),
+            ],
+        };
+
+        let (flagged, invalid) = check_review(review.clone(), &diff, CitationPolicy::Flag);
+        assert_eq!(invalid, 2);
+        assert_eq!(flagged.summary, review.summary);
+        assert_eq!(flagged.findings.iter().map(|f| f.message.as_str()).collect::<Vec<_>>(), vec![
+            "`bc` is misnamed (unlike `src/new.rs:5`).",
+            "Out of the hunk. (⚠️ outside of the diff)",
+            "See `src/new.rs:40` (⚠️ outside of the diff).",
+        ]);
+
+        // Dropping a message's only line drops the finding.
+        let (dropped, invalid) = check_review(review.clone(), &diff, CitationPolicy::Drop);
+        assert_eq!(invalid, 2);
+        assert_eq!(dropped.findings, review.findings[..1]);
+
+        let (ignored, invalid) = check_review(review.clone(), &diff, CitationPolicy::Ignore);
+        assert_eq!(invalid, 2);
+        assert_eq!(ignored, review);
+    }
+
+    #[test]
+    fn numbers_the_lines_of_the_new_file() {
+        let diff = Diff::parse(CHANGES).unwrap();
+
+        assert_eq!(diff.to_numbered_string(), "\
+diff --git a/src/old.rs b/src/new.rs
+similarity index 90%
+rename from src/old.rs
+rename to src/new.rs
+--- a/src/old.rs
++++ b/src/new.rs
+@@ -3,4 +3,3 @@ fn moved() {
+3   let a = 1;
+  - let b = 2;
+  - let c = 3;
+4 + let bc = 5;
+5   let d = 4;
+diff --git a/src/gone.rs b/src/gone.rs
+deleted file mode 100644
+--- a/src/gone.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+  - fn gone() {}
+  - fn also_gone() {}
+");
+    }
+}
@@ -0,0 +1,135 @@
+//! The commit module.
+//!
+//! Cleans up generated commit messages, and checks them against the configured conventions.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+// Statics.
+
+/// The Conventional Commits types that a subject may start with.
+pub static CONVENTIONAL_TYPES: &[&str] = &["feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert"];
+
+fn conventional_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(&format!(r"^({})(\([^()]+\))?!?: \S", CONVENTIONAL_TYPES.join("|"))).unwrap())
+}
+
+// Types.
+
+/// The conventions that a commit message should follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitStyle {
+    /// Whether the subject should follow Conventional Commits (e.g., `fix(parser): handle empty input`).
+    pub conventional: bool,
+    /// The maximum number of characters in the subject line.
+    pub max_subject_length: usize,
+}
+
+// Helpers.
+
+/// Removes the wrapping that models tend to add around a commit message (code fences, quotes, and a "Commit message:"
+/// label).
+pub fn normalize(text: &str) -> String {
+    let mut text = text.trim();
+
+    if let Some(rest) = text.strip_prefix("```") {
+        // Skip the (optional) language of the fence.
+        let rest = rest.split_once('\n').map(|(_, rest)| rest).unwrap_or_default();
+        text = rest.trim_end().strip_suffix("```").unwrap_or(rest).trim();
+    }
+
+    for label in ["Commit message:", "commit message:", "Subject:"] {
+        text = text.strip_prefix(label).unwrap_or(text).trim_start();
+    }
+
+    let mut lines = text.lines().map(str::trim_end).collect::<Vec<_>>();
+
+    if let Some(subject) = lines.first_mut() {
+        let trimmed = subject.trim();
+
+        *subject = match trimmed.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(unquoted) => unquoted,
+            None => trimmed,
+        };
+    }
+
+    // Keep a single blank line between the subject and the body.
+    if lines.len() > 1 && !lines[1].is_empty() {
+        lines.insert(1, "");
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+/// Lists the ways in which the message does not follow the style (if any).
+pub fn problems(message: &str, style: CommitStyle) -> Vec<String> {
+    let mut problems = Vec::new();
+    let subject = message.lines().next().unwrap_or_default();
+
+    if subject.is_empty() {
+        problems.push("The subject line is empty.".to_string());
+        return problems;
+    }
+
+    let length = subject.chars().count();
+
+    if length > style.max_subject_length {
+        problems.push(format!("The subject line is {} characters long (the limit is {}).", length, style.max_subject_length));
+    }
+
+    if style.conventional && !conventional_regex().is_match(subject) {
+        problems.push(format!("The subject line does not follow Conventional Commits (`<type>[(scope)][!]: <description>`, where the type is one of {}).", CONVENTIONAL_TYPES.join(", ")));
+    }
+
+    if message.lines().nth(1).is_some_and(|line| !line.trim().is_empty()) {
+        problems.push("The subject line is not followed by a blank line.".to_string());
+    }
+
+    problems
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static STYLE: CommitStyle = CommitStyle { conventional: true, max_subject_length: 50 };
+
+    #[test]
+    fn accepts_valid_messages() {
+        for message in ["fix: handle empty input", "feat(parser)!: drop the old syntax", "docs(readme): fix a typo\n\nThe body.\n\nMore body."] {
+            assert_eq!(problems(message, STYLE), Vec::<String>::new(), "{}", message);
+        }
+
+        assert_eq!(problems("Handle empty input", CommitStyle { conventional: false, ..STYLE }), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_problems() {
+        let conventional = |message: &str| problems(message, STYLE).iter().any(|p| p.contains("Conventional Commits"));
+
+        assert!(conventional("feature: add a flag"));
+        assert!(conventional("Fix: handle empty input"));
+        assert!(conventional("fix handle empty input"));
+        assert!(conventional("fix(): handle empty input"));
+        assert!(conventional("fix!:handle empty input"));
+        assert!(!conventional("fix!: handle empty input"));
+
+        assert_eq!(problems(&format!("fix: {}", "x".repeat(46)), STYLE), vec!["The subject line is 51 characters long (the limit is 50).".to_string()]);
+        assert_eq!(problems(&format!("fix: {}", "é".repeat(45)), STYLE), Vec::<String>::new());
+
+        assert_eq!(problems("fix: handle empty input\nThe body.", STYLE), vec!["The subject line is not followed by a blank line.".to_string()]);
+        assert_eq!(problems("", STYLE), vec!["The subject line is empty.".to_string()]);
+    }
+
+    #[test]
+    fn normalizes_generated_messages() {
+        assert_eq!(normalize("```text\nfix: handle empty input\n\nThe body.\n```\n"), "fix: handle empty input\n\nThe body.");
+        assert_eq!(normalize("Commit message: \"fix: handle empty input\""), "fix: handle empty input");
+        assert_eq!(normalize("  fix: handle empty input  \nThe body.  \n"), "fix: handle empty input\n\nThe body.");
+        assert_eq!(normalize("fix: handle empty input\n\nThe \"body\"."), "fix: handle empty input\n\nThe \"body\".");
+    }
+}
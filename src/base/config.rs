@@ -3,27 +3,131 @@
 use config::{Environment, File};
 use serde::{Deserialize, Serialize};
 
-use super::types::{Res, Mode};
+use std::collections::HashMap;
+
+use crate::services::{vcs::VcsBackend, llm::{Provider, ModelSettings}};
+
+use super::{types::{Res, Mode, ContextMode}, citation::CitationPolicy, severity::Severity, guidelines::GuidelineSet, focus::FocusPass};
+
+// Statics.
+
+static DEFAULT_MAX_CHUNK_TOKENS: usize = 4000;
+static DEFAULT_MAX_CONTEXT_TOKENS: usize = 2000;
+static DEFAULT_MAX_CONTEXT_FILE_TOKENS: usize = 1000;
+static DEFAULT_MAX_UNTRACKED_BYTES: u64 = 100_000;
+static DEFAULT_HOOK_FAIL_ON: Severity = Severity::Runtime;
+static DEFAULT_COMMIT_MAX_SUBJECT_LENGTH: usize = 72;
+static DEFAULT_GUIDELINES_FILE_NAME: &str = "guidelines.md";
+
+static DEFAULT_OPENAI_URL: &str = "https://api.openai.com";
+static DEFAULT_LOCAL_URL: &str = "http://localhost:3000";
+static DEFAULT_ANTHROPIC_URL: &str = "https://api.anthropic.com";
+static DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+static DEFAULT_OPENAI_MODEL: &str = "gpt-4";
+static DEFAULT_ANTHROPIC_MODEL: &str = "claude-3-5-sonnet-latest";
+static DEFAULT_OLLAMA_MODEL: &str = "codellama";
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct OptionalConfig {
     openai_key: Option<String>,
+    anthropic_key: Option<String>,
     mode: Option<Mode>,
+    provider: Option<Provider>,
+    provider_url: Option<String>,
+    model: Option<String>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    max_tokens: Option<u32>,
+    seed: Option<u64>,
+    stop: Option<Vec<String>>,
+    timeout_secs: Option<u64>,
+    commands: Option<HashMap<String, ModelSettings>>,
+    stream: Option<bool>,
+    language: Option<String>,
     model_url: Option<String>,
     cria_port: Option<u16>,
+    max_chunk_tokens: Option<usize>,
+    skip_generated: Option<bool>,
+    context: Option<ContextMode>,
+    max_context_tokens: Option<usize>,
+    max_context_file_tokens: Option<usize>,
+    invalid_citations: Option<CitationPolicy>,
+    include_untracked: Option<bool>,
+    max_untracked_bytes: Option<u64>,
+    vcs_backend: Option<VcsBackend>,
+    fail_on: Option<Severity>,
+    guidelines: Option<Vec<String>>,
+    guideline_sets: Option<Vec<GuidelineSet>>,
+    focus: Option<Vec<String>>,
+    review_passes: Option<Vec<FocusPass>>,
+    hook_fail_on: Option<Severity>,
+    commit_conventional: Option<bool>,
+    commit_max_subject_length: Option<usize>,
 }
 
 /// The configuration type.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
-    pub openai_endpoint: String,
     pub mode: Mode,
     pub data_path: String,
 
+    /// Which API serves the model.
+    pub provider: Provider,
+    /// The base URL of the provider's API (e.g., `https://api.openai.com`).
+    pub provider_url: String,
+    /// The model to use (e.g., `gpt-4`), unless the command overrides it.
+    pub model: String,
+    /// The settings of the model (e.g., the temperature) for every command.
+    pub model_settings: ModelSettings,
+    /// The settings of the model for specific commands (e.g., `commit-msg`), which override the `model_settings`.
+    pub commands: HashMap<String, ModelSettings>,
+    /// Whether responses are streamed (and shown as they arrive) when running in a terminal.
+    pub stream: bool,
+    /// The (natural) language that responses are written in (e.g., `German`), unless it is English.
+    pub language: Option<String>,
+
     pub cria_port: Option<u16>,
     pub openai_key: Option<String>,
+    pub anthropic_key: Option<String>,
     pub model_url: Option<String>,
     pub model_path: Option<String>,
+
+    /// The maximum number of tokens of diff to send in a single request (counted by the provider if it can count them,
+    /// e.g., Anthropic, and estimated otherwise).
+    pub max_chunk_tokens: usize,
+    /// Whether to skip binary, lockfile, generated, and minified files in reviews.
+    pub skip_generated: bool,
+    /// How much surrounding code to send along with each changed file.
+    pub context: ContextMode,
+    /// The maximum number of (estimated) tokens of context to send across all files.
+    pub max_context_tokens: usize,
+    /// The maximum number of (estimated) tokens of a file for it to be sent whole in [`ContextMode::Auto`].
+    pub max_context_file_tokens: usize,
+    /// What to do with review citations (e.g., `path/file.rs:30`) that do not point into a changed hunk.
+    pub invalid_citations: CitationPolicy,
+    /// Whether to include untracked (and not ignored) files when reviewing the working tree.
+    pub include_untracked: bool,
+    /// The maximum size of an untracked file to include in a review.
+    pub max_untracked_bytes: u64,
+    /// Which implementation is used to read the repository.
+    pub vcs_backend: VcsBackend,
+    /// The lowest severity of finding that makes `review` exit with a failure (if any).
+    pub fail_on: Option<Severity>,
+    /// The paths of the coding guidelines files that are sent along with every review (e.g., `CONTRIBUTING.md`).
+    pub guidelines: Vec<String>,
+    /// The guidelines that are only sent along with reviews of the files matching their globs.
+    pub guideline_sets: Vec<GuidelineSet>,
+    /// The focused review passes (e.g., `security`) that every review runs, instead of a single generic one.
+    pub focus: Vec<String>,
+    /// The custom focused review passes, which can be selected (by name) in `focus`.
+    pub review_passes: Vec<FocusPass>,
+    /// The lowest severity of review suggestion that makes the `pre-commit` and `pre-push` hooks fail.
+    pub hook_fail_on: Severity,
+    /// Whether proposed commit messages follow Conventional Commits.
+    pub commit_conventional: bool,
+    /// The maximum number of characters in the subject line of a proposed commit message.
+    pub commit_max_subject_length: usize,
 }
 
 impl Config {
@@ -39,33 +143,144 @@ impl Config {
         let optional_config: OptionalConfig = builder.build()?.try_deserialize()?;
 
         let mode = optional_config.mode.unwrap_or(mode);
+        let provider = optional_config.provider.unwrap_or_default();
 
-        let openai_endpoint = match mode {
-            Mode::LocalCpu | Mode::LocalGpu => {
-                println!("{} Running in local mode.", yansi::Paint::green("✅"));
-                "http://localhost:3000".to_string()
-            },
-            Mode::OpenAi => {
-                println!("{} Running in OpenAI mode.", yansi::Paint::green("✅"));
-                "https://api.openai.com".to_string()
-            },
+        // The local modes serve the model from the Cria server, which has an OpenAI-compatible API.
+        let default_url = match (mode, provider) {
+            (Mode::LocalCpu | Mode::LocalGpu, Provider::OpenAi) => DEFAULT_LOCAL_URL,
+            (_, Provider::OpenAi) => DEFAULT_OPENAI_URL,
+            (_, Provider::Anthropic) => DEFAULT_ANTHROPIC_URL,
+            (_, Provider::Ollama) => DEFAULT_OLLAMA_URL,
         };
 
+        let default_model = match provider {
+            Provider::OpenAi => DEFAULT_OPENAI_MODEL,
+            Provider::Anthropic => DEFAULT_ANTHROPIC_MODEL,
+            Provider::Ollama => DEFAULT_OLLAMA_MODEL,
+        };
+
+        match mode {
+            Mode::LocalCpu | Mode::LocalGpu => eprintln!("{} Running in local mode.", yansi::Paint::green("✅")),
+            Mode::OpenAi => eprintln!("{} Running in {} mode.", yansi::Paint::green("✅"), provider),
+        }
+
         let model_path = optional_config.model_url.as_ref().map(|url| {
-            let file_name = url.split('/').last().unwrap();
+            let file_name = url.split('/').next_back().unwrap();
             format!("{}/{}", data_path, file_name)
         });
 
         let config = Config {
-            openai_endpoint,
+            provider,
+            provider_url: optional_config.provider_url.unwrap_or_else(|| default_url.to_string()),
+            model: optional_config.model.unwrap_or_else(|| default_model.to_string()),
+            model_settings: ModelSettings {
+                model: None,
+                temperature: optional_config.temperature,
+                top_p: optional_config.top_p,
+                max_tokens: optional_config.max_tokens,
+                seed: optional_config.seed,
+                stop: optional_config.stop,
+                timeout_secs: optional_config.timeout_secs,
+            },
+            commands: optional_config.commands.unwrap_or_default(),
+            stream: optional_config.stream.unwrap_or(true),
+            language: optional_config.language,
             openai_key: optional_config.openai_key,
+            anthropic_key: optional_config.anthropic_key,
             mode,
             data_path: data_path.to_string(),
             model_url: optional_config.model_url,
             cria_port: optional_config.cria_port,
-            model_path
+            model_path,
+            max_chunk_tokens: optional_config.max_chunk_tokens.unwrap_or(DEFAULT_MAX_CHUNK_TOKENS),
+            skip_generated: optional_config.skip_generated.unwrap_or(true),
+            context: optional_config.context.unwrap_or_default(),
+            max_context_tokens: optional_config.max_context_tokens.unwrap_or(DEFAULT_MAX_CONTEXT_TOKENS),
+            max_context_file_tokens: optional_config.max_context_file_tokens.unwrap_or(DEFAULT_MAX_CONTEXT_FILE_TOKENS),
+            invalid_citations: optional_config.invalid_citations.unwrap_or_default(),
+            include_untracked: optional_config.include_untracked.unwrap_or(false),
+            max_untracked_bytes: optional_config.max_untracked_bytes.unwrap_or(DEFAULT_MAX_UNTRACKED_BYTES),
+            vcs_backend: optional_config.vcs_backend.unwrap_or_default(),
+            fail_on: optional_config.fail_on,
+            guidelines: optional_config.guidelines.unwrap_or_else(|| vec![format!("{}/{}", data_path, DEFAULT_GUIDELINES_FILE_NAME)]),
+            guideline_sets: optional_config.guideline_sets.unwrap_or_default(),
+            focus: optional_config.focus.unwrap_or_default(),
+            review_passes: optional_config.review_passes.unwrap_or_default(),
+            hook_fail_on: optional_config.hook_fail_on.unwrap_or(DEFAULT_HOOK_FAIL_ON),
+            commit_conventional: optional_config.commit_conventional.unwrap_or(false),
+            commit_max_subject_length: optional_config.commit_max_subject_length.unwrap_or(DEFAULT_COMMIT_MAX_SUBJECT_LENGTH),
         };
 
         Ok(config)
     }
+
+    /// Applies the model settings from the command line, which take precedence over those of every command.
+    pub fn with_overrides(mut self, overrides: &ModelSettings) -> Self {
+        self.model_settings = overrides.clone().or(&self.model_settings);
+
+        for settings in self.commands.values_mut() {
+            *settings = overrides.clone().or(settings);
+        }
+
+        self
+    }
+
+    /// The model settings for the command (e.g., `review`): its own settings, then those for every command, and then
+    /// the default model.
+    pub fn settings(&self, command: &str) -> ModelSettings {
+        let defaults = ModelSettings { model: Some(self.model.clone()), ..Default::default() };
+
+        // Accept `commit_msg` for `commit-msg` (as TOML users tend to write it).
+        let settings = self.commands.iter().find(|(name, _)| name.replace('_', "-") == command).map(|(_, s)| s.clone());
+
+        settings.unwrap_or_default().or(&self.model_settings).or(&defaults)
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_model_settings_by_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+
+        std::fs::write(dir.path().join("config.toml"), "\
+model = \"gpt-4o\"
+temperature = 0.5
+max_tokens = 1000
+seed = 7
+
+[commands.review]
+model = \"o3\"
+temperature = 0.1
+
+[commands.commit_msg]
+max_tokens = 100
+").unwrap();
+
+        let config = Config::new(dir.path().to_str().unwrap(), Mode::OpenAi).unwrap();
+
+        // A command's own settings come first, then those for every command, and then the default model.
+        let review = config.settings("review");
+        assert_eq!((review.model.as_deref(), review.temperature, review.max_tokens, review.seed), (Some("o3"), Some(0.1), Some(1000), Some(7)));
+
+        let commit = config.settings("commit-msg");
+        assert_eq!((commit.model.as_deref(), commit.temperature, commit.max_tokens), (Some("gpt-4o"), Some(0.5), Some(100)));
+
+        let ask = config.settings("ask");
+        assert_eq!(ask, ModelSettings { model: Some("gpt-4o".to_string()), temperature: Some(0.5), max_tokens: Some(1000), seed: Some(7), ..Default::default() });
+
+        // The command line beats both.
+        let flags = ModelSettings { model: Some("flag-model".to_string()), max_tokens: Some(10), ..Default::default() };
+        let config = config.with_overrides(&flags);
+
+        let review = config.settings("review");
+        assert_eq!((review.model.as_deref(), review.temperature, review.max_tokens, review.seed), (Some("flag-model"), Some(0.1), Some(10), Some(7)));
+
+        let ask = config.settings("ask");
+        assert_eq!((ask.model.as_deref(), ask.temperature, ask.max_tokens), (Some("flag-model"), Some(0.5), Some(10)));
+    }
 }
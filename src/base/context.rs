@@ -0,0 +1,389 @@
+//! The context expansion module.
+//!
+//! Attaches extra context to each changed file (either the whole new file, or the items enclosing each hunk),
+//! so that the model does not need to guess about code that sits just outside of the diff.
+
+use std::{fmt::{self, Display, Formatter}, ops::RangeInclusive};
+
+use super::{diff::{FileDiff, Hunk, LineKind}, types::{estimate_tokens, ContextMode}};
+
+// Statics.
+
+/// The keywords that (roughly) start an item (function, type, impl, class, ...) across common languages.
+static ITEM_KEYWORDS: &[&str] = &[
+    "fn ", "impl ", "impl<", "struct ", "enum ", "trait ", "mod ", "union ", "macro_rules!",
+    "def ", "class ", "function ", "func ", "interface ", "type ", "namespace ", "module ", "object ", "record ", "sub ",
+];
+
+/// The modifiers that may precede an item keyword.
+static ITEM_MODIFIERS: &[&str] = &[
+    "pub ", "pub(crate) ", "pub(super) ", "async ", "const ", "unsafe ", "extern ", "export ", "default ", "static ", "public ", "private ", "protected ",
+    "internal ", "abstract ", "final ", "sealed ", "override ", "virtual ", "partial ", "open ", "data ", "inline ",
+];
+
+/// The maximum number of lines in a single enclosing item (anything larger is truncated).
+static MAX_ITEM_LINES: usize = 200;
+
+// Types.
+
+/// Additional context for a single changed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileContext {
+    pub path: String,
+    /// The (inclusive, 1-based) line ranges of the new file that are included.
+    pub ranges: Vec<RangeInclusive<usize>>,
+    /// The rendered context (numbered lines of the new file).
+    pub text: String,
+}
+
+impl FileContext {
+    pub fn tokens(&self) -> usize {
+        estimate_tokens(&self.text)
+    }
+}
+
+impl Display for FileContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let ranges = self.ranges.iter().map(|r| format!("{}-{}", r.start(), r.end())).collect::<Vec<_>>().join(", ");
+
+        writeln!(f, "`{}` (lines {}):", self.path, ranges)?;
+        writeln!(f, "```")?;
+        write!(f, "{}", self.text)?;
+        writeln!(f, "```")
+    }
+}
+
+// Expansion.
+
+/// Builds the context for the specified file, given the full contents of its new version.
+///
+/// In [`ContextMode::Auto`], the whole file is used if it fits within `max_file_tokens`, and the enclosing
+/// items otherwise.
+pub fn expand(file: &FileDiff, contents: &str, mode: ContextMode, max_file_tokens: usize) -> Option<FileContext> {
+    if file.is_deleted || file.is_binary || file.hunks.is_empty() {
+        return None;
+    }
+
+    let lines = contents.lines().collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    let whole_file = estimate_tokens(contents) <= max_file_tokens;
+
+    let ranges = match mode {
+        ContextMode::None => return None,
+        ContextMode::File => vec![1..=lines.len()],
+        ContextMode::Auto if whole_file => vec![1..=lines.len()],
+        ContextMode::Auto | ContextMode::Item => enclosing_items(file, &lines),
+    };
+
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let width = lines.len().to_string().len();
+    let mut text = String::new();
+
+    for (k, range) in ranges.iter().enumerate() {
+        if k > 0 {
+            text.push_str("...\n");
+        }
+
+        for n in range.clone() {
+            text.push_str(&format!("{:>width$} | {}\n", n, lines[n - 1], width = width));
+        }
+    }
+
+    Some(FileContext { path: file.path().to_string(), ranges, text })
+}
+
+// Helpers.
+
+/// Finds the (merged) line ranges of the items that enclose the changed lines of each hunk.
+fn enclosing_items(file: &FileDiff, lines: &[&str]) -> Vec<RangeInclusive<usize>> {
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+
+    for hunk in &file.hunks {
+        let hunk_range = hunk.new_range();
+
+        for changed in changed_lines(hunk) {
+            let changed = (changed as usize).clamp(1, lines.len());
+
+            // Lines of an item that was already found do not need to be looked up again.
+            if ranges.iter().any(|r| r.contains(&changed)) {
+                continue;
+            }
+
+            let Some(range) = enclosing_item(lines, changed) else {
+                continue;
+            };
+
+            // Skip items that are already entirely visible in the hunk itself.
+            if hunk_range.contains(&(*range.start() as u32)) && hunk_range.contains(&(*range.end() as u32)) {
+                continue;
+            }
+
+            ranges.push(range);
+        }
+    }
+
+    ranges.sort_by_key(|r| *r.start());
+
+    let mut merged: Vec<RangeInclusive<usize>> = Vec::new();
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= *last.end() + 1 => *last = *last.start()..=(*last.end()).max(*range.end()),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// The (1-based) lines of the new file where the hunk changes something: its added lines, and the line that follows
+/// each run of removed lines.
+fn changed_lines(hunk: &Hunk) -> Vec<u32> {
+    let mut changed = Vec::new();
+    let mut next = hunk.new_start;
+
+    for line in &hunk.lines {
+        match (line.kind, line.new_line) {
+            (LineKind::Removed, _) => {
+                if changed.last() != Some(&next) {
+                    changed.push(next);
+                }
+            },
+            (LineKind::Added, Some(n)) => {
+                changed.push(n);
+                next = n + 1;
+            },
+            (_, Some(n)) => next = n + 1,
+            (_, None) => {},
+        }
+    }
+
+    if changed.is_empty() {
+        changed.push(hunk.new_start);
+    }
+
+    changed
+}
+
+/// Finds the item that encloses the specified (1-based) line.
+fn enclosing_item(lines: &[&str], line: usize) -> Option<RangeInclusive<usize>> {
+    let changed_indent = lines[line - 1..].iter().find(|l| !l.trim().is_empty()).map(|l| indent(l)).unwrap_or(0);
+
+    // Walk upwards to the closest item header that is not indented further than the changed line.
+    let start = (1..=line).rev().find(|&n| {
+        let l = lines[n - 1];
+        is_item_start(l) && (indent(l) < changed_indent || n == line)
+    })?;
+
+    let start_indent = indent(lines[start - 1]);
+    let max_end = (start + MAX_ITEM_LINES - 1).min(lines.len());
+
+    // For brace-delimited languages, match braces; otherwise, use indentation.
+    let mut depth = 0i32;
+    let mut saw_brace = false;
+
+    for n in start..=max_end {
+        let l = lines[n - 1];
+
+        for c in code(l).chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    saw_brace = true;
+                },
+                '}' => depth -= 1,
+                _ => {},
+            }
+        }
+
+        if saw_brace && depth <= 0 {
+            return Some(start..=n);
+        }
+
+        // Without braces (e.g., Python), the item ends before the next line at (or above) the header's indentation.
+        if !saw_brace && n > start && !l.trim().is_empty() && indent(l) <= start_indent && !l.trim_start().starts_with(['{', ')', ']']) && !l.trim_start().starts_with("where") {
+            return Some(start..=(n - 1));
+        }
+    }
+
+    Some(start..=max_end)
+}
+
+/// The code of the line, without its string and character literals and its trailing `//` comment (so that the braces
+/// in them are not matched).
+fn code(line: &str) -> String {
+    let mut code = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        },
+                        '"' => break,
+                        _ => {},
+                    }
+                }
+            },
+            // A character literal (e.g., `'{'` or `'\''`), rather than a lifetime (e.g., `'a`).
+            '\'' if is_char_literal(chars.clone()) => {
+                if chars.next() == Some('\\') {
+                    chars.next();
+                }
+
+                chars.by_ref().find(|&c| c == '\'');
+            },
+            '/' if chars.peek() == Some(&'/') => break,
+            _ => code.push(c),
+        }
+    }
+
+    code
+}
+
+fn is_char_literal(mut rest: impl Iterator<Item = char>) -> bool {
+    match rest.next() {
+        Some('\\') => true,
+        Some(_) => rest.next() == Some('\''),
+        None => false,
+    }
+}
+
+fn is_item_start(line: &str) -> bool {
+    let mut rest = line.trim_start();
+
+    // Skip decorators, attributes, and comments.
+    if rest.starts_with('@') || rest.starts_with("#[") || rest.starts_with("//") || rest.starts_with('#') {
+        return false;
+    }
+
+    while let Some(modifier) = ITEM_MODIFIERS.iter().find(|m| rest.starts_with(*m)) {
+        rest = &rest[modifier.len()..];
+    }
+
+    ITEM_KEYWORDS.iter().any(|k| rest.starts_with(k))
+}
+
+fn indent(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::base::diff::Diff;
+
+    use super::*;
+
+    static SOURCE: &str = r#"use std::fmt;
+
+fn first() {
+    let open = "{";
+    let close = '}';
+    println!("{}", open);
+}
+
+fn second<'a>(name: &'a str) -> &'a str {
+    // A brace in a comment: {
+    name
+}
+
+fn third() {
+    3;
+}
+"#;
+
+    fn file(diff: &str) -> FileDiff {
+        Diff::parse(diff).unwrap().files.remove(0)
+    }
+
+    #[test]
+    fn finds_the_items_of_every_changed_line() {
+        // One hunk that edits both `first` and `second`.
+        let file = file("\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -5,7 +5,7 @@
+-    let close = '{';
++    let close = '}';
+     println!(\"{}\", open);
+ }
+ 
+ fn second<'a>(name: &'a str) -> &'a str {
+     // A brace in a comment: {
+-    \"name\"
++    name
+");
+
+        let context = expand(&file, SOURCE, ContextMode::Item, 0).unwrap();
+
+        assert_eq!(context.ranges, vec![3..=7, 9..=12]);
+        assert!(context.text.starts_with(" 3 | fn first() {\n"));
+        assert!(context.text.contains(" 7 | }\n...\n 9 | fn second"));
+        assert!(context.text.ends_with("12 | }\n"));
+
+        // The whole file fits.
+        assert_eq!(expand(&file, SOURCE, ContextMode::Auto, 1000).unwrap().ranges, vec![1..=16]);
+        assert_eq!(expand(&file, SOURCE, ContextMode::None, 1000), None);
+    }
+
+    #[test]
+    fn merges_adjacent_items_and_skips_visible_ones() {
+        let lines = ["fn a() {", "    1", "}", "fn b() {", "    2", "}", "fn c() {", "    3", "}"];
+
+        // A deletion counts at the line that follows it; `c` is entirely visible in its hunk.
+        let file = file("\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,1 +2,1 @@
+-    0
++    1
+@@ -5,2 +5,1 @@
+-    0
+     2
+@@ -8,3 +7,3 @@
+ fn c() {
+-    0
++    3
+ }
+");
+
+        assert_eq!(changed_lines(&file.hunks[1]), vec![5]);
+        assert_eq!(enclosing_items(&file, &lines), vec![1..=6]);
+    }
+
+    #[test]
+    fn matches_braces_outside_of_literals_and_comments() {
+        let lines = SOURCE.lines().collect::<Vec<_>>();
+
+        assert_eq!(enclosing_item(&lines, 5), Some(3..=7));
+        assert_eq!(enclosing_item(&lines, 11), Some(9..=12));
+        assert_eq!(enclosing_item(&lines, 1), None);
+
+        assert_eq!(code(r#"let s = "}{\"{"; // {"#), "let s = ; ");
+        assert_eq!(code(r"match c { '{' | '\'' => {}, _ => {} }"), "match c {  |  => {}, _ => {} }");
+        assert_eq!(code("fn f<'a>(s: &'a str) {"), "fn f<'a>(s: &'a str) {");
+    }
+
+    #[test]
+    fn falls_back_to_indentation() {
+        let lines = ["class A:", "    def f(self):", "        return [", "            1, 2]", "", "    def g(self):", "        return 2", "", "def h():", "    pass"];
+
+        assert_eq!(enclosing_item(&lines, 4), Some(2..=5));
+        assert_eq!(enclosing_item(&lines, 7), Some(6..=8));
+        assert_eq!(enclosing_item(&lines, 10), Some(9..=10));
+    }
+}
@@ -0,0 +1,994 @@
+//! The unified diff module.
+//!
+//! Parses the output of `git diff` (and friends) into a typed model of files, hunks, and lines
+//! so that the rest of the pipeline can reason about paths and line numbers.
+
+use std::fmt::{self, Display, Formatter};
+
+use super::types::{estimate_tokens, Res};
+
+// Types.
+
+/// A parsed unified diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub files: Vec<FileDiff>,
+}
+
+/// The changes made to a single file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiff {
+    /// The path before the change (`None` if the file was added).
+    pub old_path: Option<String>,
+    /// The path after the change (`None` if the file was deleted).
+    pub new_path: Option<String>,
+    pub is_new: bool,
+    pub is_deleted: bool,
+    pub is_rename: bool,
+    pub is_binary: bool,
+    /// The raw header lines (e.g., `diff --git ...`, `index ...`, `--- ...`, `+++ ...`).
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A single `@@ -a,b +c,d @@` hunk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// The (optional) section heading that follows the range (usually the enclosing function).
+    pub section: String,
+    pub lines: Vec<Line>,
+}
+
+/// A single line within a [`Hunk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub kind: LineKind,
+    pub content: String,
+    /// The line number in the old file (for context and removed lines).
+    pub old_line: Option<u32>,
+    /// The line number in the new file (for context and added lines).
+    pub new_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+    /// The `\ No newline at end of file` marker.
+    NoNewline,
+}
+
+// Parsing.
+
+impl Diff {
+    /// Parses the specified unified diff text.
+    ///
+    /// Handles both `git diff` output (with `diff --git` headers) and plain `diff -u` output.  Any text that
+    /// does not belong to a file (e.g., a commit message preceding a patch) is ignored.
+    pub fn parse(text: &str) -> Res<Self> {
+        let mut files = Vec::new();
+        let mut current: Option<FileDiff> = None;
+        let mut hunk: Option<(Hunk, u32, u32)> = None;
+
+        for raw in text.lines() {
+            // If we are within a hunk, consume lines until the hunk's counts are exhausted.
+            if let Some((h, old_remaining, new_remaining)) = hunk.as_mut() {
+                if *old_remaining > 0 || *new_remaining > 0 || raw.starts_with('\\') {
+                    let old_line = h.old_start + h.old_lines - *old_remaining;
+                    let new_line = h.new_start + h.new_lines - *new_remaining;
+
+                    let (kind, content) = match raw.chars().next() {
+                        Some('+') => (LineKind::Added, &raw[1..]),
+                        Some('-') => (LineKind::Removed, &raw[1..]),
+                        Some('\\') => (LineKind::NoNewline, raw),
+                        Some(' ') => (LineKind::Context, &raw[1..]),
+                        // Some tools strip the trailing whitespace of empty context lines.
+                        None => (LineKind::Context, ""),
+                        Some(_) => return Err(anyhow::Error::msg(format!("Unexpected line in hunk: `{}`.", raw))),
+                    };
+
+                    let line = match kind {
+                        LineKind::Context => {
+                            *old_remaining = old_remaining.saturating_sub(1);
+                            *new_remaining = new_remaining.saturating_sub(1);
+                            Line { kind, content: content.to_string(), old_line: Some(old_line), new_line: Some(new_line) }
+                        },
+                        LineKind::Added => {
+                            *new_remaining = new_remaining.saturating_sub(1);
+                            Line { kind, content: content.to_string(), old_line: None, new_line: Some(new_line) }
+                        },
+                        LineKind::Removed => {
+                            *old_remaining = old_remaining.saturating_sub(1);
+                            Line { kind, content: content.to_string(), old_line: Some(old_line), new_line: None }
+                        },
+                        LineKind::NoNewline => Line { kind, content: content.to_string(), old_line: None, new_line: None },
+                    };
+
+                    h.lines.push(line);
+                    continue;
+                }
+
+                let (h, _, _) = hunk.take().unwrap();
+                current.as_mut().unwrap().hunks.push(h);
+            }
+
+            if let Some(rest) = raw.strip_prefix("diff --git ") {
+                files.extend(current.take());
+
+                let (old_path, new_path) = parse_git_header_paths(rest);
+                current = Some(FileDiff {
+                    old_path,
+                    new_path,
+                    header: vec![raw.to_string()],
+                    ..Default::default()
+                });
+
+                continue;
+            }
+
+            if raw.starts_with("@@ ") {
+                let file = current.as_mut().ok_or_else(|| anyhow::Error::msg("Found a hunk without a file header."))?;
+                let h = parse_hunk_header(raw)?;
+                let (old_remaining, new_remaining) = (h.old_lines, h.new_lines);
+
+                if file.old_path.is_none() && file.new_path.is_none() {
+                    return Err(anyhow::Error::msg("Found a hunk without file paths."));
+                }
+
+                hunk = Some((h, old_remaining, new_remaining));
+                continue;
+            }
+
+            if let Some(path) = raw.strip_prefix("--- ") {
+                // A plain `diff -u` file starts with `---` rather than `diff --git`.
+                let starts_new_file = match current.as_ref() {
+                    None => true,
+                    Some(file) => !file.hunks.is_empty() || file.header.iter().any(|l| l.starts_with("--- ")),
+                };
+
+                if starts_new_file {
+                    files.extend(current.take());
+                    current = Some(FileDiff::default());
+                }
+
+                let file = current.as_mut().unwrap();
+                file.header.push(raw.to_string());
+                file.old_path = parse_marker_path(path, "a/");
+                file.is_new |= file.old_path.is_none();
+
+                continue;
+            }
+
+            let Some(file) = current.as_mut() else {
+                continue;
+            };
+
+            // Once a file has hunks, anything else is trailing noise (e.g., a patch signature).
+            if !file.hunks.is_empty() {
+                continue;
+            }
+
+            if let Some(path) = raw.strip_prefix("+++ ") {
+                file.new_path = parse_marker_path(path, "b/");
+                file.is_deleted |= file.new_path.is_none();
+            } else if raw.starts_with("new file mode") {
+                file.is_new = true;
+                file.old_path = None;
+            } else if raw.starts_with("deleted file mode") {
+                file.is_deleted = true;
+                file.new_path = None;
+            } else if let Some(path) = raw.strip_prefix("rename from ") {
+                file.is_rename = true;
+                file.old_path = Some(unquote(path));
+            } else if let Some(path) = raw.strip_prefix("rename to ") {
+                file.is_rename = true;
+                file.new_path = Some(unquote(path));
+            } else if let Some(path) = raw.strip_prefix("copy from ") {
+                file.old_path = Some(unquote(path));
+            } else if let Some(path) = raw.strip_prefix("copy to ") {
+                file.new_path = Some(unquote(path));
+            } else if raw.starts_with("Binary files ") || raw.starts_with("GIT binary patch") {
+                file.is_binary = true;
+            } else if !is_known_header_line(raw) {
+                // Unknown text between files (e.g., an email body); drop it.
+                continue;
+            }
+
+            file.header.push(raw.to_string());
+        }
+
+        if let Some((h, _, _)) = hunk.take() {
+            current.as_mut().unwrap().hunks.push(h);
+        }
+
+        files.extend(current.take());
+
+        Ok(Diff { files })
+    }
+}
+
+// Construction.
+
+impl FileDiff {
+    /// Synthesizes the diff of a newly added file (e.g., an untracked file) from its contents.
+    pub fn new_file(path: &str, contents: &[u8]) -> Self {
+        let mut file = FileDiff {
+            new_path: Some(path.to_string()),
+            is_new: true,
+            header: vec![format!("diff --git a/{} b/{}", path, path), "new file mode 100644".to_string()],
+            ..Default::default()
+        };
+
+        let text = match std::str::from_utf8(contents) {
+            Ok(text) if !text.contains('\0') => text,
+            _ => {
+                file.is_binary = true;
+                file.header.push(format!("Binary files /dev/null and b/{} differ", path));
+                return file;
+            },
+        };
+
+        if text.is_empty() {
+            return file;
+        }
+
+        file.header.push("--- /dev/null".to_string());
+        file.header.push(format!("+++ b/{}", path));
+
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(k, l)| Line { kind: LineKind::Added, content: l.to_string(), old_line: None, new_line: Some(k as u32 + 1) })
+            .collect::<Vec<_>>();
+
+        let new_lines = lines.len() as u32;
+
+        if !text.ends_with('\n') {
+            lines.push(Line { kind: LineKind::NoNewline, content: "\\ No newline at end of file".to_string(), old_line: None, new_line: None });
+        }
+
+        file.hunks.push(Hunk { old_start: 0, old_lines: 0, new_start: 1, new_lines, section: String::new(), lines });
+
+        file
+    }
+}
+
+// Accessors.
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Finds the file with the specified path (or whose path ends with the specified path).
+    pub fn find_file(&self, path: &str) -> Option<&FileDiff> {
+        let path = path.trim_start_matches("./");
+
+        self.files.iter().find(|f| f.path() == path).or_else(|| self.files.iter().find(|f| f.path().ends_with(&format!("/{}", path))))
+    }
+
+    /// Whether the specified new-file line falls within one of the hunks of the specified file.
+    pub fn contains_line(&self, path: &str, line: u32) -> bool {
+        self.find_file(path).is_some_and(|f| f.hunks.iter().any(|h| h.new_range().contains(&line)))
+    }
+}
+
+impl FileDiff {
+    /// The most relevant path of the file (the new path, or the old path for deletions).
+    pub fn path(&self) -> &str {
+        self.new_path.as_deref().or(self.old_path.as_deref()).unwrap_or_default()
+    }
+}
+
+impl Hunk {
+    /// The (inclusive) range of new-file line numbers that this hunk covers.
+    pub fn new_range(&self) -> std::ops::RangeInclusive<u32> {
+        self.new_start..=(self.new_start + self.new_lines.max(1) - 1)
+    }
+}
+
+// Chunking.
+
+impl Diff {
+    /// Splits the diff into chunks whose rendered size is (roughly) below the specified token budget.
+    ///
+    /// Files are packed together whenever possible; files that are too large on their own are split by hunk,
+    /// and hunks that are too large on their own are split into smaller hunks.
+    pub fn chunk(&self, max_tokens: usize) -> Vec<Diff> {
+        self.chunk_with(max_tokens, |_| 0)
+    }
+
+    /// Like [`Diff::chunk`], but also budgets for the tokens that are sent along with each chunk, which depend on
+    /// the files of the chunk (e.g., the guidelines that apply to them).
+    pub fn chunk_with(&self, max_tokens: usize, overhead: impl Fn(&[FileDiff]) -> usize) -> Vec<Diff> {
+        let mut chunks = Vec::new();
+        let mut current = Diff::default();
+        let mut current_tokens = 0;
+
+        let files = self.files.iter().flat_map(|f| f.split(max_tokens.saturating_sub(overhead(std::slice::from_ref(f))).max(1)));
+
+        for file in files {
+            let tokens = estimate_tokens(&file.to_string());
+
+            current.files.push(file);
+
+            if current.files.len() > 1 && current_tokens + tokens + overhead(&current.files) > max_tokens {
+                let file = current.files.pop().unwrap_or_default();
+                chunks.push(std::mem::replace(&mut current, Diff { files: vec![file] }));
+                current_tokens = 0;
+            }
+
+            current_tokens += tokens;
+        }
+
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+
+        chunks
+    }
+}
+
+impl FileDiff {
+    /// Splits the file into several files (sharing the same header) whose hunks fit the specified token budget.
+    fn split(&self, max_tokens: usize) -> Vec<FileDiff> {
+        if estimate_tokens(&self.to_string()) <= max_tokens {
+            return vec![self.clone()];
+        }
+
+        let header_tokens = estimate_tokens(&self.header.join("\n"));
+        let budget = max_tokens.saturating_sub(header_tokens).max(1);
+
+        let mut files = Vec::new();
+        let mut current = FileDiff { hunks: Vec::new(), ..self.clone() };
+        let mut current_tokens = 0;
+
+        for hunk in self.hunks.iter().flat_map(|h| h.split(budget)) {
+            let tokens = estimate_tokens(&hunk.to_string());
+
+            if !current.hunks.is_empty() && current_tokens + tokens > budget {
+                files.push(std::mem::replace(&mut current, FileDiff { hunks: Vec::new(), ..self.clone() }));
+                current_tokens = 0;
+            }
+
+            current.hunks.push(hunk);
+            current_tokens += tokens;
+        }
+
+        if !current.hunks.is_empty() {
+            files.push(current);
+        }
+
+        files
+    }
+}
+
+impl Hunk {
+    /// Splits the hunk into consecutive smaller hunks whose rendered size fits the specified token budget.
+    fn split(&self, max_tokens: usize) -> Vec<Hunk> {
+        if estimate_tokens(&self.to_string()) <= max_tokens {
+            return vec![self.clone()];
+        }
+
+        // An empty range starts at the line before it (e.g., `@@ -5,0 +6,2 @@` adds two lines after line 5).
+        let first = |start: u32, lines: u32| if lines == 0 { start + 1 } else { start };
+        let (mut next_old, mut next_new) = (first(self.old_start, self.old_lines), first(self.new_start, self.new_lines));
+
+        let mut hunks = Vec::new();
+        let mut current = Hunk { old_start: next_old, new_start: next_new, section: self.section.clone(), ..Default::default() };
+        let mut current_tokens = 0;
+
+        for line in &self.lines {
+            let tokens = estimate_tokens(&line.to_string()) + 1;
+
+            if !current.lines.is_empty() && line.kind != LineKind::NoNewline && current_tokens + tokens > max_tokens {
+                let next = Hunk { old_start: next_old, new_start: next_new, section: self.section.clone(), ..Default::default() };
+                hunks.push(std::mem::replace(&mut current, next).with_git_starts());
+                current_tokens = 0;
+            }
+
+            match line.kind {
+                LineKind::Context => {
+                    current.old_lines += 1;
+                    current.new_lines += 1;
+                    next_old += 1;
+                    next_new += 1;
+                },
+                LineKind::Added => {
+                    current.new_lines += 1;
+                    next_new += 1;
+                },
+                LineKind::Removed => {
+                    current.old_lines += 1;
+                    next_old += 1;
+                },
+                LineKind::NoNewline => {},
+            }
+
+            current.lines.push(line.clone());
+            current_tokens += tokens;
+        }
+
+        if !current.lines.is_empty() {
+            hunks.push(current.with_git_starts());
+        }
+
+        hunks
+    }
+
+    /// Moves the start of an empty range to the line before it, as git writes it.
+    fn with_git_starts(mut self) -> Hunk {
+        if self.old_lines == 0 {
+            self.old_start = self.old_start.saturating_sub(1);
+        }
+
+        if self.new_lines == 0 {
+            self.new_start = self.new_start.saturating_sub(1);
+        }
+
+        self
+    }
+}
+
+// Rendering.
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            write!(f, "{}", file)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for FileDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+
+        for hunk in &self.hunks {
+            write!(f, "{}", hunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Hunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let range = |start: u32, lines: u32| if lines == 1 { start.to_string() } else { format!("{},{}", start, lines) };
+
+        write!(f, "@@ -{} +{} @@", range(self.old_start, self.old_lines), range(self.new_start, self.new_lines))?;
+
+        if !self.section.is_empty() {
+            write!(f, " {}", self.section)?;
+        }
+
+        writeln!(f)?;
+
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LineKind::Context => write!(f, " {}", self.content),
+            LineKind::Added => write!(f, "+{}", self.content),
+            LineKind::Removed => write!(f, "-{}", self.content),
+            LineKind::NoNewline => write!(f, "{}", self.content),
+        }
+    }
+}
+
+impl Diff {
+    /// Renders the diff with explicit line numbers, so that the model can cite accurate `file:line` locations.
+    ///
+    /// Added and context lines are prefixed with their line number in the new file; removed lines have no number.
+    pub fn to_numbered_string(&self) -> String {
+        let mut out = String::new();
+
+        for file in &self.files {
+            for line in &file.header {
+                out.push_str(line);
+                out.push('\n');
+            }
+
+            let width = file.hunks.iter().map(|h| (h.new_start + h.new_lines).to_string().len()).max().unwrap_or(1);
+
+            for hunk in &file.hunks {
+                let mut header = hunk.to_string();
+                header.truncate(header.find('\n').unwrap_or(header.len()) + 1);
+                out.push_str(&header);
+
+                for line in &hunk.lines {
+                    let number = line.new_line.map(|n| n.to_string()).unwrap_or_default();
+
+                    let numbered = match line.kind {
+                        LineKind::Context => format!("{:>width$}   {}", number, line.content, width = width),
+                        LineKind::Added => format!("{:>width$} + {}", number, line.content, width = width),
+                        LineKind::Removed => format!("{:>width$} - {}", number, line.content, width = width),
+                        LineKind::NoNewline => format!("{:>width$}   {}", number, line.content, width = width),
+                    };
+
+                    out.push_str(&numbered);
+                    out.push('\n');
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Helpers.
+
+fn parse_hunk_header(raw: &str) -> Res<Hunk> {
+    let err = || anyhow::Error::msg(format!("Invalid hunk header: `{}`.", raw));
+
+    let rest = raw.strip_prefix("@@ -").ok_or_else(err)?;
+    let (ranges, section) = rest.split_once(" @@").ok_or_else(err)?;
+    let (old, new) = ranges.split_once(" +").ok_or_else(err)?;
+
+    let parse_range = |range: &str| -> Res<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, lines)) => Ok((start.parse().map_err(|_| err())?, lines.parse().map_err(|_| err())?)),
+            None => Ok((range.parse().map_err(|_| err())?, 1)),
+        }
+    };
+
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+
+    Ok(Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+fn parse_git_header_paths(rest: &str) -> (Option<String>, Option<String>) {
+    // Paths with special characters (e.g., spaces, or non-ASCII) are C-quoted (e.g., `"a/x y"`), and the others are not.
+    let split = if let Some(quoted) = rest.strip_prefix('"') {
+        closing_quote(quoted).map(|end| (&rest[..end + 2], rest[end + 2..].trim_start()))
+    } else if rest.ends_with('"') {
+        rest.rfind(" \"").map(|start| (&rest[..start], &rest[start + 1..]))
+    } else {
+        rest.split_once(" b/").map(|(old, _)| (old, &rest[old.len() + 1..]))
+    };
+
+    match split {
+        Some((old, new)) => (Some(strip_prefix(unquote(old), "a/")), Some(strip_prefix(unquote(new), "b/"))),
+        None => (None, None),
+    }
+}
+
+/// Finds the index of the closing quote of a C-quoted string (without its opening quote).
+fn closing_quote(quoted: &str) -> Option<usize> {
+    let mut escaped = false;
+
+    for (k, c) in quoted.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(k),
+            _ => {},
+        }
+    }
+
+    None
+}
+
+fn strip_prefix(path: String, prefix: &str) -> String {
+    path.strip_prefix(prefix).map(str::to_string).unwrap_or(path)
+}
+
+fn parse_marker_path(path: &str, prefix: &str) -> Option<String> {
+    // Plain `diff -u` output appends a tab and a timestamp.
+    let path = path.split('\t').next().unwrap_or_default().trim_end();
+
+    if path == "/dev/null" {
+        return None;
+    }
+
+    Some(strip_prefix(unquote(path), prefix))
+}
+
+/// Unquotes a C-quoted path (e.g., `"caf\303\251 menu.txt"`), as git writes paths with special characters.
+fn unquote(path: &str) -> String {
+    let Some(inner) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut rest = inner.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+
+        let Some((&escape, tail)) = rest.split_first() else {
+            bytes.push(b);
+            break;
+        };
+
+        rest = tail;
+
+        match escape {
+            b'n' => bytes.push(b'\n'),
+            b't' => bytes.push(b'\t'),
+            b'r' => bytes.push(b'\r'),
+            b'a' => bytes.push(0x07),
+            b'b' => bytes.push(0x08),
+            b'f' => bytes.push(0x0c),
+            b'v' => bytes.push(0x0b),
+            // Non-ASCII bytes are written as three octal digits.
+            b'0'..=b'7' => {
+                let digits = std::iter::once(escape).chain(rest.iter().copied().take(2).take_while(|d| (b'0'..=b'7').contains(d))).collect::<Vec<_>>();
+                rest = &rest[digits.len() - 1..];
+                bytes.push(digits.iter().fold(0u8, |n, d| n.wrapping_mul(8).wrapping_add(d - b'0')));
+            },
+            _ => bytes.push(escape),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn is_known_header_line(raw: &str) -> bool {
+    const PREFIXES: &[&str] = &["index ", "old mode ", "new mode ", "similarity index ", "dissimilarity index ", "copy from ", "copy to "];
+
+    PREFIXES.iter().any(|p| raw.starts_with(p))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Diff {
+        Diff::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parses_modified_files_with_line_numbers() {
+        let diff = parse(MODIFIED);
+        let file = &diff.files[0];
+
+        assert_eq!(file.old_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(file.new_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(file.hunks.len(), 1);
+
+        let hunk = &file.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 3, 1, 4));
+        assert_eq!(hunk.section, "fn main() {");
+
+        let numbers = hunk.lines.iter().map(|l| (l.kind, l.old_line, l.new_line)).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![
+            (LineKind::Context, Some(1), Some(1)),
+            (LineKind::Removed, Some(2), None),
+            (LineKind::Added, None, Some(2)),
+            (LineKind::Added, None, Some(3)),
+            (LineKind::Context, Some(3), Some(4)),
+        ]);
+
+        // The typed model renders back to the original text.
+        assert_eq!(diff.to_string(), MODIFIED);
+    }
+
+    #[test]
+    fn parses_renames() {
+        let diff = parse(RENAMED);
+
+        assert_eq!(diff.files.len(), 2);
+
+        let edited = &diff.files[0];
+        assert!(edited.is_rename);
+        assert_eq!(edited.old_path.as_deref(), Some("old name.rs"));
+        assert_eq!(edited.new_path.as_deref(), Some("new name.rs"));
+        assert_eq!(edited.hunks.len(), 1);
+
+        let pure = &diff.files[1];
+        assert!(pure.is_rename);
+        assert_eq!(pure.old_path.as_deref(), Some("a.rs"));
+        assert_eq!(pure.path(), "b.rs");
+        assert!(pure.hunks.is_empty());
+    }
+
+    #[test]
+    fn parses_copies() {
+        let diff = parse(COPIED);
+        let file = &diff.files[0];
+
+        assert!(!file.is_rename);
+        assert_eq!(file.old_path.as_deref(), Some("template.rs"));
+        assert_eq!(file.new_path.as_deref(), Some("copy.rs"));
+        assert_eq!(file.hunks[0].lines.iter().filter(|l| l.kind == LineKind::Added).count(), 1);
+    }
+
+    #[test]
+    fn parses_mode_changes() {
+        let diff = parse(MODE_CHANGED);
+        let file = &diff.files[0];
+
+        assert_eq!(file.path(), "run.sh");
+        assert!(file.hunks.is_empty());
+        assert!(file.header.iter().any(|l| l == "new mode 100755"));
+    }
+
+    #[test]
+    fn parses_binary_files() {
+        let diff = parse(BINARY);
+
+        assert_eq!(diff.files.len(), 2);
+        assert!(diff.files.iter().all(|f| f.is_binary && f.hunks.is_empty()));
+        assert_eq!(diff.files[0].path(), "logo.png");
+        assert!(diff.files[1].is_new);
+        assert_eq!(diff.files[1].path(), "icon.ico");
+    }
+
+    #[test]
+    fn parses_missing_trailing_newlines() {
+        let diff = parse(NO_NEWLINE);
+
+        assert_eq!(diff.files.len(), 2);
+
+        let lines = &diff.files[0].hunks[0].lines;
+        assert_eq!(lines.iter().map(|l| l.kind).collect::<Vec<_>>(), vec![LineKind::Removed, LineKind::NoNewline, LineKind::Added, LineKind::NoNewline]);
+        assert_eq!(lines[1].content, "\\ No newline at end of file");
+
+        // The marker does not swallow the next file.
+        assert_eq!(diff.files[1].path(), "b.txt");
+        assert_eq!(diff.files[1].hunks.len(), 1);
+    }
+
+    #[test]
+    fn parses_added_and_deleted_files() {
+        let diff = parse(ADDED_AND_DELETED);
+
+        let added = &diff.files[0];
+        assert!(added.is_new && !added.is_deleted);
+        assert_eq!(added.old_path, None);
+        assert_eq!(added.path(), "new.rs");
+        assert_eq!(added.hunks[0].lines.iter().map(|l| l.new_line).collect::<Vec<_>>(), vec![Some(1), Some(2)]);
+
+        let deleted = &diff.files[1];
+        assert!(deleted.is_deleted && !deleted.is_new);
+        assert_eq!(deleted.new_path, None);
+        assert_eq!(deleted.path(), "gone.rs");
+        assert_eq!(deleted.hunks[0].lines.iter().map(|l| l.old_line).collect::<Vec<_>>(), vec![Some(1)]);
+    }
+
+    #[test]
+    fn parses_quoted_paths() {
+        let diff = parse(QUOTED);
+
+        assert_eq!(diff.files.len(), 3);
+        assert_eq!(diff.files[0].path(), "docs/read me.md");
+        assert_eq!(diff.files[0].old_path.as_deref(), Some("docs/read me.md"));
+
+        // A binary file has no `---`/`+++` lines, so its path only comes from the `diff --git` header.
+        assert!(diff.files[1].is_binary);
+        assert_eq!(diff.files[1].path(), "assets/café logo.png");
+
+        // Only one side needs quoting when only one side has special characters.
+        assert_eq!(diff.files[2].old_path.as_deref(), Some("plain.txt"));
+        assert_eq!(diff.files[2].new_path.as_deref(), Some("tab\there.txt"));
+    }
+
+    #[test]
+    fn parses_plain_unified_diffs() {
+        let diff = parse(PLAIN);
+        let file = &diff.files[0];
+
+        assert_eq!(file.old_path.as_deref(), Some("config.toml"));
+        assert_eq!(file.new_path.as_deref(), Some("config.toml"));
+        assert_eq!(file.hunks[0].lines.len(), 2);
+    }
+
+    #[test]
+    fn budgets_for_chunk_overhead() {
+        let diff = parse(&["a", "b", "c"].iter().map(|n| format!("diff --git a/{n} b/{n}\n--- a/{n}\n+++ b/{n}\n@@ -0,0 +1 @@\n+{}\n", "x".repeat(40))).collect::<String>());
+        let tokens = estimate_tokens(&diff.files[0].to_string());
+
+        assert_eq!(diff.chunk(3 * tokens).len(), 1);
+
+        // Something large is sent along with any chunk that contains `b` (e.g., the guidelines for it).
+        let chunks = diff.chunk_with(3 * tokens, |files| if files.iter().any(|f| f.path() == "b") { tokens } else { 0 });
+        let paths = chunks.iter().map(|c| c.files.iter().map(|f| f.path()).collect::<Vec<_>>()).collect::<Vec<_>>();
+
+        assert_eq!(paths, vec![vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn splits_hunks_into_consistent_hunks() {
+        // Lines of the same size: 6 context lines, 6 removed lines, 6 added lines, and 3 more context lines.
+        let lines = (1..=6).map(|n| format!(" c{:02}\n", n)).chain((1..=6).map(|n| format!("-r{:02}\n", n))).chain((1..=6).map(|n| format!("+a{:02}\n", n))).chain((7..=9).map(|n| format!(" c{:02}\n", n))).collect::<String>();
+        let diff = parse(&format!("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -10,15 +20,15 @@ fn f() {{\n{}", lines));
+        let file = &diff.files[0];
+
+        let line_tokens = estimate_tokens(&file.hunks[0].lines[0].to_string()) + 1;
+        let hunks = file.hunks[0].split(4 * line_tokens);
+
+        // The hunks are split across context, removed, and added lines (and in between them).
+        let kinds = hunks.iter().map(|h| h.lines.iter().map(|l| l.to_string().remove(0)).collect::<String>()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["    ", "  --", "----", "++++", "++  ", " "]);
+
+        // The split hunks reassemble to the original.
+        assert_eq!(hunks.iter().flat_map(|h| h.lines.clone()).collect::<Vec<_>>(), file.hunks[0].lines);
+
+        let (mut next_old, mut next_new) = (10, 20);
+
+        for hunk in &hunks {
+            // Each header matches its body, as though git had written it (an empty range starts at the line before it).
+            let count = |kind: LineKind| hunk.lines.iter().filter(|l| l.kind == kind || l.kind == LineKind::Context).count() as u32;
+            let (old_lines, new_lines) = (count(LineKind::Removed), count(LineKind::Added));
+
+            assert_eq!((hunk.old_lines, hunk.new_lines), (old_lines, new_lines), "{}", hunk);
+            assert_eq!(hunk.old_start, if old_lines == 0 { next_old - 1 } else { next_old }, "{}", hunk);
+            assert_eq!(hunk.new_start, if new_lines == 0 { next_new - 1 } else { next_new }, "{}", hunk);
+            assert_eq!(hunk.section, "fn f() {");
+
+            // Parsing the rendered hunk gives back the same lines (with the same line numbers).
+            let reparsed = parse(&format!("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n{}", hunk));
+            assert_eq!(reparsed.files[0].hunks, vec![hunk.clone()]);
+
+            next_old += old_lines;
+            next_new += new_lines;
+        }
+
+        assert_eq!((next_old, next_new), (10 + 15, 20 + 15));
+
+        // A file is split along the same lines, keeping its header for each part.
+        let parts = file.split(4 * line_tokens + estimate_tokens(&file.header.join("\n")));
+        assert_eq!(parts.iter().flat_map(|f| f.hunks.clone()).collect::<Vec<_>>(), hunks);
+        assert!(parts.iter().all(|f| f.header == file.header));
+
+        // The additions after line 5 stay after line 5.
+        let added = parse("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -5,0 +6,4 @@\n+a01\n+a02\n+a03\n+a04\n");
+        let headers = added.files[0].hunks[0].split(2 * line_tokens).iter().map(|h| h.to_string().lines().next().unwrap_or_default().to_string()).collect::<Vec<_>>();
+        assert_eq!(headers, vec!["@@ -5,0 +6,2 @@", "@@ -5,0 +8,2 @@"]);
+    }
+
+    #[test]
+    fn unquotes_c_style_escapes() {
+        assert_eq!(unquote("plain"), "plain");
+        assert_eq!(unquote("\"a \\\"quoted\\\" \\\\ path\""), "a \"quoted\" \\ path");
+        assert_eq!(unquote("\"caf\\303\\251\\n\""), "café\n");
+    }
+
+    // Fixtures.
+
+    static MODIFIED: &str = r#"diff --git a/src/lib.rs b/src/lib.rs
+index 83db48f..bf269f4 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@ fn main() {
+ let a = 1;
+-let b = 2;
++let b = 3;
++let c = 4;
+ println!("{}", a + b);
+"#;
+
+    static RENAMED: &str = r#"diff --git "a/old name.rs" "b/new name.rs"
+similarity index 90%
+rename from "old name.rs"
+rename to "new name.rs"
+index 1111111..2222222 100644
+--- "a/old name.rs"
++++ "b/new name.rs"
+@@ -1 +1 @@
+-fn old() {}
++fn new() {}
+diff --git a/a.rs b/b.rs
+similarity index 100%
+rename from a.rs
+rename to b.rs
+"#;
+
+    static COPIED: &str = r#"diff --git a/template.rs b/copy.rs
+similarity index 95%
+copy from template.rs
+copy to copy.rs
+index 1111111..2222222 100644
+--- a/template.rs
++++ b/copy.rs
+@@ -1,2 +1,3 @@
+ fn template() {}
++fn copy() {}
+ 
+"#;
+
+    static MODE_CHANGED: &str = r#"diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+"#;
+
+    static BINARY: &str = r#"diff --git a/logo.png b/logo.png
+index 1111111..2222222 100644
+Binary files a/logo.png and b/logo.png differ
+diff --git a/icon.ico b/icon.ico
+new file mode 100644
+index 0000000..3333333
+GIT binary patch
+literal 4
+LcmZQzWMT#Y01f~L
+
+literal 0
+HcmV?d00001
+
+"#;
+
+    static NO_NEWLINE: &str = r#"diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-old
+\ No newline at end of file
++new
+\ No newline at end of file
+diff --git a/b.txt b/b.txt
+index 3333333..4444444 100644
+--- a/b.txt
++++ b/b.txt
+@@ -1 +1,2 @@
+ one
++two
+"#;
+
+    static ADDED_AND_DELETED: &str = r#"diff --git a/new.rs b/new.rs
+new file mode 100644
+index 0000000..1111111
+--- /dev/null
++++ b/new.rs
+@@ -0,0 +1,2 @@
++fn a() {}
++fn b() {}
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+index 2222222..0000000
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn gone() {}
+"#;
+
+    static QUOTED: &str = r#"diff --git "a/docs/read me.md" "b/docs/read me.md"
+index 1111111..2222222 100644
+--- "a/docs/read me.md"
++++ "b/docs/read me.md"
+@@ -1 +1 @@
+-# Old
++# New
+diff --git "a/assets/caf\303\251 logo.png" "b/assets/caf\303\251 logo.png"
+index 3333333..4444444 100644
+Binary files "a/assets/caf\303\251 logo.png" and "b/assets/caf\303\251 logo.png" differ
+diff --git a/plain.txt "b/tab\there.txt"
+similarity index 100%
+rename from plain.txt
+rename to "tab\there.txt"
+"#;
+
+    static PLAIN: &str = "--- config.toml\t2024-01-01 00:00:00.000000000 +0000\n+++ config.toml\t2024-01-02 00:00:00.000000000 +0000\n@@ -1 +1 @@\n-a = 1\n+a = 2\n";
+}
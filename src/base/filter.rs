@@ -0,0 +1,332 @@
+//! The path filter module.
+//!
+//! Decides which files of a [`Diff`] are sent for review, based on `--include`/`--exclude` globs,
+//! gitignore-style `.augreignore` files, and heuristics for binary, generated, and minified files.
+
+use std::{fmt::{self, Display, Formatter}, path::Path, sync::OnceLock};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
+
+use super::{diff::{Diff, FileDiff, LineKind}, types::Res};
+
+// Statics.
+
+static IGNORE_FILE_NAME: &str = ".augreignore";
+
+static LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "composer.lock",
+    "Gemfile.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "go.sum",
+    "flake.lock",
+];
+
+/// The tokens that start a comment line, across common languages.
+static COMMENT_PREFIXES: &[&str] = &["//", "/*", "*", "#", "--", "<!--", ";", "%", "\"\"\""];
+
+/// Matches the text of a comment that marks its file as generated: Go's `Code generated ... DO NOT EDIT.`,
+/// `@generated`, or a comment that starts by saying so (e.g., `Generated by the protocol buffer compiler.`).
+fn generated_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"^Code generated .* DO NOT EDIT\.$|@generated\b|(?i)^(?:this (?:file|code) (?:is|was|has been) )?(?:auto-?generated|automatically generated|generated (?:by|from|with))\b").unwrap()
+    })
+}
+
+/// The number of lines at the top of a file that are searched for a generated code marker (see
+/// [`is_generated_marker`]).
+static GENERATED_MARKER_LINES: u32 = 10;
+
+/// The line length above which a file is considered minified.
+static MINIFIED_LINE_LENGTH: usize = 500;
+
+// Types.
+
+/// A file that was removed from the diff before review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file did not match any of the `--include` globs.
+    NotIncluded,
+    /// The file matched one of the `--exclude` globs.
+    Excluded,
+    /// The file matched a pattern in an `.augreignore` file.
+    Ignored,
+    /// The file is binary.
+    Binary,
+    /// The file is a known package manager lockfile.
+    Lockfile,
+    /// The file starts with a generated code comment (e.g., `// @generated`).
+    Generated,
+    /// The file contains very long lines (or is named `*.min.*`).
+    Minified,
+    /// The (untracked) file is larger than the configured limit.
+    TooLarge,
+    /// The (untracked) file could not be read.
+    Unreadable(String),
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotIncluded => write!(f, "not included"),
+            SkipReason::Excluded => write!(f, "excluded"),
+            SkipReason::Ignored => write!(f, "ignored by `{}`", IGNORE_FILE_NAME),
+            SkipReason::Binary => write!(f, "binary"),
+            SkipReason::Lockfile => write!(f, "lockfile"),
+            SkipReason::Generated => write!(f, "generated"),
+            SkipReason::Minified => write!(f, "minified"),
+            SkipReason::TooLarge => write!(f, "too large"),
+            SkipReason::Unreadable(err) => write!(f, "unreadable: {}", err),
+        }
+    }
+}
+
+/// A set of path filters applied to a [`Diff`].
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    ignore: Gitignore,
+}
+
+impl PathFilter {
+    /// Creates a new [`PathFilter`] from the specified globs, and the `.augreignore` files in the repository
+    /// root and the data directory (if present).
+    ///
+    /// The `root` is the directory that the diff paths are relative to.
+    pub fn new(include: &[String], exclude: &[String], root: &Path, data_path: &str) -> Res<Self> {
+        let include = if include.is_empty() { None } else { Some(build_glob_set(include)?) };
+        let exclude = build_glob_set(exclude)?;
+
+        let mut builder = GitignoreBuilder::new(root);
+
+        for path in [root.join(IGNORE_FILE_NAME), Path::new(data_path).join(IGNORE_FILE_NAME)] {
+            if path.is_file() {
+                if let Some(err) = builder.add(&path) {
+                    return Err(anyhow::Error::msg(format!("Unable to parse `{}`: {}", path.display(), err)));
+                }
+            }
+        }
+
+        let ignore = builder.build()?;
+
+        Ok(Self { include, exclude, ignore })
+    }
+
+    /// Returns the reason the specified path should be skipped (or `None` if it should be reviewed).
+    pub fn check(&self, path: &str) -> Option<SkipReason> {
+        if let Some(include) = &self.include {
+            if !include.is_match(path) {
+                return Some(SkipReason::NotIncluded);
+            }
+        }
+
+        if self.exclude.is_match(path) {
+            return Some(SkipReason::Excluded);
+        }
+
+        if self.ignore.matched_path_or_any_parents(path, false).is_ignore() {
+            return Some(SkipReason::Ignored);
+        }
+
+        None
+    }
+
+    /// Removes the filtered files from the diff, and returns the remaining diff along with the skipped files.
+    pub fn apply(&self, diff: Diff) -> (Diff, Vec<SkippedFile>) {
+        retain(diff, |file| self.check(file.path()))
+    }
+}
+
+/// Removes the binary, lockfile, generated, and minified files from the diff, and returns the remaining diff
+/// along with the skipped files.
+pub fn skip_generated(diff: Diff) -> (Diff, Vec<SkippedFile>) {
+    retain(diff, detect_generated)
+}
+
+/// Returns the reason the specified file looks like it should not be reviewed by a human (or `None`).
+pub fn detect_generated(file: &FileDiff) -> Option<SkipReason> {
+    if file.is_binary {
+        return Some(SkipReason::Binary);
+    }
+
+    let path = file.path();
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    if LOCKFILE_NAMES.contains(&file_name) {
+        return Some(SkipReason::Lockfile);
+    }
+
+    let lines = file.hunks.iter().flat_map(|h| &h.lines).filter(|l| l.kind != LineKind::Removed && l.kind != LineKind::NoNewline);
+
+    if lines.clone().filter(|l| l.new_line.is_some_and(|n| n <= GENERATED_MARKER_LINES)).any(|l| is_generated_marker(&l.content)) {
+        return Some(SkipReason::Generated);
+    }
+
+    if file_name.contains(".min.") || lines.clone().any(|l| l.content.len() > MINIFIED_LINE_LENGTH) {
+        return Some(SkipReason::Minified);
+    }
+
+    None
+}
+
+// Helpers.
+
+/// Whether the line is a comment that marks its file as generated (a marker elsewhere, e.g. in a string, or a plain
+/// "do not edit" does not count).
+fn is_generated_marker(line: &str) -> bool {
+    let line = line.trim();
+
+    let Some(prefix) = COMMENT_PREFIXES.iter().find(|p| line.starts_with(*p)) else {
+        return false;
+    };
+
+    // Doc comments (e.g., `//!` or `/**`) carry extra comment characters.
+    let text = line[prefix.len()..].trim_start_matches(|c: char| "/*#!-;%".contains(c)).trim();
+    let text = text.trim_end_matches("*/").trim_end_matches("-->").trim_end_matches("\"\"\"").trim_end();
+
+    generated_regex().is_match(text)
+}
+
+fn retain(diff: Diff, check: impl Fn(&FileDiff) -> Option<SkipReason>) -> (Diff, Vec<SkippedFile>) {
+    let mut skipped = Vec::new();
+
+    let files = diff
+        .files
+        .into_iter()
+        .filter(|file| match check(file) {
+            Some(reason) => {
+                skipped.push(SkippedFile { path: file.path().to_string(), reason });
+                false
+            },
+            None => true,
+        })
+        .collect();
+
+    (Diff { files }, skipped)
+}
+
+pub(crate) fn build_glob_set(patterns: &[String]) -> Res<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        // Treat a trailing slash as "everything in this directory", like `.gitignore` does.
+        let pattern = match pattern.strip_suffix('/') {
+            Some(dir) => format!("{}/**", dir),
+            None => pattern.clone(),
+        };
+
+        builder.add(Glob::new(&pattern)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    /// A diff that adds the file with the specified lines.
+    fn added(path: &str, lines: &[&str]) -> FileDiff {
+        let body = lines.iter().map(|l| format!("+{}\n", l)).collect::<String>();
+        let text = format!("diff --git a/{path} b/{path}\nnew file mode 100644\n--- /dev/null\n+++ b/{path}\n@@ -0,0 +1,{} @@\n{}", lines.len(), body, path = path);
+
+        Diff::parse(&text).unwrap().files.remove(0)
+    }
+
+    #[test]
+    fn filters_by_include_and_exclude_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let filter = PathFilter::new(&globs(&["src/**", "*.md"]), &globs(&["src/gen/", "**/*_test.rs"]), dir.path(), "/nonexistent").unwrap();
+
+        assert_eq!(filter.check("src/main.rs"), None);
+        assert_eq!(filter.check("README.md"), None);
+        assert_eq!(filter.check("docs/guide.md"), None);
+        assert_eq!(filter.check("build.rs"), Some(SkipReason::NotIncluded));
+        assert_eq!(filter.check("src/gen/api.rs"), Some(SkipReason::Excluded));
+        assert_eq!(filter.check("src/base/diff_test.rs"), Some(SkipReason::Excluded));
+
+        let everything = PathFilter::new(&[], &[], dir.path(), "/nonexistent").unwrap();
+        assert_eq!(everything.check("build.rs"), None);
+    }
+
+    #[test]
+    fn applies_the_ignore_files_after_the_globs() {
+        let repo = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+
+        std::fs::write(repo.path().join(IGNORE_FILE_NAME), "*.snap\nfixtures/\n").unwrap();
+        // The data directory's file comes last, so it can re-include what the repository's file ignores.
+        std::fs::write(data.path().join(IGNORE_FILE_NAME), "!keep.snap\n").unwrap();
+
+        let filter = PathFilter::new(&[], &globs(&["*.lock"]), repo.path(), data.path().to_str().unwrap()).unwrap();
+
+        assert_eq!(filter.check("tests/output.snap"), Some(SkipReason::Ignored));
+        assert_eq!(filter.check("tests/fixtures/a.rs"), Some(SkipReason::Ignored));
+        assert_eq!(filter.check("tests/keep.snap"), None);
+        assert_eq!(filter.check("src/lib.rs"), None);
+
+        // The globs are checked first.
+        let included = PathFilter::new(&globs(&["src/**"]), &globs(&["src/fixtures/**"]), repo.path(), data.path().to_str().unwrap()).unwrap();
+        assert_eq!(included.check("tests/output.snap"), Some(SkipReason::NotIncluded));
+        assert_eq!(included.check("src/fixtures/a.rs"), Some(SkipReason::Excluded));
+
+        let diff = Diff { files: vec![added("src/lib.rs", &["fn f() {}"]), added("output.snap", &["x"])] };
+        let (diff, skipped) = filter.apply(diff);
+
+        assert_eq!(diff.files.iter().map(|f| f.path()).collect::<Vec<_>>(), vec!["src/lib.rs"]);
+        assert_eq!(skipped, vec![SkippedFile { path: "output.snap".to_string(), reason: SkipReason::Ignored }]);
+    }
+
+    #[test]
+    fn detects_generated_files() {
+        let long_line = "x".repeat(MINIFIED_LINE_LENGTH + 1);
+
+        let cases = [
+            ("gen/api.go", vec!["// Code generated by protoc-gen-go. DO NOT EDIT.", "", "package api"], Some(SkipReason::Generated)),
+            ("src/schema.rs", vec!["// @generated by diesel", "use diesel::*;"], Some(SkipReason::Generated)),
+            ("src/bindings.rs", vec!["/* automatically generated by rust-bindgen 0.69.4 */"], Some(SkipReason::Generated)),
+            ("api_pb2.py", vec!["# -*- coding: utf-8 -*-", "# Generated by the protocol buffer compiler.  DO NOT EDIT!"], Some(SkipReason::Generated)),
+            ("src/version.rs", vec!["//! This file is auto-generated from `build.rs`."], Some(SkipReason::Generated)),
+            ("Cargo.lock", vec!["version = 3"], Some(SkipReason::Lockfile)),
+            ("web/go.sum", vec!["x"], Some(SkipReason::Lockfile)),
+            ("app.min.js", vec!["var a=1;"], Some(SkipReason::Minified)),
+            ("bundle.js", vec![long_line.as_str()], Some(SkipReason::Minified)),
+            // Not a comment, not near the top, or not a generated code marker.
+            ("src/hooks.rs", vec!["// DO NOT EDIT the hook by hand; run `augre install` instead."], None),
+            ("src/ids.rs", vec!["// Parses the ids generated by the server.", "fn parse() {}"], None),
+            ("src/filter.rs", vec!["static MARKERS: &[&str] = &[\"@generated\", \"DO NOT EDIT\"];"], None),
+            ("src/late.rs", [vec![""; 10], vec!["// @generated"]].concat(), None),
+            ("src/lib.rs", vec!["fn f() {}"], None),
+        ];
+
+        for (path, lines, expected) in cases {
+            assert_eq!(detect_generated(&added(path, &lines)), expected, "{}", path);
+        }
+
+        let mut binary = added("logo.png", &["x"]);
+        binary.is_binary = true;
+        assert_eq!(detect_generated(&binary), Some(SkipReason::Binary));
+    }
+}
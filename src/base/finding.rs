@@ -0,0 +1,592 @@
+//! The finding module.
+//!
+//! Parses a review into typed [`Finding`]s: from the JSON that the review prompt asks for (repairing it when the
+//! model gets it slightly wrong), or from the numbered three-category format as a fallback.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{citation, severity::{self, Severity}};
+
+// Statics.
+
+/// The category of findings that do not specify one.
+pub static DEFAULT_CATEGORY: &str = "general";
+
+// Types.
+
+/// An inclusive range of lines in the new version of a file.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl LineRange {
+    /// Whether the ranges share at least one line.
+    pub fn overlaps(&self, other: &LineRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl std::fmt::Display for LineRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// A single issue that a review found.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Finding {
+    /// The path of the file that the finding is about (if any).
+    pub file: Option<String>,
+    /// The lines of the file that the finding is about (if any).
+    pub line_range: Option<LineRange>,
+    pub severity: Severity,
+    /// What the finding is about (e.g., `error-handling`, `performance`, or `naming`).
+    pub category: String,
+    pub message: String,
+    /// A code snippet (or description) of the fix (if any).
+    pub suggested_fix: Option<String>,
+    /// The project guideline that the finding enforces (if any).
+    pub guideline: Option<String>,
+    /// The focused review pass that found it (e.g., `security`), if any.
+    pub pass: Option<String>,
+}
+
+impl Finding {
+    /// The `path/file.rs:30-32` location of the finding (if it has a file).
+    pub fn location(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+
+        Some(match self.line_range {
+            Some(range) => format!("{}:{}", file, range),
+            None => file.clone(),
+        })
+    }
+
+    /// Whether the finding reports the same issue as the other one: the same file, severity, and category (in the same
+    /// focused pass), with overlapping lines (or no lines at all).
+    pub fn duplicates(&self, other: &Finding) -> bool {
+        let same_lines = match (self.line_range, other.line_range) {
+            (Some(a), Some(b)) => a.overlaps(&b),
+            (None, None) => true,
+            _ => false,
+        };
+
+        self.file == other.file && self.pass == other.pass && self.severity == other.severity && self.category.eq_ignore_ascii_case(&other.category) && same_lines
+    }
+}
+
+/// The result of a review.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Review {
+    /// A short summary of the changes and the review (or the raw response, if it could not be parsed).
+    pub summary: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl Review {
+    /// Counts the findings at or above the specified severity.
+    pub fn count_at_least(&self, severity: Severity) -> usize {
+        self.findings.iter().filter(|f| f.severity >= severity).count()
+    }
+
+    /// Sorts the findings by severity (most severe first), keeping the model's order otherwise.
+    pub fn sort(&mut self) {
+        self.findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    }
+
+    /// Tags the findings (and the summary) with the focused review pass that produced them.
+    pub fn tag(&mut self, pass: &str) {
+        for finding in &mut self.findings {
+            finding.pass = Some(pass.to_string());
+        }
+
+        self.summary = self.summary.take().map(|s| format!("**{}**: {}", pass, s));
+    }
+
+    /// Merges the reviews (e.g., of the chunks of a large diff, or of the focused passes), removing the findings that
+    /// duplicate an earlier one (see [`Finding::duplicates`]); the first finding is kept, and takes the suggested fix
+    /// and guideline of its duplicates when it has none.
+    pub fn merge(reviews: Vec<Review>) -> Review {
+        let mut merged = Review::default();
+        let mut summaries = Vec::new();
+
+        for review in reviews {
+            summaries.extend(review.summary);
+
+            for finding in review.findings {
+                match merged.findings.iter_mut().find(|f| f.duplicates(&finding)) {
+                    Some(existing) => {
+                        existing.suggested_fix = existing.suggested_fix.take().or(finding.suggested_fix);
+                        existing.guideline = existing.guideline.take().or(finding.guideline);
+                    }
+                    None => merged.findings.push(finding),
+                }
+            }
+        }
+
+        merged.summary = if summaries.is_empty() { None } else { Some(summaries.join("\n\n")) };
+        merged.sort();
+
+        merged
+    }
+}
+
+impl Review {
+    /// Renders the review in the numbered three-category format (for the terminal).
+    pub fn to_markdown(&self) -> String {
+        let mut text = String::new();
+
+        if let Some(summary) = &self.summary {
+            text.push_str(summary);
+            text.push_str("\n\n");
+        }
+
+        if self.findings.is_empty() {
+            text.push_str("No issues found.\n");
+            return text;
+        }
+
+        for (k, severity) in [Severity::Runtime, Severity::Logic, Severity::Style].into_iter().enumerate() {
+            text.push_str(&format!("{}. Likely {} bugs:\n\n", k + 1, severity));
+
+            let findings = self.findings.iter().filter(|f| f.severity == severity).collect::<Vec<_>>();
+
+            if findings.is_empty() {
+                text.push_str("- None.\n");
+            }
+
+            for finding in findings {
+                text.push_str(&finding.to_markdown());
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+impl Finding {
+    /// Renders the finding as a Markdown list item.
+    pub fn to_markdown(&self) -> String {
+        let mut text = String::from("- ");
+
+        // Findings from the numbered format already cite their location in the message.
+        if let Some(location) = self.location().filter(|l| !self.message.contains(l.as_str())) {
+            text.push_str(&format!("`{}`: ", location));
+        }
+
+        if let Some(pass) = &self.pass {
+            text.push_str(&format!("[{}] ", pass));
+        }
+
+        if self.category != DEFAULT_CATEGORY {
+            text.push_str(&format!("({}) ", self.category));
+        }
+
+        text.push_str(&self.message.replace('\n', "\n  "));
+        text.push('\n');
+
+        if let Some(guideline) = &self.guideline {
+            text.push_str(&format!("  - Guideline: {}\n", guideline));
+        }
+
+        match &self.suggested_fix {
+            Some(fix) if fix.contains('\n') && !fix.contains("```") => text.push_str(&format!("\n  ```\n  {}\n  ```\n\n", fix.replace('\n', "\n  "))),
+            Some(fix) if fix.contains('\n') => text.push_str(&format!("\n  {}\n\n", fix.replace('\n', "\n  "))),
+            Some(fix) => text.push_str(&format!("  - Fix: {}\n", fix)),
+            None => {},
+        }
+
+        text
+    }
+}
+
+// Parsing.
+
+/// Parses the model's response into a [`Review`].
+///
+/// JSON is preferred (and repaired if needed); otherwise, the numbered three-category format is parsed, and, failing
+/// that, the whole response is kept as the summary.
+pub fn parse(response: &str) -> Review {
+    if let Some(review) = parse_json(response) {
+        return review;
+    }
+
+    let findings = severity::suggestions(response).into_iter().map(|s| from_text(s.severity, &s.text)).collect::<Vec<_>>();
+    let summary = if findings.is_empty() && !response.trim().is_empty() { Some(response.trim().to_string()) } else { None };
+
+    let mut review = Review { summary, findings };
+    review.sort();
+
+    review
+}
+
+/// Parses the (possibly malformed) JSON response, accepting either `{"summary": ..., "findings": [...]}` or a bare
+/// array of findings.
+///
+/// Each `{` or `[` is tried in turn, since the commentary before the JSON may have brackets of its own (e.g., `Here is
+/// the [JSON] you asked for: {...}`).
+pub fn parse_json(response: &str) -> Option<Review> {
+    response.match_indices(['{', '[']).find_map(|(start, _)| to_review(&parse_value(&response[start..])?, response))
+}
+
+fn to_review(value: &Value, response: &str) -> Option<Review> {
+    let (summary, findings) = match value {
+        // Guard against arrays in the code snippets of a response in the numbered format.
+        Value::Array(findings) if findings.iter().all(Value::is_object) && (!findings.is_empty() || response.trim_start().starts_with('[')) => (None, findings.as_slice()),
+        Value::Object(object) if ["summary", "findings", "issues"].iter().any(|k| object.contains_key(*k)) => {
+            let summary = object.get("summary").and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+            let findings = object.get("findings").or_else(|| object.get("issues")).and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+
+            (summary, findings)
+        },
+        _ => return None,
+    };
+
+    let mut review = Review { summary, findings: findings.iter().filter_map(from_value).collect() };
+    review.sort();
+
+    Some(review)
+}
+
+/// Parses the JSON value at the start of the text.
+fn parse_value(mut text: &str) -> Option<Value> {
+    // A response that was cut off mid-finding can still be parsed up to its last complete finding.
+    loop {
+        let (repaired, scanned) = repair_scanned(text);
+
+        if let Ok(value) = serde_json::from_str::<Value>(&repaired) {
+            if value.is_object() || value.is_array() {
+                return Some(value);
+            }
+        }
+
+        // Cutting the text after the end of the scan would not change the repair.
+        let scanned = &text[..scanned];
+        let last = scanned.chars().next_back().map_or(0, char::len_utf8);
+        let end = scanned[..scanned.len() - last].rfind('}')?;
+        text = &text[..=end];
+    }
+}
+
+fn from_value(value: &Value) -> Option<Finding> {
+    let object = value.as_object()?;
+    let string = |keys: &[&str]| keys.iter().find_map(|k| object.get(*k)).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+
+    let message = string(&["message", "description", "suggestion"])?;
+
+    let severity = string(&["severity", "kind"])
+        .and_then(|s| s.split_whitespace().find_map(|word| word.trim_matches(|c: char| !c.is_ascii_alphabetic()).parse().ok()))
+        .unwrap_or(Severity::Logic);
+
+    let file = string(&["file", "path"]).map(|f| f.trim_start_matches("./").to_string());
+    let line_range = ["line_range", "lines", "line"].iter().find_map(|k| object.get(*k)).and_then(line_range);
+
+    Some(Finding {
+        file,
+        line_range,
+        severity,
+        category: string(&["category"]).unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+        message,
+        suggested_fix: string(&["suggested_fix", "fix"]),
+        guideline: string(&["guideline", "rule"]),
+        pass: None,
+    })
+}
+
+/// Reads a line range from `[30, 32]`, `[30]`, `30`, `"30-32"`, or `{"start": 30, "end": 32}`.
+fn line_range(value: &Value) -> Option<LineRange> {
+    let number = |v: &Value| v.as_u64().or_else(|| v.as_str().and_then(|s| s.trim().parse().ok())).and_then(|n| u32::try_from(n).ok());
+
+    let (start, end) = match value {
+        Value::Array(items) => (number(items.first()?)?, items.get(1).and_then(number)),
+        Value::Object(object) => (number(object.get("start")?)?, object.get("end").and_then(number)),
+        Value::String(s) => match s.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()),
+            None => (s.trim().parse().ok()?, None),
+        },
+        _ => (number(value)?, None),
+    };
+
+    let end = end.unwrap_or(start).max(start);
+
+    Some(LineRange { start, end })
+}
+
+/// Builds a finding from a suggestion of the numbered format, taking its location from its first citation.
+fn from_text(severity: Severity, text: &str) -> Finding {
+    let citation = citation::find(text).into_iter().next();
+
+    Finding {
+        file: citation.as_ref().map(|c| c.path.clone()),
+        line_range: citation.as_ref().map(|c| LineRange { start: c.line, end: c.end_line.unwrap_or(c.line).max(c.line) }),
+        severity,
+        category: DEFAULT_CATEGORY.to_string(),
+        message: text.to_string(),
+        suggested_fix: None,
+        guideline: None,
+        pass: None,
+    }
+}
+
+// Repair.
+
+/// Repairs the most common ways in which models break JSON: code fences and commentary around it, trailing commas,
+/// and truncation (unterminated strings, arrays, and objects).
+pub fn repair(text: &str) -> String {
+    repair_scanned(text).0
+}
+
+/// Repairs the text (see [`repair`]), and returns the length of the text that was scanned (the rest was dropped).
+fn repair_scanned(text: &str) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (k, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                // Raw newlines are not allowed in JSON strings.
+                '\n' => {
+                    result.push_str("\\n");
+                    continue;
+                },
+                _ => {},
+            }
+
+            result.push(c);
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                remove_trailing_comma(&mut result);
+
+                if stack.pop() != Some(c) {
+                    return (close(result, in_string, stack), k + c.len_utf8());
+                }
+            },
+            _ => {},
+        }
+
+        result.push(c);
+
+        // Anything after the top-level value (e.g., a closing code fence, or commentary) is dropped.
+        if stack.is_empty() && matches!(c, '}' | ']') {
+            return (result, k + c.len_utf8());
+        }
+    }
+
+    (close(result, in_string, stack), text.len())
+}
+
+/// Terminates the unterminated string, arrays, and objects of a truncated value.
+fn close(mut result: String, in_string: bool, mut stack: Vec<char>) -> String {
+    if in_string {
+        result.push('"');
+    }
+
+    remove_trailing_comma(&mut result);
+
+    while let Some(close) = stack.pop() {
+        result.push(close);
+    }
+
+    result
+}
+
+fn remove_trailing_comma(result: &mut String) {
+    let trimmed = result.trim_end().len();
+
+    if result[..trimmed].ends_with(',') {
+        result.truncate(trimmed - 1);
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(file: &str, lines: (u32, u32), severity: Severity, category: &str) -> Finding {
+        Finding {
+            file: Some(file.to_string()),
+            line_range: Some(LineRange { start: lines.0, end: lines.1 }),
+            severity,
+            category: category.to_string(),
+            message: format!("An issue in {}.", file),
+            suggested_fix: None,
+            guideline: None,
+            pass: None,
+        }
+    }
+
+    /// The summary and the `(location, severity)` of each finding of the parsed response.
+    fn outline(response: &str) -> (Option<String>, Vec<(Option<String>, Severity)>) {
+        let review = parse(response);
+
+        (review.summary, review.findings.iter().map(|f| (f.location(), f.severity)).collect())
+    }
+
+    #[test]
+    fn parses_and_repairs_json_responses() {
+        let finding = r#"{"file": "src/a.rs", "line_range": [3, 4], "severity": "runtime", "message": "Panics."}"#;
+        let expected = (Some("Looks fine.".to_string()), vec![(Some("src/a.rs:3-4".to_string()), Severity::Runtime)]);
+
+        let cases = [
+            ("plain", format!(r#"{{"summary": "Looks fine.", "findings": [{}]}}"#, finding)),
+            ("fenced", format!("```json\n{{\"summary\": \"Looks fine.\", \"findings\": [{}]}}\n```\n", finding)),
+            ("prose before and after", format!("Here is the review:\n\n{{\"summary\": \"Looks fine.\", \"findings\": [{}]}}\n\nLet me know!", finding)),
+            ("brackets in the prose", format!(r#"Here is the [JSON] you asked for (see {{above}}): {{"summary": "Looks fine.", "findings": [{}]}}"#, finding)),
+            ("trailing commas", format!(r#"{{"summary": "Looks fine.", "findings": [{},],}}"#, finding.replace("}", ",}"))),
+            ("raw newlines in strings", format!("{{\"summary\": \"Looks\nfine.\", \"findings\": [{}]}}", finding)),
+            // The incomplete finding is dropped.
+            ("truncated array", format!(r#"{{"summary": "Looks fine.", "findings": [{}, {{"file": "src/b.rs", "mess"#, finding)),
+        ];
+
+        for (name, response) in &cases {
+            let (summary, findings) = outline(response);
+
+            assert_eq!(summary.map(|s| s.replace('\n', " ")), expected.0, "{}", name);
+            assert_eq!(findings, expected.1, "{}", name);
+        }
+
+        // A finding whose message was cut off is kept, as far as it goes.
+        let review = parse(&format!(r#"{{"findings": [{}, {{"file": "src/b.rs", "message": "Cut o"#, finding));
+        assert_eq!(review.findings.iter().map(|f| f.message.as_str()).collect::<Vec<_>>(), vec!["Panics.", "Cut o"]);
+    }
+
+    #[test]
+    fn parses_bare_arrays_of_findings() {
+        let (summary, findings) = outline(r#"[{"path": "./src/a.rs", "line": 7, "kind": "Likely style bug", "description": "Rename it."}]"#);
+
+        assert_eq!(summary, None);
+        assert_eq!(findings, vec![(Some("src/a.rs:7".to_string()), Severity::Style)]);
+
+        assert_eq!(parse("[]"), Review { summary: None, findings: Vec::new() });
+        assert_eq!(parse(r#"{"summary": "Nothing to report.", "findings": []}"#).summary.as_deref(), Some("Nothing to report."));
+    }
+
+    #[test]
+    fn reads_every_form_of_line_range() {
+        let cases = [
+            (r#"[30, 32]"#, Some((30, 32))),
+            (r#"[30]"#, Some((30, 30))),
+            (r#"30"#, Some((30, 30))),
+            (r#""30""#, Some((30, 30))),
+            (r#""30-32""#, Some((30, 32))),
+            (r#""30 - 32""#, Some((30, 32))),
+            (r#"{"start": 30, "end": 32}"#, Some((30, 32))),
+            (r#"{"start": "30"}"#, Some((30, 30))),
+            // An end before the start is clamped.
+            (r#"[32, 30]"#, Some((32, 32))),
+            (r#""L30""#, None),
+            (r#"null"#, None),
+            (r#"[]"#, None),
+        ];
+
+        for (value, expected) in cases {
+            let value = serde_json::from_str::<Value>(value).unwrap();
+
+            assert_eq!(line_range(&value), expected.map(|(start, end)| LineRange { start, end }), "{}", value);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_numbered_format() {
+        let response = "\
+Likely runtime bugs:
+1. `src/a.rs:10` unwraps a `None`, e.g. `let v = [1, 2];`.
+
+Likely logic bugs:
+- None
+
+Likely style bugs:
+1. Rename `x` (src/b.rs:3-4).
+";
+
+        let (summary, findings) = outline(response);
+
+        assert_eq!(summary, None);
+        assert_eq!(findings, vec![
+            (Some("src/a.rs:10".to_string()), Severity::Runtime),
+            (Some("src/b.rs:3-4".to_string()), Severity::Style),
+        ]);
+
+        // A response in neither format is kept as the summary.
+        assert_eq!(outline("All good {really}."), (Some("All good {really}.".to_string()), Vec::new()));
+        assert_eq!(outline("  "), (None, Vec::new()));
+    }
+
+    #[test]
+    fn merges_duplicate_findings() {
+        let first = Review { summary: Some("First chunk.".to_string()), findings: vec![finding("src/a.rs", (10, 12), Severity::Logic, "error-handling")] };
+
+        let mut duplicate = finding("src/a.rs", (12, 14), Severity::Logic, "Error-Handling");
+        duplicate.message = "The error is ignored.".to_string();
+        duplicate.suggested_fix = Some("Use `?`.".to_string());
+
+        let second = Review {
+            summary: Some("Second chunk.".to_string()),
+            findings: vec![
+                duplicate,
+                finding("src/a.rs", (13, 14), Severity::Style, "error-handling"),
+                finding("src/a.rs", (20, 20), Severity::Logic, "error-handling"),
+                finding("src/b.rs", (10, 12), Severity::Logic, "error-handling"),
+                finding("src/a.rs", (10, 12), Severity::Logic, "naming"),
+            ],
+        };
+
+        let merged = Review::merge(vec![first, second]);
+
+        assert_eq!(merged.summary.as_deref(), Some("First chunk.\n\nSecond chunk."));
+        assert_eq!(merged.findings.len(), 5);
+        assert_eq!(merged.findings[0].message, "An issue in src/a.rs.");
+        assert_eq!(merged.findings[0].line_range, Some(LineRange { start: 10, end: 12 }));
+        assert_eq!(merged.findings[0].suggested_fix.as_deref(), Some("Use `?`."));
+        assert_eq!(merged.findings.last().map(|f| f.severity), Some(Severity::Style));
+    }
+
+    #[test]
+    fn keeps_the_findings_of_different_passes() {
+        let mut security = finding("src/a.rs", (3, 3), Severity::Runtime, "input-validation");
+        security.pass = Some("security".to_string());
+
+        let mut performance = security.clone();
+        performance.pass = Some("performance".to_string());
+
+        let merged = Review::merge(vec![Review { summary: None, findings: vec![security] }, Review { summary: None, findings: vec![performance] }]);
+
+        assert_eq!(merged.findings.iter().map(|f| f.pass.as_deref()).collect::<Vec<_>>(), vec![Some("security"), Some("performance")]);
+    }
+
+    #[test]
+    fn keeps_findings_without_lines_apart_from_those_with_lines() {
+        let mut general = finding("src/a.rs", (1, 1), Severity::Logic, "general");
+        general.line_range = None;
+
+        let merged = Review::merge(vec![
+            Review { summary: None, findings: vec![general.clone(), finding("src/a.rs", (1, 1), Severity::Logic, "general")] },
+            Review { summary: None, findings: vec![general] },
+        ]);
+
+        assert_eq!(merged.summary, None);
+        assert_eq!(merged.findings.len(), 2);
+    }
+}
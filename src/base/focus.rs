@@ -0,0 +1,155 @@
+//! The focus module.
+//!
+//! Defines the focused review passes (e.g., `security`), each of which reviews the diff against its own checklist:
+//! the built-in ones, and the custom ones from the config (which can also be limited to the files matching their
+//! globs).
+
+use serde::{Deserialize, Serialize};
+
+use super::{diff::Diff, filter, types::Res};
+
+// Statics.
+
+/// The names of the built-in passes.
+pub static BUILTIN_NAMES: &[&str] = &["security", "performance", "tests", "api"];
+
+// Types.
+
+/// A focused review pass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusPass {
+    pub name: String,
+    /// The instructions (usually a checklist) that the pass reviews the diff against.
+    pub prompt: String,
+    /// The globs of the files that the pass reviews (all of them, if empty).
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl FocusPass {
+    /// Gets the part of the diff that the pass reviews.
+    pub fn filter(&self, diff: &Diff) -> Res<Diff> {
+        if self.paths.is_empty() {
+            return Ok(diff.clone());
+        }
+
+        let globs = filter::build_glob_set(&self.paths)?;
+
+        Ok(Diff { files: diff.files.iter().filter(|f| globs.is_match(f.path())).cloned().collect() })
+    }
+}
+
+// Helpers.
+
+/// Resolves the names of the passes (custom passes take precedence over the built-in ones with the same name).
+pub fn resolve(names: &[String], custom: &[FocusPass]) -> Res<Vec<FocusPass>> {
+    names
+        .iter()
+        .map(|name| {
+            if let Some(pass) = custom.iter().find(|p| p.name == *name) {
+                return Ok(pass.clone());
+            }
+
+            let prompt = builtin(name).ok_or_else(|| {
+                let available = BUILTIN_NAMES.iter().copied().chain(custom.iter().map(|p| p.name.as_str())).collect::<Vec<_>>();
+                anyhow::Error::msg(format!("Invalid focus specified (expected one of {}): {}.", available.join(", "), name))
+            })?;
+
+            Ok(FocusPass { name: name.clone(), prompt: prompt.to_string(), paths: Vec::new() })
+        })
+        .collect()
+}
+
+/// Gets the checklist of the built-in pass.
+fn builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "security" => Some(SECURITY_CHECKLIST),
+        "performance" => Some(PERFORMANCE_CHECKLIST),
+        "tests" => Some(TESTS_CHECKLIST),
+        "api" => Some(API_CHECKLIST),
+        _ => None,
+    }
+}
+
+// Checklists.
+
+static SECURITY_CHECKLIST: &str = r#"
+- Injection (e.g., SQL, shell, template, or path traversal) from untrusted input.
+- Missing or bypassable authentication and authorization checks.
+- Secrets, keys, or credentials in the code, in logs, or in error messages.
+- Unsafe deserialization, `unsafe` code, or unchecked memory access.
+- Misused cryptography (e.g., weak algorithms, fixed nonces, or non-constant-time comparisons of secrets).
+- Missing input validation, and resource exhaustion (e.g., unbounded allocations, regexes, or recursion).
+"#;
+
+static PERFORMANCE_CHECKLIST: &str = r#"
+- Algorithmic complexity (e.g., quadratic scans, or repeated lookups that want a map).
+- Work in hot loops that could be hoisted or cached, and needless allocations or clones.
+- Blocking calls on async paths, and concurrent work that is needlessly serialized.
+- Unbatched database, network, or disk access (e.g., N+1 queries).
+- Unbounded growth of collections, caches, or buffers.
+"#;
+
+static TESTS_CHECKLIST: &str = r#"
+- New or changed behavior without tests (including error paths and edge cases).
+- Existing tests that were not updated for changed behavior.
+- Tests that do not assert what they claim to, or that depend on timing, ordering, or the environment.
+- Missing boundary cases (e.g., empty, zero, maximum, or non-ASCII input).
+"#;
+
+static API_CHECKLIST: &str = r#"
+- Breaking changes to public APIs (e.g., removed or renamed items, or changed signatures, types, or defaults).
+- Changes to serialized formats, command line flags, config keys, or wire protocols that break existing users.
+- Removed or changed behavior without a deprecation path.
+- Public items without documentation, or whose documentation no longer matches their behavior.
+"#;
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn custom(name: &str, paths: &[&str]) -> FocusPass {
+        FocusPass { name: name.to_string(), prompt: format!("- Check the {}.", name), paths: names(paths) }
+    }
+
+    #[test]
+    fn resolves_builtin_and_custom_passes() {
+        let passes = resolve(&names(&["security", "migrations", "tests"]), &[custom("migrations", &["db/**"]), custom("tests", &[])]).unwrap();
+
+        assert_eq!(passes.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["security", "migrations", "tests"]);
+        assert_eq!(passes[0].prompt, SECURITY_CHECKLIST);
+        assert_eq!(passes[1].paths, names(&["db/**"]));
+        // A custom pass replaces the built-in one with the same name.
+        assert_eq!(passes[2].prompt, "- Check the tests.");
+
+        assert!(resolve(&[], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_passes() {
+        let err = resolve(&names(&["security", "style"]), &[custom("migrations", &[])]).unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid focus specified (expected one of security, performance, tests, api, migrations): style.");
+    }
+
+    #[test]
+    fn filters_the_diff_to_the_files_of_the_pass() {
+        let text = ["db/migrations/001.sql", "src/db.rs", "README.md"].iter().map(|p| format!("diff --git a/{p} b/{p}\n--- a/{p}\n+++ b/{p}\n@@ -1 +1 @@\n-a\n+b\n", p = p)).collect::<String>();
+        let diff = Diff::parse(&text).unwrap();
+
+        let paths = |pass: &FocusPass| pass.filter(&diff).unwrap().files.iter().map(|f| f.path().to_string()).collect::<Vec<_>>();
+
+        assert_eq!(paths(&custom("migrations", &["db/"])), vec!["db/migrations/001.sql"]);
+        assert_eq!(paths(&custom("rust", &["**/*.rs", "*.md"])), vec!["src/db.rs", "README.md"]);
+        assert_eq!(paths(&custom("docs", &["docs/**"])), Vec::<String>::new());
+        assert_eq!(paths(&custom("all", &[])).len(), 3);
+
+        assert!(custom("broken", &["src/[.rs"]).filter(&diff).is_err());
+    }
+}
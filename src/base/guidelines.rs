@@ -0,0 +1,153 @@
+//! The guidelines module.
+//!
+//! Loads the project's coding guidelines (e.g., `CONTRIBUTING.md`), which are sent along with every review: the ones
+//! that apply to every file, and the guideline sets that only apply to the files matching their globs (e.g., to one
+//! package of a monorepo).
+
+use std::path::Path;
+
+use globset::GlobSet;
+use serde::{Deserialize, Serialize};
+
+use super::{diff::FileDiff, filter, types::{Res, estimate_tokens}};
+
+// Types.
+
+/// A set of guidelines that only applies to the files matching its globs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuidelineSet {
+    /// The globs of the files that the guidelines apply to (e.g., `web/**`).
+    pub paths: Vec<String>,
+    /// The paths of the guideline files (e.g., `web/STYLE.md`).
+    pub files: Vec<String>,
+}
+
+/// A guidelines file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuidelineFile {
+    pub path: String,
+    pub text: String,
+}
+
+/// The project's guidelines.
+#[derive(Debug)]
+pub struct Guidelines {
+    global: Vec<GuidelineFile>,
+    sets: Vec<(GlobSet, Vec<GuidelineFile>)>,
+}
+
+impl Guidelines {
+    /// Reads the guideline files, relative to the repository `root` (skipping those that do not exist).
+    pub fn load(root: &Path, files: &[String], sets: &[GuidelineSet]) -> Res<Self> {
+        let global = read_all(root, files)?;
+        let sets = sets.iter().map(|set| Ok((filter::build_glob_set(&set.paths)?, read_all(root, &set.files)?))).collect::<Res<Vec<_>>>()?;
+
+        Ok(Self { global, sets })
+    }
+
+    /// Gets the guidelines that apply to any of the changed files.
+    pub fn for_files(&self, changed: &[FileDiff]) -> Vec<&GuidelineFile> {
+        let mut files = self.global.iter().collect::<Vec<_>>();
+
+        for (globs, set) in &self.sets {
+            if !changed.iter().any(|f| globs.is_match(f.path())) {
+                continue;
+            }
+
+            for file in set {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        files
+    }
+
+    /// Renders the guidelines that apply to any of the changed files (or returns `None` if there are none).
+    pub fn render_for(&self, changed: &[FileDiff]) -> Option<String> {
+        render(&self.for_files(changed))
+    }
+
+    /// Estimates the number of tokens of the guidelines that apply to any of the changed files.
+    pub fn tokens(&self, changed: &[FileDiff]) -> usize {
+        self.render_for(changed).map_or(0, |text| estimate_tokens(&text))
+    }
+}
+
+// Helpers.
+
+/// Renders the guideline files as one Markdown document (or returns `None` if there are none).
+pub fn render(files: &[&GuidelineFile]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+
+    Some(files.iter().map(|f| format!("## `{}`\n\n{}", f.path, f.text.trim())).collect::<Vec<_>>().join("\n\n"))
+}
+
+fn read_all(root: &Path, paths: &[String]) -> Res<Vec<GuidelineFile>> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        match std::fs::read_to_string(root.join(path)) {
+            Ok(text) if !text.trim().is_empty() => files.push(GuidelineFile { path: path.clone(), text }),
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+            Err(e) => return Err(anyhow::Error::msg(format!("Unable to read the guidelines file `{}`: {}", path, e))),
+        }
+    }
+
+    Ok(files)
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use crate::base::diff::Diff;
+
+    use super::*;
+
+    fn changed(paths: &[&str]) -> Vec<FileDiff> {
+        let text = paths.iter().map(|p| format!("diff --git a/{p} b/{p}\n--- a/{p}\n+++ b/{p}\n@@ -1 +1 @@\n-a\n+b\n", p = p)).collect::<String>();
+
+        Diff::parse(&text).unwrap().files
+    }
+
+    fn paths(files: &[&GuidelineFile]) -> Vec<String> {
+        files.iter().map(|f| f.path.clone()).collect()
+    }
+
+    #[test]
+    fn selects_the_guidelines_of_the_changed_paths() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+
+        std::fs::create_dir_all(root.join("web")).unwrap();
+        std::fs::write(root.join("CONTRIBUTING.md"), "Be kind.\n").unwrap();
+        std::fs::write(root.join("web/STYLE.md"), "Use hooks.\n").unwrap();
+        std::fs::write(root.join("API.md"), "Version the endpoints.\n").unwrap();
+        std::fs::write(root.join("EMPTY.md"), "\n").unwrap();
+
+        let sets = [
+            GuidelineSet { paths: vec!["web/".to_string()], files: vec!["web/STYLE.md".to_string()] },
+            GuidelineSet { paths: vec!["server/api/**".to_string(), "web/api/**".to_string()], files: vec!["API.md".to_string(), "CONTRIBUTING.md".to_string()] },
+        ];
+
+        // Missing and empty files are skipped.
+        let guidelines = Guidelines::load(root, &["CONTRIBUTING.md".to_string(), "MISSING.md".to_string(), "EMPTY.md".to_string()], &sets).unwrap();
+
+        assert_eq!(paths(&guidelines.for_files(&changed(&["src/main.rs"]))), vec!["CONTRIBUTING.md"]);
+        assert_eq!(paths(&guidelines.for_files(&changed(&["src/main.rs", "web/app.tsx"]))), vec!["CONTRIBUTING.md", "web/STYLE.md"]);
+        assert_eq!(paths(&guidelines.for_files(&changed(&["server/api/routes.rs"]))), vec!["CONTRIBUTING.md", "API.md"]);
+        assert_eq!(paths(&guidelines.for_files(&changed(&["web/api/client.ts"]))), vec!["CONTRIBUTING.md", "web/STYLE.md", "API.md"]);
+
+        assert_eq!(guidelines.render_for(&changed(&["web/app.tsx"])).unwrap(), "## `CONTRIBUTING.md`\n\nBe kind.\n\n## `web/STYLE.md`\n\nUse hooks.");
+        assert!(guidelines.tokens(&changed(&["web/app.tsx"])) > guidelines.tokens(&changed(&["src/main.rs"])));
+
+        let none = Guidelines::load(root, &[], &sets).unwrap();
+        assert_eq!(none.render_for(&changed(&["src/main.rs"])), None);
+        assert_eq!(none.tokens(&changed(&["src/main.rs"])), 0);
+    }
+}
@@ -0,0 +1,89 @@
+//! The interrupt module.
+//!
+//! Handles Ctrl-C through a single, process-wide handler (see [`install`]): while a response is being streamed, a
+//! press stops the response (see [`pressed`]), so that the partial response can be kept; otherwise, the process exits
+//! with [`EXIT_INTERRUPTED`].
+
+use std::sync::OnceLock;
+
+use tokio::sync::watch;
+
+use super::types::EXIT_INTERRUPTED;
+
+// Statics.
+
+/// Counts the presses of Ctrl-C that were delivered to a pending [`pressed`].
+static PRESSES: OnceLock<watch::Sender<u64>> = OnceLock::new();
+
+// Handler.
+
+/// Installs the Ctrl-C handler (once, at startup).
+pub fn install() {
+    let presses = sender();
+
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if !press(presses) {
+                eprintln!();
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+        }
+    });
+}
+
+/// Resolves once Ctrl-C is pressed (after the first poll); until then, Ctrl-C does not exit the process.
+///
+/// Never resolves if the handler is not installed (e.g., in tests), in which case Ctrl-C keeps its default behavior.
+pub async fn pressed() {
+    let Some(presses) = PRESSES.get() else {
+        return std::future::pending().await;
+    };
+
+    let mut receiver = presses.subscribe();
+    let _ = receiver.changed().await;
+}
+
+// Helpers.
+
+fn sender() -> &'static watch::Sender<u64> {
+    PRESSES.get_or_init(|| watch::channel(0).0)
+}
+
+/// Delivers a press to the pending [`pressed`] futures, and returns whether there were any.
+fn press(presses: &watch::Sender<u64>) -> bool {
+    if presses.receiver_count() == 0 {
+        return false;
+    }
+
+    presses.send_modify(|count| *count += 1);
+
+    true
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_only_pending_streams() {
+        let presses = sender();
+
+        // With no stream to stop, the handler exits instead.
+        assert!(!press(presses));
+
+        let stream = tokio::spawn(pressed());
+
+        while presses.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert!(press(presses));
+        tokio::time::timeout(Duration::from_secs(5), stream).await.unwrap().unwrap();
+
+        assert_eq!(presses.receiver_count(), 0);
+    }
+}
@@ -0,0 +1,112 @@
+//! The live module.
+//!
+//! Shows a streamed response in the terminal as it arrives: the last screenful of the response (rendered as Markdown
+//! through a [`MadSkin`], e.g., from the findings parsed so far) is redrawn as the deltas come in, and erased once the
+//! response completes, so that the final output can be printed in its place.
+
+use std::{io::{IsTerminal, Write}, time::{Duration, Instant}};
+
+use termimad::{crossterm::{cursor, terminal::{self, ClearType}, QueueableCommand}, MadSkin};
+
+// Statics.
+
+/// How often the preview is redrawn (at most).
+static REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// Types.
+
+/// A live preview of a streamed response.
+pub struct LivePreview {
+    skin: MadSkin,
+    /// Renders the response (so far) as Markdown.
+    render: fn(&str) -> String,
+    text: String,
+    /// The number of terminal rows that the preview currently takes up.
+    rows: u16,
+    drawn_at: Option<Instant>,
+}
+
+impl LivePreview {
+    /// Starts a preview (or returns `None` if stdout is not a terminal).
+    pub fn start(render: fn(&str) -> String) -> Option<Self> {
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+
+        Some(Self {
+            skin: MadSkin::default(),
+            render,
+            text: String::new(),
+            rows: 0,
+            drawn_at: None,
+        })
+    }
+
+    /// Adds the delta to the response, and redraws the preview (unless it was redrawn very recently).
+    pub fn push(&mut self, delta: &str) {
+        self.text.push_str(delta);
+
+        if self.drawn_at.is_some_and(|t| t.elapsed() < REDRAW_INTERVAL) {
+            return;
+        }
+
+        // The preview is best-effort: failing to draw it should not fail the request.
+        let _ = self.draw();
+    }
+
+    /// Erases the preview, and returns the (whole, or partial) response.
+    pub fn finish(mut self) -> String {
+        let _ = self.erase();
+
+        std::mem::take(&mut self.text)
+    }
+
+    fn draw(&mut self) -> std::io::Result<()> {
+        // Some terminals (e.g., a bare pty) report a size of zero.
+        let (width, height) = terminal::size().ok().filter(|(w, h)| *w > 0 && *h > 0).unwrap_or((80, 24));
+
+        // Leave a column free, so that full lines do not wrap (and a couple of rows, so that the preview never scrolls).
+        let rendered = self.skin.text(&(self.render)(&self.text), Some(width.saturating_sub(1).max(20) as usize)).to_string();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        let visible = &lines[lines.len().saturating_sub(height.saturating_sub(2).max(1) as usize)..];
+
+        self.erase()?;
+
+        let mut stdout = std::io::stdout();
+
+        for line in visible {
+            write!(stdout, "{}\r\n", line)?;
+        }
+
+        stdout.flush()?;
+
+        self.rows = visible.len() as u16;
+        self.drawn_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Moves the cursor back to the first row of the preview, and clears everything below it.
+    fn erase(&mut self) -> std::io::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        let mut stdout = std::io::stdout();
+
+        stdout.queue(cursor::MoveUp(self.rows))?;
+        stdout.queue(cursor::MoveToColumn(0))?;
+        stdout.queue(terminal::Clear(ClearType::FromCursorDown))?;
+        stdout.flush()?;
+
+        self.rows = 0;
+
+        Ok(())
+    }
+}
+
+impl Drop for LivePreview {
+    fn drop(&mut self) {
+        let _ = self.erase();
+    }
+}
@@ -1,2 +1,18 @@
 pub mod types;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod diff;
+pub mod filter;
+pub mod context;
+pub mod citation;
+pub mod patch;
+pub mod severity;
+pub mod commit;
+pub mod pr;
+pub mod changelog;
+pub mod finding;
+pub mod report;
+pub mod live;
+pub mod interrupt;
+pub mod prompt;
+pub mod guidelines;
+pub mod focus;
\ No newline at end of file
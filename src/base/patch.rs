@@ -0,0 +1,203 @@
+//! The patch module.
+//!
+//! Reads standalone patches (e.g., a `.diff` file, or a `git format-patch` mbox series) for review.
+
+use super::{diff::Diff, types::Res};
+
+// Types.
+
+/// A single patch from a patch file or mbox series.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patch {
+    /// The `Subject:` of the patch email (if any).
+    pub subject: Option<String>,
+    /// The commit message body of the patch email (if any).
+    pub message: Option<String>,
+    pub diff: Diff,
+}
+
+// Reading.
+
+/// Reads the patch text from the specified path, or from stdin if the path is `-`.
+pub fn read(path: &str) -> Res<String> {
+    if path == "-" {
+        return Ok(std::io::read_to_string(std::io::stdin())?);
+    }
+
+    std::fs::read_to_string(path).map_err(|e| anyhow::Error::msg(format!("Unable to read the patch `{}`: {}", path, e)))
+}
+
+// Parsing.
+
+/// Splits the text into its patches.
+///
+/// An mbox series (as produced by `git format-patch --stdout`) yields one [`Patch`] per email; any other text is
+/// treated as a single patch.
+pub fn split(text: &str) -> Res<Vec<Patch>> {
+    let mut emails = Vec::new();
+    let mut current = String::new();
+
+    for line in text.split_inclusive('\n') {
+        if is_mbox_separator(line) && !current.trim().is_empty() {
+            emails.push(std::mem::take(&mut current));
+        }
+
+        current.push_str(line);
+    }
+
+    if !current.trim().is_empty() {
+        emails.push(current);
+    }
+
+    emails.iter().map(|email| parse(email)).filter(|p| p.as_ref().map_or(true, |p| !p.diff.is_empty())).collect()
+}
+
+fn parse(email: &str) -> Res<Patch> {
+    let diff = Diff::parse(email)?;
+
+    if !email.lines().next().is_some_and(is_mbox_separator) {
+        return Ok(Patch { diff, ..Default::default() });
+    }
+
+    let mut lines = email.lines().skip(1);
+    let mut subject: Option<String> = None;
+    let mut in_subject = false;
+
+    // Headers run until the first blank line; `Subject:` may be folded onto several lines.
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(rest) = line.strip_prefix("Subject:") {
+            subject = Some(strip_patch_prefix(rest.trim()).to_string());
+            in_subject = true;
+        } else if in_subject && line.starts_with([' ', '\t']) {
+            if let Some(s) = subject.as_mut() {
+                s.push(' ');
+                s.push_str(line.trim());
+            }
+        } else {
+            in_subject = false;
+        }
+    }
+
+    // The body runs until the `---` line that precedes the diffstat.
+    let message = lines.take_while(|l| *l != "---" && !l.starts_with("diff --git ")).collect::<Vec<_>>().join("\n").trim().to_string();
+
+    Ok(Patch {
+        subject,
+        message: if message.is_empty() { None } else { Some(message) },
+        diff,
+    })
+}
+
+// Helpers.
+
+fn is_mbox_separator(line: &str) -> bool {
+    // E.g., `From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001`.
+    line.strip_prefix("From ").and_then(|rest| rest.split_whitespace().next()).is_some_and(|sha| sha.len() == 40 && sha.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn strip_patch_prefix(subject: &str) -> &str {
+    match subject.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((tag, rest)) if tag.contains("PATCH") => rest.trim(),
+        _ => subject,
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SERIES: &str = "\
+From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+From: A U Thor <author@example.com>
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+Subject: [PATCH 0/2] A cover letter
+
+The cover letter has no diff.
+--
+2.39.5
+
+From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+From: A U Thor <author@example.com>
+Subject: [PATCH 1/2] Fix the greeting
+
+Say hello properly.
+
+Signed-off-by: A U Thor <author@example.com>
+---
+ a.txt | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-helo
++hello
+--
+2.39.5
+
+From 3333333333333333333333333333333333333333 Mon Sep 17 00:00:00 2001
+From: A U Thor <author@example.com>
+Subject: [RFC PATCH v2 2/2] Add a farewell, with a subject long enough to
+ be folded onto a second line
+Date: Mon, 1 Jan 2024 00:00:00 +0000
+
+---
+diff --git a/b.txt b/b.txt
+new file mode 100644
+--- /dev/null
++++ b/b.txt
+@@ -0,0 +1 @@
++goodbye
+--
+2.39.5
+";
+
+    #[test]
+    fn splits_mbox_series_into_patches() {
+        let patches = split(SERIES).unwrap();
+
+        // The cover letter has no diff, so it is not a patch.
+        assert_eq!(patches.len(), 2);
+
+        assert_eq!(patches[0].subject.as_deref(), Some("Fix the greeting"));
+        assert_eq!(patches[0].message.as_deref(), Some("Say hello properly.\n\nSigned-off-by: A U Thor <author@example.com>"));
+        assert_eq!(patches[0].diff.files.iter().map(|f| f.path()).collect::<Vec<_>>(), vec!["a.txt"]);
+
+        assert_eq!(patches[1].subject.as_deref(), Some("Add a farewell, with a subject long enough to be folded onto a second line"));
+        assert_eq!(patches[1].message, None);
+        assert_eq!(patches[1].diff.files.iter().map(|f| f.path()).collect::<Vec<_>>(), vec!["b.txt"]);
+    }
+
+    #[test]
+    fn treats_plain_diffs_as_a_single_patch() {
+        let text = "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-helo\n+hello\ndiff --git a/c.txt b/c.txt\n--- a/c.txt\n+++ b/c.txt\n@@ -1 +1 @@\n-x\n+y\n";
+        let patches = split(text).unwrap();
+
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].subject, None);
+        assert_eq!(patches[0].message, None);
+        assert_eq!(patches[0].diff.files.len(), 2);
+    }
+
+    #[test]
+    fn finds_no_patches_in_empty_input() {
+        assert!(split("").unwrap().is_empty());
+        assert!(split("\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn strips_patch_tags_only() {
+        assert_eq!(strip_patch_prefix("[PATCH] Fix it"), "Fix it");
+        assert_eq!(strip_patch_prefix("[PATCH v3 12/15] Fix it"), "Fix it");
+        assert_eq!(strip_patch_prefix("[RFC PATCH] Fix it"), "Fix it");
+        assert_eq!(strip_patch_prefix("[core] Fix it"), "[core] Fix it");
+        assert_eq!(strip_patch_prefix("Fix [PATCH] handling"), "Fix [PATCH] handling");
+    }
+}
@@ -0,0 +1,96 @@
+//! The pull request module.
+//!
+//! Finds the repository's pull request template (if any), which the generated descriptions fill in.
+
+use std::path::{Path, PathBuf};
+
+use super::types::Res;
+
+// Statics.
+
+/// The places that GitHub looks for a pull request template, relative to the root of the repository.
+static TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+];
+
+// Helpers.
+
+/// Finds the pull request template of the repository at `root` (the first of [`TEMPLATE_PATHS`] that exists).
+pub fn find_template(root: &Path) -> Option<(PathBuf, String)> {
+    TEMPLATE_PATHS.iter().map(|p| root.join(p)).find_map(|path| std::fs::read_to_string(&path).ok().map(|text| (path, text)))
+}
+
+/// Reads the template from the specified path, or finds the template of the repository at `root` if there is no path.
+pub fn read_template(path: Option<&str>, root: &Path) -> Res<Option<(PathBuf, String)>> {
+    match path {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| anyhow::Error::msg(format!("Unable to read the template `{}`: {}", path, e)))?;
+            Ok(Some((PathBuf::from(path), text)))
+        },
+        None => Ok(find_template(root)),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn finds_the_template_in_discovery_order() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+
+        assert!(find_template(root).is_none());
+
+        write(root, "pull_request_template.md", "root");
+        assert_eq!(find_template(root).unwrap().1, "root");
+
+        write(root, "docs/pull_request_template.md", "docs");
+        assert_eq!(find_template(root).unwrap().1, "docs");
+
+        write(root, ".github/pull_request_template.md", "github");
+        assert_eq!(find_template(root), Some((root.join(".github/pull_request_template.md"), "github".to_string())));
+    }
+
+    #[test]
+    fn looks_only_in_the_root() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+
+        write(root, "src/.github/pull_request_template.md", "nested");
+
+        assert!(find_template(root).is_none());
+        assert!(read_template(None, root).unwrap().is_none());
+    }
+
+    #[test]
+    fn prefers_the_explicit_path() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+
+        write(root, ".github/pull_request_template.md", "github");
+        write(root, "other.md", "other");
+
+        let path = root.join("other.md");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(read_template(Some(path), root).unwrap(), Some((PathBuf::from(path), "other".to_string())));
+        assert_eq!(read_template(None, root).unwrap().unwrap().1, "github");
+
+        let err = read_template(Some("/nonexistent/template.md"), root).unwrap_err();
+        assert!(err.to_string().starts_with("Unable to read the template `/nonexistent/template.md`"));
+    }
+}
@@ -0,0 +1,419 @@
+//! The prompt module.
+//!
+//! Renders the prompts that are sent to the model from [MiniJinja](https://docs.rs/minijinja) templates: the built-in
+//! ones, or the overrides in `<data path>/prompts/<name>.md` (e.g., `.augre/prompts/review.md`).
+
+use std::path::PathBuf;
+
+use minijinja::Environment;
+use serde::Serialize;
+
+use super::types::Res;
+
+// Statics.
+
+/// The names of the prompts (which are also the names of their override files, without the `.md`).
+pub static NAMES: &[&str] = &["review", "guidelines", "merge", "commit-msg", "pr-description", "changelog", "ask"];
+
+// Types.
+
+/// A commit, as a template variable.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitVar {
+    pub short_sha: String,
+    pub subject: String,
+    pub message: String,
+}
+
+/// The variables of a prompt template (each prompt only sets those that apply to it; the others are empty, and unset
+/// optional ones are undefined, so that `{{ branch }}` renders nothing rather than `none`).
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PromptVars {
+    /// The diff (with line numbers, for reviews).
+    pub diff: String,
+    /// The paths of the changed files.
+    pub files: Vec<String>,
+    /// The current branch (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// The messages of the commits (e.g., of a pull request).
+    pub commit_messages: Vec<String>,
+    /// The project's coding guidelines (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guidelines: Option<String>,
+    /// The (natural) language to respond in (if configured).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// The commits (e.g., to classify for a changelog).
+    pub commits: Vec<CommitVar>,
+    /// The additional context from the changed files (for reviews).
+    pub context: String,
+    /// The findings of the reviews of the chunks of a large diff, as JSON (to merge).
+    pub findings: String,
+    /// The name of the focused review pass (e.g., `security`), if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+    /// The checklist of the focused review pass (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checklist: Option<String>,
+    /// The commit message (or patch description) that accompanies the diff (for reviews).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// The prompt (for `ask`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// The pull request template (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Whether commit messages follow Conventional Commits.
+    pub conventional: bool,
+    /// The Conventional Commits types.
+    pub types: Vec<String>,
+    /// The maximum number of characters in the subject line of a commit message.
+    pub max_subject_length: usize,
+    /// The problems with a previous commit message (if any).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<String>,
+}
+
+/// The prompt templates, along with the variables that every prompt shares (e.g., the branch).
+pub struct Prompts {
+    dir: PathBuf,
+    globals: PromptVars,
+}
+
+impl Prompts {
+    pub fn new(data_path: &str, globals: PromptVars) -> Self {
+        Self {
+            dir: PathBuf::from(data_path).join("prompts"),
+            globals,
+        }
+    }
+
+    /// Gets the template of the prompt, along with the path of its override (or `None` if it is the built-in one).
+    pub fn template(&self, name: &str) -> Res<(String, Option<PathBuf>)> {
+        let path = self.dir.join(format!("{}.md", name));
+
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            return Ok((text, Some(path)));
+        }
+
+        Ok((builtin(name)?.to_string(), None))
+    }
+
+    /// Whether the template of the prompt uses the variable (e.g., `branch`, which is only looked up when it is used).
+    pub fn uses(&self, name: &str, var: &str) -> bool {
+        let Ok((template, _)) = self.template(name) else {
+            return false;
+        };
+
+        // A template that does not parse fails to render anyway.
+        environment().template_from_str(&template).is_ok_and(|t| t.undeclared_variables(false).contains(var))
+    }
+
+    /// Renders the prompt with the variables (and the shared ones, unless they are set).
+    pub fn render(&self, name: &str, vars: PromptVars) -> Res<String> {
+        let (template, path) = self.template(name)?;
+
+        let vars = PromptVars {
+            branch: vars.branch.or_else(|| self.globals.branch.clone()),
+            guidelines: vars.guidelines.or_else(|| self.globals.guidelines.clone()),
+            language: vars.language.or_else(|| self.globals.language.clone()),
+            ..vars
+        };
+
+        let prompt = environment().render_str(&template, vars).map_err(|e| {
+            let source = path.map(|p| format!("`{}`", p.display())).unwrap_or_else(|| "built-in".to_string());
+            anyhow::Error::msg(format!("Unable to render the `{}` prompt ({}): {}", name, source, e))
+        })?;
+
+        Ok(prompt.trim().to_string())
+    }
+}
+
+// Helpers.
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+
+    env
+}
+
+/// Gets the built-in template of the prompt.
+pub fn builtin(name: &str) -> Res<&'static str> {
+    match name {
+        "review" => Ok(REVIEW_PROMPT),
+        "guidelines" => Ok(GUIDELINES_PROMPT),
+        "merge" => Ok(MERGE_PROMPT),
+        "commit-msg" => Ok(COMMIT_PROMPT),
+        "pr-description" => Ok(PR_PROMPT),
+        "changelog" => Ok(CLASSIFY_PROMPT),
+        "ask" => Ok(ASK_PROMPT),
+        _ => Err(anyhow::Error::msg(format!("Invalid prompt specified (expected one of {}).", NAMES.join(", ")))),
+    }
+}
+
+// Templates.
+
+static REVIEW_PROMPT: &str = r#"
+Please perform a code review of the following diff (produced by `git diff` on my code), and provide suggestions for improvement.
+
+Each added (`+`) and context line of the diff is prefixed with its line number in the new version of the file; removed (`-`) lines have no line number.
+
+```
+{{ diff }}
+```
+{% if message %}
+
+The author described these changes with the following commit message (please also point out where the changes do not match it):
+
+```
+{{ message }}
+```
+{% endif %}
+{% if context %}
+
+For reference, here is some additional context from the new versions of the changed files (this is not part of the diff, so please only use it to understand the changes):
+
+{{ context }}
+{% endif %}
+
+{% if focus %}
+This review focuses on {{ focus }}, so please only report issues of that kind, prioritized by their impact on the code, and check the changes against the following checklist:
+
+{{ checklist }}
+
+Please rate each issue by its severity:
+{% else %}
+Please look for likely bugs and errors, prioritized by their impact on the code, and rate each by its severity:
+{% endif %}
+- `runtime`: a likely runtime bug or error (e.g., a panic, a crash, or a resource leak).
+- `logic`: a likely logic bug or error (e.g., an off-by-one error, or a wrong condition).
+- `style`: a likely style bug or error (e.g., naming, duplication, or readability).
+
+If possible, please also provide a suggested fix to each issue (as a code snippet, when that would be helpful).
+
+Please respond with only a JSON object (no code fences, or commentary) in the following format:
+
+{
+  "summary": "A sentence or two about the changes, and about the review.",
+  "findings": [
+    {
+      "file": "path/file.rs",
+      "line_range": [30, 32],
+      "severity": "runtime",
+      "category": "error-handling",
+      "message": "What the issue is, and why it matters.",
+{% if guidelines %}
+      "guideline": "The guideline that the finding enforces (or null).",
+{% endif %}
+      "suggested_fix": "The fix (or null)."
+    }
+  ]
+}
+
+The `line_range` uses the line numbers shown in the diff (and must point into the changed hunks), and the `category` is a short, lowercase description of what the finding is about (e.g., `error-handling`, `concurrency`, `performance`, or `naming`).  If there are no issues, please respond with an empty list of findings.
+{% if language %}
+
+Please write the summary and the messages in {{ language }}.
+{% endif %}
+"#;
+
+static GUIDELINES_PROMPT: &str = r#"
+The project has the following coding guidelines, which every change is expected to follow:
+
+{{ guidelines }}
+
+When reviewing, please report each change that violates one of these guidelines as a finding, and set its `guideline` to the guideline that it violates (by its heading, or by quoting it briefly).
+"#;
+
+static COMMIT_PROMPT: &str = r#"
+Please write a git commit message for the following staged changes (produced by `git diff --staged` on my code).
+
+```
+{{ diff }}
+```
+
+The message should have a subject line of at most {{ max_subject_length }} characters, written in the imperative mood (e.g., "Add ...", not "Added ..."), and without a trailing period.
+If the changes need more explanation, add a blank line and a short body (wrapped at 72 characters) that explains what changed and why.
+{% if conventional %}
+
+The subject line must follow Conventional Commits: `<type>[(<scope>)][!]: <description>`, where the type is one of {{ types | join(", ") }} (e.g., `fix(parser): handle empty input`).
+{% endif %}
+{% if feedback %}
+
+A previous attempt was rejected for the following reasons, so please address them:
+
+{{ feedback }}
+{% endif %}
+{% if language %}
+
+Please write the message in {{ language }}.
+{% endif %}
+
+Please respond with only the commit message (no code fences, quotes, or commentary).
+"#;
+
+static PR_PROMPT: &str = r#"
+Please write the description of a pull request{% if branch %} (from the branch `{{ branch }}`){% endif %} that contains the following commits (oldest first):
+
+{% for message in commit_messages %}
+- {{ message | replace("\n", "\n  ") }}
+{% endfor %}
+
+The aggregate diff of the pull request (produced by `git diff` between the merge-base and the head of the branch) is:
+
+```
+{{ diff }}
+```
+{% if template %}
+
+The repository has the following pull request template, so please fill in its headings and sections (keeping them in the same order, and checking off any checklist items that the changes clearly satisfy) rather than inventing your own; remove any HTML comments (`<!-- ... -->`) that only give instructions:
+
+```markdown
+{{ template }}
+```
+{% else %}
+
+The description should have the following Markdown sections:
+
+```
+## Summary
+(What the pull request does, in a few sentences.)
+
+## Motivation
+(Why the change is needed.)
+
+## Notable Changes
+- (The changes that reviewers should pay attention to.)
+
+## Risk
+(What could break, and how risky the change is.)
+
+## Testing
+(How the change was, or should be, tested.)
+```
+{% endif %}
+
+Please be specific and concise: refer to the changed files, types, and functions by name (e.g., `path/file.rs`), and do not invent details that are not supported by the commits or the diff (say "N/A" instead).
+{% if language %}
+
+Please write the description in {{ language }}.
+{% endif %}
+
+Please respond with only the Markdown pull request description (no code fences around it, or commentary).
+"#;
+
+static CLASSIFY_PROMPT: &str = r#"
+Please classify each of the following commits (listed by their short sha and commit message) for a changelog:
+
+{% for commit in commits %}
+- {{ commit.short_sha }} {{ commit.message | replace("\n", "\n  ") }}
+{% endfor %}
+
+The kinds are:
+- `breaking`: a change that breaks compatibility for users (e.g., a removed or renamed API, option, or behavior).
+- `feature`: a new feature, or a new capability of an existing feature.
+- `fix`: a bug fix.
+- `changed`: any other change that users would notice (e.g., performance, or a change in behavior).
+- `internal`: a change that users would not notice (e.g., tests, documentation, CI, refactoring, or dependency bumps).
+
+Please respond with one line per commit, in the format `<short sha> <kind>` (e.g., `abc1234 fix`), and nothing else.
+"#;
+
+static MERGE_PROMPT: &str = r#"
+The following are the findings of code reviews of separate parts of a single diff (produced by `git diff` on my code){% if files %} to {{ files | join(", ") }}{% endif %}:
+
+```json
+{{ findings }}
+```
+
+Please merge them into a single code review: combine the findings that describe the same issue (keeping the most precise `line_range`, and the clearest message and fix), and order the findings by their impact on the code.
+Please keep the `file`, `line_range`, `severity`, and `category` of each finding as they are, and do not add new findings.
+
+Please respond with only a JSON object (no code fences, or commentary) in the same format, with a `summary` of a sentence or two about the whole review.
+{% if language %}
+
+Please write the summary and the messages in {{ language }}.
+{% endif %}
+"#;
+
+static ASK_PROMPT: &str = r#"{{ prompt }}
+{% if language %}
+
+Please respond in {{ language }}.
+{% endif %}
+"#;
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompts(overrides: &[(&str, &str)]) -> (tempfile::TempDir, Prompts) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("prompts")).unwrap();
+
+        for (name, text) in overrides {
+            std::fs::write(dir.path().join("prompts").join(format!("{}.md", name)), text).unwrap();
+        }
+
+        let globals = PromptVars { language: Some("French".to_string()), ..Default::default() };
+        let prompts = Prompts::new(&dir.path().to_string_lossy(), globals);
+
+        (dir, prompts)
+    }
+
+    fn ask(prompt: &str) -> PromptVars {
+        PromptVars { prompt: Some(prompt.to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn prefers_overrides_to_the_built_in_templates() {
+        let (dir, prompts) = prompts(&[("ask", "Q: {{ prompt }} (on {{ branch }})")]);
+
+        assert_eq!(prompts.template("ask").unwrap().1, Some(dir.path().join("prompts").join("ask.md")));
+        assert_eq!(prompts.template("review").unwrap().1, None);
+
+        let vars = PromptVars { branch: Some("main".to_string()), ..ask("Why?") };
+        assert_eq!(prompts.render("ask", vars).unwrap(), "Q: Why? (on main)");
+
+        // The shared variables apply to the built-in templates too.
+        let (_dir, prompts) = self::prompts(&[]);
+        assert_eq!(prompts.render("ask", ask("Why?")).unwrap(), "Why?\n\nPlease respond in French.");
+    }
+
+    #[test]
+    fn renders_missing_variables_as_empty() {
+        let (_dir, prompts) = prompts(&[("ask", "{{ prompt }} [{{ branch }}] [{{ message }}] [{{ nonexistent }}]")]);
+
+        assert_eq!(prompts.render("ask", ask("Why?")).unwrap(), "Why? [] [] []");
+    }
+
+    #[test]
+    fn reports_render_errors_with_their_template() {
+        let (dir, prompts) = prompts(&[("ask", "{% if prompt %}unterminated")]);
+
+        let err = prompts.render("ask", ask("Why?")).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("Unable to render the `ask` prompt (`{}`): ", dir.path().join("prompts").join("ask.md").display())), "{}", err);
+
+        let err = prompts.render("nonexistent", PromptVars::default()).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid prompt specified"), "{}", err);
+    }
+
+    #[test]
+    fn finds_the_variables_that_templates_use() {
+        let (_dir, prompts) = prompts(&[("commit-msg", "{% if branch %}On {{ branch }}: {% endif %}{{ diff }}"), ("changelog", "{% if %}")]);
+
+        assert!(prompts.uses("pr-description", "branch"));
+        assert!(prompts.uses("commit-msg", "branch"));
+        assert!(!prompts.uses("ask", "branch"));
+        assert!(!prompts.uses("changelog", "branch"));
+        assert!(!prompts.uses("nonexistent", "branch"));
+    }
+}
@@ -0,0 +1,397 @@
+//! The report module.
+//!
+//! Serializes the findings of one or more reviews for machines: JSON, SARIF (for code scanning dashboards), and JUnit
+//! XML (for test reporting), in addition to the terminal Markdown.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{finding::{Finding, Review}, severity::Severity, types::Res};
+
+// Statics.
+
+static SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+static INFORMATION_URI: &str = "https://github.com/twitchax/augre";
+
+// Types.
+
+/// The format of a review report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Markdown, in the numbered three-category format.
+    #[default]
+    Markdown,
+    /// The findings as JSON.
+    Json,
+    /// SARIF 2.1.0.
+    Sarif,
+    /// JUnit XML, with one test case per finding.
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "json" => Ok(ReportFormat::Json),
+            "sarif" => Ok(ReportFormat::Sarif),
+            "junit" | "xml" => Ok(ReportFormat::Junit),
+            _ => Err(anyhow::Error::msg("Invalid format specified (expected `markdown`, `json`, `sarif`, or `junit`).")),
+        }
+    }
+}
+
+/// A single review in a report (e.g., of one commit of a range, or of one patch of a series).
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportItem {
+    /// What was reviewed (e.g., the commit), if the report has several reviews.
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub review: Review,
+}
+
+/// The reviews of a single `review` run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub reviews: Vec<ReportItem>,
+}
+
+impl ReportItem {
+    /// Renders the review as Markdown (under a heading, if it has a title).
+    pub fn to_markdown(&self) -> String {
+        match &self.title {
+            Some(title) => format!("# {}\n\n{}", title, self.review.to_markdown()),
+            None => self.review.to_markdown(),
+        }
+    }
+}
+
+impl Report {
+    pub fn push(&mut self, title: Option<String>, review: Review) {
+        self.reviews.push(ReportItem { title, review });
+    }
+
+    /// All of the findings across the reviews.
+    pub fn findings(&self) -> impl Iterator<Item = &Finding> {
+        self.reviews.iter().flat_map(|item| item.review.findings.iter())
+    }
+}
+
+/// Collects the reviews of a run, and writes them out in the requested format once it completes.
+pub struct ReportWriter {
+    format: ReportFormat,
+    output: Option<String>,
+    report: Report,
+}
+
+impl ReportWriter {
+    pub fn new(format: ReportFormat, output: Option<String>) -> Self {
+        Self { format, output, report: Report::default() }
+    }
+
+    /// Adds a review to the report, and returns its Markdown if it should be shown in the terminal now (i.e., unless a
+    /// machine-readable report is written to stdout at the end).
+    pub fn add(&mut self, title: Option<String>, review: Review) -> Option<String> {
+        self.report.push(title, review);
+
+        if self.format == ReportFormat::Markdown || self.output.is_some() {
+            self.report.reviews.last().map(ReportItem::to_markdown)
+        } else {
+            None
+        }
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Writes the report to the output file (returning its path), or returns the report to print (for the
+    /// machine-readable formats without an output file).
+    pub fn finish(&self) -> Res<Output> {
+        match &self.output {
+            Some(path) => {
+                std::fs::write(path, render(&self.report, self.format)?)?;
+                Ok(Output::File(path.clone()))
+            },
+            None if self.format == ReportFormat::Markdown => Ok(Output::None),
+            None => Ok(Output::Stdout(render(&self.report, self.format)?)),
+        }
+    }
+}
+
+/// Where a report ended up.
+pub enum Output {
+    /// Nothing else to write (the Markdown was already shown in the terminal).
+    None,
+    File(String),
+    Stdout(String),
+}
+
+// Rendering.
+
+/// Renders the report in the specified format.
+pub fn render(report: &Report, format: ReportFormat) -> Res<String> {
+    match format {
+        ReportFormat::Markdown => Ok(to_markdown(report)),
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(report)?),
+        ReportFormat::Sarif => Ok(serde_json::to_string_pretty(&to_sarif(report))?),
+        ReportFormat::Junit => Ok(to_junit(report)),
+    }
+}
+
+/// Renders the report as Markdown (with a heading per review, if they have titles).
+pub fn to_markdown(report: &Report) -> String {
+    report.reviews.iter().map(ReportItem::to_markdown).collect::<Vec<_>>().join("\n")
+}
+
+/// Renders the report as a SARIF 2.1.0 log, with one rule per category, and the severities mapped to SARIF levels.
+pub fn to_sarif(report: &Report) -> Value {
+    let mut categories = report.findings().map(|f| f.category.as_str()).collect::<Vec<_>>();
+    categories.sort_unstable();
+    categories.dedup();
+
+    let rules = categories.iter().map(|c| json!({ "id": c, "name": c, "shortDescription": { "text": format!("augre: {}", c) } })).collect::<Vec<_>>();
+
+    let results = report
+        .findings()
+        .map(|finding| {
+            let mut result = json!({
+                "ruleId": finding.category,
+                "ruleIndex": categories.iter().position(|c| *c == finding.category),
+                "level": sarif_level(finding.severity),
+                "message": { "text": finding_text(finding) },
+                "properties": { "severity": finding.severity.to_string(), "category": finding.category },
+            });
+
+            if let Some(guideline) = &finding.guideline {
+                result["properties"]["guideline"] = json!(guideline);
+            }
+
+            if let Some(pass) = &finding.pass {
+                result["properties"]["pass"] = json!(pass);
+            }
+
+            if let Some(file) = &finding.file {
+                let mut location = json!({ "artifactLocation": { "uri": file } });
+
+                if let Some(range) = finding.line_range {
+                    location["region"] = json!({ "startLine": range.start, "endLine": range.end });
+                }
+
+                result["locations"] = json!([{ "physicalLocation": location }]);
+            }
+
+            result
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "augre",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": INFORMATION_URI,
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Renders the report as JUnit XML, with one test suite per review, and one failed test case per finding (whose
+/// `type` is the severity); a review without findings has a single passing test case.
+pub fn to_junit(report: &Report) -> String {
+    let tests = report.reviews.iter().map(|item| item.review.findings.len().max(1)).sum::<usize>();
+    let failures = report.findings().count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites name=\"augre\" tests=\"{}\" failures=\"{}\">\n", tests, failures));
+
+    for item in &report.reviews {
+        let name = item.title.as_deref().unwrap_or("review");
+        let findings = &item.review.findings;
+
+        xml.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n", escape(name), findings.len().max(1), findings.len()));
+
+        if findings.is_empty() {
+            xml.push_str(&format!("    <testcase name=\"{}\" classname=\"augre\"/>\n", escape(name)));
+        }
+
+        for finding in findings {
+            let case = match finding.location() {
+                Some(location) => format!("{}: {}", location, first_line(&finding.message)),
+                None => first_line(&finding.message).to_string(),
+            };
+
+            let class = match &finding.pass {
+                Some(pass) => format!("{}.{}", pass, finding.category),
+                None => finding.category.clone(),
+            };
+
+            xml.push_str(&format!("    <testcase name=\"{}\" classname=\"augre.{}\"", escape(&case), escape(&class)));
+
+            if let Some(file) = &finding.file {
+                xml.push_str(&format!(" file=\"{}\"", escape(file)));
+            }
+
+            if let Some(range) = finding.line_range {
+                xml.push_str(&format!(" line=\"{}\"", range.start));
+            }
+
+            xml.push_str(">\n");
+            xml.push_str(&format!("      <failure type=\"{}\" message=\"{}\">{}</failure>\n", finding.severity, escape(first_line(&finding.message)), escape(&finding_text(finding))));
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+// Helpers.
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Runtime => "error",
+        Severity::Logic => "warning",
+        Severity::Style => "note",
+    }
+}
+
+/// The message of the finding, along with the guideline that it enforces and its suggested fix (if any).
+fn finding_text(finding: &Finding) -> String {
+    let mut text = finding.message.clone();
+
+    if let Some(guideline) = &finding.guideline {
+        text.push_str(&format!("\n\nGuideline: {}", guideline));
+    }
+
+    if let Some(fix) = &finding.suggested_fix {
+        text.push_str(&format!("\n\nSuggested fix:\n{}", fix));
+    }
+
+    text
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::{*, super::finding::LineRange};
+
+    fn finding(severity: Severity, category: &str, message: &str) -> Finding {
+        Finding {
+            file: Some("src/lib.rs".to_string()),
+            line_range: Some(LineRange { start: 3, end: 5 }),
+            severity,
+            category: category.to_string(),
+            message: message.to_string(),
+            suggested_fix: None,
+            guideline: None,
+            pass: None,
+        }
+    }
+
+    fn report() -> Report {
+        let mut report = Report::default();
+
+        report.push(Some("`abc123` Add a parser".to_string()), Review {
+            summary: Some("Adds a parser.".to_string()),
+            findings: vec![
+                finding(Severity::Runtime, "error-handling", "Panics on `<empty>` input & \"quoted\" paths.\nSecond line."),
+                Finding { file: None, line_range: None, pass: Some("security".to_string()), ..finding(Severity::Logic, "logic", "Off by one.") },
+                Finding { guideline: Some("Use `snake_case`.".to_string()), suggested_fix: Some("let x_y = 1;".to_string()), ..finding(Severity::Style, "naming", "Rename `xY`.") },
+            ],
+        });
+        report.push(Some("`def456` Fix a typo".to_string()), Review { summary: Some("Fixes a typo.".to_string()), findings: Vec::new() });
+
+        report
+    }
+
+    #[test]
+    fn renders_markdown_with_a_heading_per_review() {
+        let markdown = render(&report(), ReportFormat::Markdown).unwrap();
+
+        assert!(markdown.starts_with("# `abc123` Add a parser\n\n"));
+        assert!(markdown.contains("\n# `def456` Fix a typo\n\n"));
+    }
+
+    #[test]
+    fn renders_json() {
+        let json = serde_json::from_str::<Value>(&render(&report(), ReportFormat::Json).unwrap()).unwrap();
+
+        assert_eq!(json["reviews"][0]["title"], "`abc123` Add a parser");
+        assert_eq!(json["reviews"][0]["findings"].as_array().unwrap().len(), 3);
+        assert_eq!(json["reviews"][1]["findings"], json!([]));
+    }
+
+    #[test]
+    fn renders_sarif() {
+        let sarif = serde_json::from_str::<Value>(&render(&report(), ReportFormat::Sarif).unwrap()).unwrap();
+        let run = &sarif["runs"][0];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().iter().map(|r| r["id"].as_str().unwrap()).collect::<Vec<_>>(), vec!["error-handling", "logic", "naming"]);
+
+        // The severities map to SARIF levels.
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.iter().map(|r| r["level"].as_str().unwrap()).collect::<Vec<_>>(), vec!["error", "warning", "note"]);
+
+        assert_eq!(results[0]["ruleIndex"], 0);
+        assert_eq!(results[0]["locations"][0]["physicalLocation"], json!({ "artifactLocation": { "uri": "src/lib.rs" }, "region": { "startLine": 3, "endLine": 5 } }));
+        assert!(results[1].get("locations").is_none());
+        assert_eq!(results[1]["properties"]["pass"], "security");
+        assert_eq!(results[2]["properties"]["guideline"], "Use `snake_case`.");
+        assert_eq!(results[2]["message"]["text"], "Rename `xY`.\n\nGuideline: Use `snake_case`.\n\nSuggested fix:\nlet x_y = 1;");
+    }
+
+    #[test]
+    fn renders_junit() {
+        let xml = render(&report(), ReportFormat::Junit).unwrap();
+
+        assert!(xml.contains("<testsuites name=\"augre\" tests=\"4\" failures=\"3\">"));
+        assert!(xml.contains("<testsuite name=\"`abc123` Add a parser\" tests=\"3\" failures=\"3\">"));
+
+        // The names and messages are escaped (and only the first line of a message names the test case).
+        assert!(xml.contains("<testcase name=\"src/lib.rs:3-5: Panics on `&lt;empty&gt;` input &amp; &quot;quoted&quot; paths.\" classname=\"augre.error-handling\" file=\"src/lib.rs\" line=\"3\">"));
+        assert!(xml.contains("<failure type=\"runtime\" message=\"Panics on `&lt;empty&gt;` input &amp; &quot;quoted&quot; paths.\">Panics on `&lt;empty&gt;` input &amp; &quot;quoted&quot; paths.\nSecond line.</failure>"));
+        assert!(xml.contains("<testcase name=\"Off by one.\" classname=\"augre.security.logic\">"));
+
+        // A review without findings passes.
+        assert!(xml.contains("<testsuite name=\"`def456` Fix a typo\" tests=\"1\" failures=\"0\">\n    <testcase name=\"`def456` Fix a typo\" classname=\"augre\"/>"));
+    }
+
+    #[test]
+    fn writes_machine_readable_reports_to_stdout_only() {
+        let mut writer = ReportWriter::new(ReportFormat::Sarif, None);
+        let item = report().reviews.remove(0);
+
+        // Nothing else is shown (so that stdout is only the report).
+        assert_eq!(writer.add(item.title, item.review), None);
+
+        let Output::Stdout(text) = writer.finish().unwrap() else {
+            panic!("The report should be written to stdout.");
+        };
+
+        assert_eq!(serde_json::from_str::<Value>(&text).unwrap()["runs"][0]["results"].as_array().unwrap().len(), 3);
+    }
+}
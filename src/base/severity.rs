@@ -0,0 +1,127 @@
+//! The severity module.
+//!
+//! Finds the suggestions in a review (in the three-category format requested by the review prompt), and rates them
+//! by the category they are listed under.
+
+use std::{str::FromStr, sync::OnceLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Statics.
+
+fn heading_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"(?i)likely\s+(runtime|logic|style)\s+bugs").unwrap())
+}
+
+fn bullet_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"^(\s*)(?:[-*•]|\d+[.)])\s+(.*)$").unwrap())
+}
+
+// Types.
+
+/// How severe a review suggestion is, from least to most severe.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// A likely style bug.
+    #[serde(alias = "style")]
+    Style,
+    /// A likely logic bug.
+    #[serde(alias = "logic")]
+    Logic,
+    /// A likely runtime bug.
+    #[serde(alias = "runtime")]
+    Runtime,
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "style" => Ok(Severity::Style),
+            "logic" => Ok(Severity::Logic),
+            "runtime" => Ok(Severity::Runtime),
+            _ => Err(anyhow::Error::msg("Invalid severity specified.")),
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Style => write!(f, "style"),
+            Severity::Logic => write!(f, "logic"),
+            Severity::Runtime => write!(f, "runtime"),
+        }
+    }
+}
+
+/// A single suggestion from a review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub severity: Severity,
+    pub text: String,
+}
+
+// Parsing.
+
+/// Finds the top-level suggestions in the review, skipping placeholders such as "- None".
+pub fn suggestions(review: &str) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+
+    // The bullets of the current section (along with their indentation), so that only the outermost ones are kept.
+    let mut section: Option<(Severity, Vec<(usize, String)>)> = None;
+    let mut in_fence = false;
+
+    for line in review.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+
+        if in_fence {
+            continue;
+        }
+
+        if let Some(captures) = heading_regex().captures(line) {
+            flush(section.take(), &mut suggestions);
+            section = Some((captures[1].parse().unwrap_or(Severity::Style), Vec::new()));
+            continue;
+        }
+
+        let Some((_, bullets)) = section.as_mut() else {
+            continue;
+        };
+
+        if let Some(captures) = bullet_regex().captures(line) {
+            bullets.push((captures[1].len(), captures[2].trim().to_string()));
+        }
+    }
+
+    flush(section, &mut suggestions);
+
+    suggestions
+}
+
+// Helpers.
+
+fn flush(section: Option<(Severity, Vec<(usize, String)>)>, suggestions: &mut Vec<Suggestion>) {
+    let Some((severity, bullets)) = section else {
+        return;
+    };
+
+    let Some(indent) = bullets.iter().map(|(indent, _)| *indent).min() else {
+        return;
+    };
+
+    suggestions.extend(bullets.into_iter().filter(|(i, text)| *i == indent && !is_placeholder(text)).map(|(_, text)| Suggestion { severity, text }));
+}
+
+fn is_placeholder(text: &str) -> bool {
+    let text = text.trim_matches(|c: char| c == '*' || c == '_' || c == '.' || c.is_whitespace()).to_ascii_lowercase();
+
+    text.is_empty() || text == "none" || text == "n/a" || text.starts_with("none ") || text.starts_with("no ") || text.starts_with("nothing")
+}
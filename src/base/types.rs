@@ -1,6 +1,6 @@
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
-use tokio::process::Command;
+use tokio::{io::AsyncWriteExt, process::Command};
 use std::{process::{Stdio, ExitStatus}, str::FromStr};
 use yansi::Paint;
 use dialoguer::Confirm;
@@ -14,6 +14,44 @@ pub static TAB: &str = "  ";
 pub type Res<T> = Result<T, anyhow::Error>;
 pub type Void = Res<()>;
 
+/// The exit code when a command ran, but found problems (e.g., a review with findings at or above `--fail-on`).
+pub static EXIT_FINDINGS: i32 = 1;
+/// The exit code when a command failed to run.
+pub static EXIT_ERROR: i32 = 2;
+/// The exit code when a command was stopped early (with Ctrl-C).
+pub static EXIT_INTERRUPTED: i32 = 130;
+
+/// The error of a command that ran, but whose result should fail the process (e.g., a review that found problems).
+#[derive(Debug)]
+pub struct FoundProblems(pub String);
+
+impl std::fmt::Display for FoundProblems {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FoundProblems {}
+
+/// The error of a command whose response was stopped early (with Ctrl-C), after showing the partial response.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The response was stopped early (with Ctrl-C), so it is incomplete.")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+// Token helpers.
+
+/// Roughly estimates the number of LLM tokens in the specified text (about four characters per token).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
 // Mode helpers.
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -55,6 +93,138 @@ impl Mode {
     }
 }
 
+// Diff target helpers.
+
+/// The set of changes that a review (or other diff-based command) operates on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffTarget {
+    /// The unstaged changes in the working tree (`git diff`).
+    WorkingTree,
+    /// The staged changes (`git diff --staged`).
+    Staged,
+    /// The changes introduced by a single commit, against its first parent (`git show <sha>`, which also handles root
+    /// commits).
+    Commit(String),
+    /// The changes between two revisions (`git diff <from>..<to>`).
+    Range(String),
+    /// The changes on `HEAD` since its merge-base with the specified ref (`git diff <base>...HEAD`).
+    MergeBase(String),
+}
+
+impl DiffTarget {
+    /// Resolves the [`DiffTarget`] from the `review` command arguments.
+    pub fn from_args(target: Option<String>, staged: bool, base: Option<String>) -> Self {
+        match (target, staged, base) {
+            (_, true, _) => DiffTarget::Staged,
+            (_, _, Some(base)) => DiffTarget::MergeBase(base),
+            (Some(target), _, _) if target.contains("..") => DiffTarget::Range(target),
+            (Some(target), _, _) => DiffTarget::Commit(target),
+            (None, _, _) => DiffTarget::WorkingTree,
+        }
+    }
+}
+
+impl DiffTarget {
+    /// The revision that holds the "new" side of the diff (or `None` for the working tree).
+    pub fn new_revision(&self) -> Option<String> {
+        match self {
+            DiffTarget::WorkingTree => None,
+            DiffTarget::Staged => Some(String::new()),
+            DiffTarget::Commit(sha) => Some(sha.clone()),
+            DiffTarget::Range(range) => {
+                let to = range.rsplit("..").next().unwrap_or_default().trim_start_matches('.');
+                Some(if to.is_empty() { "HEAD".to_string() } else { to.to_string() })
+            },
+            DiffTarget::MergeBase(_) => Some("HEAD".to_string()),
+        }
+    }
+}
+
+impl DiffTarget {
+    /// The commit range that the target covers (e.g., for reviewing each commit separately).
+    pub fn commit_range(&self) -> Res<String> {
+        match self {
+            // `<sha>^!` is the commit alone (unlike `<sha>^..<sha>`, which fails on a root commit).
+            DiffTarget::Commit(sha) => Ok(format!("{}^!", sha)),
+            DiffTarget::Range(range) => Ok(range.clone()),
+            DiffTarget::MergeBase(base) => Ok(format!("{}..HEAD", base)),
+            DiffTarget::WorkingTree | DiffTarget::Staged => Err(anyhow::Error::msg("The working tree and staged changes are not commits: please specify a commit range (or `--base`).")),
+        }
+    }
+}
+
+impl std::fmt::Display for DiffTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffTarget::WorkingTree => write!(f, "working tree"),
+            DiffTarget::Staged => write!(f, "staged"),
+            DiffTarget::Commit(sha) => write!(f, "{}", sha),
+            DiffTarget::Range(range) => write!(f, "{}", range),
+            DiffTarget::MergeBase(base) => write!(f, "{}...HEAD", base),
+        }
+    }
+}
+
+// Commit helpers.
+
+/// A single commit, as listed by the VCS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+    /// The full commit message (subject and body).
+    pub message: String,
+    /// The commit date (`YYYY-MM-DD`, in the committer's time zone).
+    pub date: String,
+}
+
+/// Formats a unix timestamp (in seconds, already adjusted for the time zone) as a `YYYY-MM-DD` date.
+pub fn format_date(seconds: i64) -> String {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Context helpers.
+
+/// How much of the surrounding code is sent along with each changed file.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ContextMode {
+    /// Only the diff itself.
+    None,
+    /// The whole file when it is small, and the enclosing items otherwise.
+    #[default]
+    Auto,
+    /// The whole file.
+    File,
+    /// The items (functions, impls, classes, ...) that enclose each hunk.
+    Item,
+}
+
+impl FromStr for ContextMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(ContextMode::None),
+            "auto" => Ok(ContextMode::Auto),
+            "file" => Ok(ContextMode::File),
+            "item" => Ok(ContextMode::Item),
+            _ => Err(anyhow::Error::msg("Invalid context mode specified.")),
+        }
+    }
+}
+
 // Traits for various internal functionality.
 
 pub trait HasName {
@@ -81,25 +251,25 @@ impl<T> EnsurableEntity for T
 {
     async fn ensure(&self, confirm: bool) -> Result<()> {
         let name = self.name();
-        print!("Checking if `{}` is present ... ", Paint::blue(name));
+        eprint!("Checking if `{}` is present ... ", Paint::blue(name));
 
         if self.is_present().await? {
-            println!("💯!");
+            eprintln!("💯!");
             return Ok(())
         }
 
-        println!("{}!", Paint::red("✘"));
+        eprintln!("{}!", Paint::red("✘"));
         
         if confirm && !Confirm::new().with_prompt(format!("{}`{}` is not present: do you want me to make it so?", TAB, Paint::blue(name))).interact()? {
-            println!("{}Skipping ...", TAB);
+            eprintln!("{}Skipping ...", TAB);
             return Err(anyhow::anyhow!("User skipped required operation."));
         }
         
-        println!("{}Ensuring presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution"));
+        eprintln!("{}Ensuring presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution"));
 
         self.make_present().await?;
         
-        println!("{}Successfully ensured `{}`.", TAB, Paint::blue(name));
+        eprintln!("{}Successfully ensured `{}`.", TAB, Paint::blue(name));
         
         Ok(())
     }
@@ -116,20 +286,20 @@ impl<T> RemovableEntity for T
         let name = self.name();
 
         if !self.is_present().await? {
-            println!("{} `{}` is not running!", TAB, Paint::blue(name));
+            eprintln!("{} `{}` is not running!", TAB, Paint::blue(name));
             return Ok(())
         }
 
         if confirm && !Confirm::new().with_prompt(format!("{}`{}` is present: do you want me to remove it?", TAB, Paint::blue(name))).interact()? {
-            println!("{}Skipping ...", TAB);
+            eprintln!("{}Skipping ...", TAB);
             return Ok(())
         }
 
-        println!("{}Removing presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution [and sudo]"));
+        eprintln!("{}Removing presence of `{}` ({}) ...", TAB, Paint::blue(name), Paint::yellow("you may need to interact with the execution [and sudo]"));
 
         self.make_not_present().await?;
         
-        println!("{}Successfully removed `{}`.", TAB, Paint::blue(name));
+        eprintln!("{}Successfully removed `{}`.", TAB, Paint::blue(name));
         
         Ok(())
     }
@@ -168,4 +338,41 @@ pub(crate) async fn is_binary_present<T>(s: &T) -> Result<bool>
         .stderr(Stdio::null())
         .stdin(Stdio::null())
         .status().await?.success())
-}
\ No newline at end of file
+}
+
+pub(crate) async fn run_output<S>(program: &str, args: &[S]) -> Result<String>
+    where S: AsRef<str>
+{
+    let args = args.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+
+    let output = Command::new(program)
+        .args(&args)
+        .output().await
+        .map_err(|e| anyhow::Error::msg(format!("Unable to run `{} {}`: {}", program, args.join(" "), e)))?;
+
+    if !output.status.success() {
+        return Err(anyhow::Error::msg(format!("The exit code of the `{} {}` operation was not successful: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Runs the program with the input on its stdin (showing its output, e.g., of git hooks), and fails if it does not
+/// succeed.
+pub(crate) async fn run_with_input<S>(program: &str, args: &[S], input: &str) -> Result<()>
+    where S: AsRef<str>
+{
+    let args = args.iter().map(|a| a.as_ref()).collect::<Vec<_>>();
+
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::Error::msg(format!("Unable to run `{} {}`: {}", program, args.join(" "), e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).await?;
+    }
+
+    child.wait().await.map_status().map_err(|e| anyhow::Error::msg(format!("Unable to run `{} {}`: {}", program, args.join(" "), e)))
+}
@@ -2,10 +2,7 @@
 
 // Directives.
 #![warn(rustdoc::broken_intra_doc_links, rust_2018_idioms, clippy::all)]
-#![allow(incomplete_features)]
-#![feature(async_fn_in_trait)]
-#![feature(fs_try_exists)]
-#![feature(vec_into_raw_parts)]
+#![allow(async_fn_in_trait)]
 
 // Modules.
 
@@ -14,9 +11,12 @@ pub mod services;
 
 // Imports.
 
-use base::{types::{Void, EnsurableEntity, Mode, RemovableEntity}, config::Config};
-use clap::{command, Parser, Subcommand};
-use services::{git::Git, gpt::Gpt, cria::Cria};
+use std::path::PathBuf;
+
+use base::{types::{Void, Res, EnsurableEntity, IsRemovable, Mode, RemovableEntity, DiffTarget, ContextMode, FoundProblems, Interrupted, EXIT_FINDINGS, EXIT_ERROR, EXIT_INTERRUPTED, TAB}, config::Config, diff::Diff, filter::{PathFilter, SkippedFile}, context::{self, FileContext}, citation, patch, finding::Review, severity::Severity, report::{self, ReportFormat, ReportWriter}, commit::{self, CommitStyle}, pr, changelog::{self, ChangeKind, Version}, prompt::{self, Prompts, PromptVars}, guidelines::Guidelines, focus::{self, FocusPass}};
+use clap::{Parser, Subcommand};
+use services::{vcs::{self, AnyVcs, DiffOptions, Vcs}, gpt::{Generated, Gpt, ReviewInput}, llm::{AnyProvider, ChatMessage, ModelSettings, Provider}, cria::Cria, hook::{self, Hook, HookKind}};
+use dialoguer::{Editor, Select};
 use termimad::MadSkin;
 use yansi::Paint;
 
@@ -39,14 +39,73 @@ struct Args {
     #[clap(long = "yes", short = 'y', action)]
     skip_confirm: bool,
 
+    #[command(flatten)]
+    model: ModelArgs,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
 
+/// The model settings, which override the config for every command.
+#[derive(clap::Args, Debug)]
+struct ModelArgs {
+    /// The model to use (e.g., `gpt-4`).
+    #[arg(long, global = true)]
+    model: Option<String>,
+
+    /// The sampling temperature.
+    #[arg(long, global = true)]
+    temperature: Option<f64>,
+
+    /// The nucleus sampling probability mass.
+    #[arg(long, global = true)]
+    top_p: Option<f64>,
+
+    /// The maximum number of tokens to generate.
+    #[arg(long, global = true)]
+    max_tokens: Option<u32>,
+
+    /// The seed for (best-effort) deterministic sampling.
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// A sequence at which the model stops generating (may be repeated).
+    #[arg(long, global = true)]
+    stop: Vec<String>,
+
+    /// How long to wait for a response from the model.
+    #[arg(long, global = true)]
+    timeout_secs: Option<u64>,
+}
+
+impl From<ModelArgs> for ModelSettings {
+    fn from(args: ModelArgs) -> Self {
+        Self {
+            model: args.model,
+            temperature: args.temperature,
+            top_p: args.top_p,
+            max_tokens: args.max_tokens,
+            seed: args.seed,
+            stop: if args.stop.is_empty() { None } else { Some(args.stop) },
+            timeout_secs: args.timeout_secs,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
-    /// Performs a code review of the current `git diff HEAD^`.
-    Review,
+    /// Performs a code review of the current `git diff` (or of the specified commit, range, or merge-base).
+    Review(ReviewArgs),
+
+    /// Proposes a commit message for the staged changes, and commits them with it once accepted.
+    CommitMsg(CommitMsgArgs),
+
+    /// Writes a pull request description for the commits on `HEAD` since its merge-base with the specified ref.
+    PrDescription(PrDescriptionArgs),
+
+    /// Writes the changelog (and suggests the semver bump) for the commits in the specified range (e.g.,
+    /// `v1.2.0..v1.3.0`).
+    Changelog(ChangelogArgs),
 
     /// Gives a response to the specified prompt.
     Ask {
@@ -54,29 +113,233 @@ enum Command {
         prompt: String,
     },
 
+    /// Lists the models that the configured provider serves.
+    Models,
+
+    /// Shows the prompts that are sent to the model (which can be overridden in `<data path>/prompts/<name>.md`).
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommand,
+    },
+
+    /// Installs, updates, or uninstalls the git hooks that review each commit (`pre-commit`) or push (`pre-push`).
+    Hook {
+        #[command(subcommand)]
+        command: HookCommand,
+    },
+
     /// Stop all of the background services.
     Stop,
 }
 
+#[derive(Subcommand, Debug)]
+enum HookCommand {
+    /// Installs the hooks (moving any existing hooks aside, and running them first).
+    Install {
+        /// The hooks to install (`pre-commit`, `pre-push`, or `prepare-commit-msg`; the first two by default).
+        #[arg(value_delimiter = ',')]
+        hooks: Vec<HookKind>,
+    },
+
+    /// Rewrites the installed hooks with the current options (e.g., `--mode` or `--data-path`).
+    Update,
+
+    /// Uninstalls the hooks (restoring any hooks that they replaced).
+    Uninstall {
+        /// The hooks to uninstall (all of them by default).
+        #[arg(value_delimiter = ',')]
+        hooks: Vec<HookKind>,
+    },
+
+    /// Runs the review for a hook, and fails if it finds issues at or above `hook_fail_on` (called by the installed
+    /// hooks).
+    #[command(hide = true)]
+    Run {
+        /// The hook that is running.
+        hook: HookKind,
+
+        /// The hook arguments from git (e.g., the remote name and url for `pre-push`).
+        args: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PromptCommand {
+    /// Prints the rendered prompt (without sending it).
+    Show(PromptShowArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct PromptShowArgs {
+    /// The prompt to show (`review`, `guidelines`, `commit-msg`, `pr-description`, `changelog`, or `ask`).
+    name: String,
+
+    /// What to render the prompt for: the commit or range to review, the base of the pull request, the changelog range,
+    /// or the question to ask.
+    target: Option<String>,
+
+    /// Whether to render the `review` prompt for the staged changes instead of the working tree.
+    #[arg(long, conflicts_with = "target")]
+    staged: bool,
+
+    /// The focused review pass to render the `review` prompt for (e.g., `security`).
+    #[arg(long)]
+    focus: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReviewArgs {
+    /// The commit (e.g., `abc123`) or range (e.g., `main..HEAD`) to review (defaults to the working tree), or `-` to
+    /// read a patch from stdin.
+    target: Option<String>,
+
+    /// Review each commit of the target range (or `--base`) separately, with its commit message as context.
+    #[arg(long, conflicts_with_all = ["staged", "include_untracked"])]
+    each_commit: bool,
+
+    /// Review the specified patch file (or mbox series, with one review per patch) instead of the repository.
+    #[arg(long, conflicts_with_all = ["target", "staged", "base", "include_untracked"])]
+    patch: Option<String>,
+
+    /// Whether to review the staged changes instead of the working tree.
+    #[arg(long, conflicts_with_all = ["target", "base"])]
+    staged: bool,
+
+    /// Review the changes on `HEAD` since its merge-base with the specified ref (e.g., `origin/main`).
+    #[arg(long, conflicts_with = "target")]
+    base: Option<String>,
+
+    /// Only review the files matching these globs (e.g., `src/**/*.rs`).
+    #[arg(long, value_delimiter = ',')]
+    include: Vec<String>,
+
+    /// Skip the files matching these globs (e.g., `*.lock`), in addition to those in `.augreignore`.
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+
+    /// Whether to include untracked (and not ignored) files when reviewing the working tree.
+    #[arg(long)]
+    include_untracked: bool,
+
+    /// How much surrounding code to send along with each changed file (`none`, `auto`, `file`, or `item`).
+    #[arg(long)]
+    context: Option<ContextMode>,
+
+    /// The format of the review (`markdown`, `json`, `sarif`, or `junit`).
+    #[arg(long, default_value = "markdown")]
+    format: ReportFormat,
+
+    /// Write the review (in `--format`) to the specified file.
+    #[arg(long)]
+    output: Option<String>,
+
+    /// Exit with a failure (code 1) when any finding is at or above this severity (`runtime`, `logic`, or `style`).
+    #[arg(long)]
+    fail_on: Option<Severity>,
+
+    /// Run a focused review pass for each of these (`security`, `performance`, `tests`, `api`, or a custom pass),
+    /// instead of a single generic one.
+    #[arg(long, value_delimiter = ',')]
+    focus: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CommitMsgArgs {
+    /// Whether the subject line should follow Conventional Commits (e.g., `fix(parser): handle empty input`).
+    #[arg(long)]
+    conventional: bool,
+
+    /// The maximum number of characters in the subject line (defaults to `commit_max_subject_length`).
+    #[arg(long)]
+    max_subject_length: Option<usize>,
+
+    /// Only print the proposed message, instead of committing with it.
+    #[arg(long)]
+    print: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct PrDescriptionArgs {
+    /// The ref that the pull request merges into (e.g., `main` or `origin/main`).
+    #[arg(long, default_value = "main")]
+    base: String,
+
+    /// The pull request template to fill in (defaults to the repository's `.github/pull_request_template.md`, if any).
+    #[arg(long)]
+    template: Option<String>,
+
+    /// Also write the description (as Markdown) to the specified file (e.g., for `gh pr create --body-file`).
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ChangelogArgs {
+    /// The range of commits to describe (e.g., `v1.2.0..v1.3.0`, or `v1.2.0` for the commits since that tag).
+    range: String,
+
+    /// The version of the release (defaults to the tag at the end of the range, or to the suggested bump of the tag at
+    /// the start).
+    #[arg(long = "release-version")]
+    version: Option<String>,
+
+    /// Prepend the release to the specified changelog (`CHANGELOG.md` by default), which is created if it does not
+    /// exist.
+    #[arg(long, num_args = 0..=1, default_missing_value = "CHANGELOG.md")]
+    prepend: Option<String>,
+}
+
 // Entrypoint.
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    base::interrupt::install();
+
     if let Err(err) = start(args).await {
-        eprintln!("{}: {}", Paint::red("ERROR"), err);
-        std::process::exit(1);
+        let code = exit_code(&err);
+
+        let label = if code == EXIT_FINDINGS {
+            Paint::yellow("FAILED")
+        } else if code == EXIT_INTERRUPTED {
+            Paint::yellow("STOPPED")
+        } else {
+            Paint::red("ERROR")
+        };
+
+        eprintln!("{}: {}", label, err);
+        std::process::exit(code);
     }
 }
 
+/// The exit code of a failed command: [`FoundProblems`] and [`Interrupted`] have their own, so that scripts (e.g., CI)
+/// can tell them apart from errors.
+fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.is::<FoundProblems>() {
+        return EXIT_FINDINGS;
+    }
+
+    if err.is::<Interrupted>() {
+        return EXIT_INTERRUPTED;
+    }
+
+    EXIT_ERROR
+}
+
 async fn start(args: Args) -> Void {
-    let config = base::config::Config::new(&args.data_path, args.mode)?;
+    let config = base::config::Config::new(&args.data_path, args.mode)?.with_overrides(&args.model.into());
     let confirm = !args.skip_confirm;
 
     match args.command {
-        Some(Command::Review) => review(&config, confirm).await?,
+        Some(Command::Review(args)) => review(&config, confirm, args).await?,
+        Some(Command::CommitMsg(args)) => commit_msg(&config, confirm, args).await?,
+        Some(Command::PrDescription(args)) => pr_description(&config, confirm, args).await?,
+        Some(Command::Changelog(args)) => changelog(&config, confirm, args).await?,
         Some(Command::Ask { prompt }) => ask(&config, confirm, &prompt).await?,
+        Some(Command::Models) => models(&config, confirm).await?,
+        Some(Command::Prompt { command }) => prompt(&config, confirm, command).await?,
+        Some(Command::Hook { command }) => hook(&config, &args.data_path, args.mode, confirm, command).await?,
         Some(Command::Stop) => stop(&config, confirm).await?,
         None => return Err(anyhow::anyhow!("No command specified.")),
     }
@@ -84,53 +347,713 @@ async fn start(args: Args) -> Void {
     Ok(())
 }
 
-async fn review(config: &Config, confirm: bool) -> Void {
-    println!();
+async fn review(config: &Config, confirm: bool, args: ReviewArgs) -> Void {
+    eprintln!();
+
+    // The focus passes from the command line replace those of the config.
+    let mut config = config.clone();
+
+    if !args.focus.is_empty() {
+        config.focus = args.focus.clone();
+    }
+
+    let config = &config;
 
     maybe_prepare_local(config, confirm).await?;
 
-    let git = Git::default();
-    let gpt = Gpt::new(&config.openai_endpoint, &config.openai_key, config.mode);
+    let vcs = AnyVcs::new(config.vcs_backend);
+    let filter = path_filter(config, &vcs, &args.include, &args.exclude).await?;
+    let options = DiffOptions {
+        skip_generated: config.skip_generated,
+        include_untracked: args.include_untracked || config.include_untracked,
+        max_untracked_bytes: config.max_untracked_bytes,
+    };
+
+    let mut writer = ReportWriter::new(args.format, args.output.clone());
+    let fail_on = args.fail_on.or(config.fail_on);
+    let patch = args.patch.clone().or_else(|| args.target.clone().filter(|t| t == "-"));
+
+    if let Some(patch) = patch {
+        review_patches(config, confirm, &vcs, &patch, &filter, &options, &mut writer).await?;
+        return finish_report(&writer, fail_on);
+    }
 
-    git.ensure(confirm).await?;
+    let gpt = new_gpt(config, "review");
+
+    vcs.ensure(confirm).await?;
     gpt.ensure(confirm).await?;
 
-    println!();
+    eprintln!();
 
-    print!("Getting diff ...");
-    let diff = Git::diff().await?;
-    println!(" {}", Paint::green("✔️"));
+    let target = DiffTarget::from_args(args.target, args.staged, args.base);
+    let context_mode = args.context.unwrap_or(config.context);
 
-    println!("Getting review ...");
-    let response = gpt.review(&diff).await?.trim().to_string();
-    println!("{}", Paint::green("✔️"));
+    if args.each_commit {
+        review_commits(config, &vcs, &gpt, &target, &filter, &options, context_mode, &mut writer).await?;
+        return finish_report(&writer, fail_on);
+    }
 
-    println!();
+    // Nothing to review is not a failure (e.g., for a hook, or CI on an empty diff).
+    let Some(review) = review_target(config, &vcs, &gpt, &target, &filter, &options, context_mode).await? else {
+        eprintln!("{} There are no changes to review.", Paint::green("✅"));
+        return finish_report(&writer, fail_on);
+    };
 
-    let skin = MadSkin::default();
-    skin.print_text(&response);
+    if let Some(markdown) = writer.add(None, review) {
+        print_markdown(&markdown);
+    }
+
+    finish_report(&writer, fail_on)
+}
+
+/// Writes out the report, and fails if any finding is at or above the threshold (if any).
+fn finish_report(writer: &ReportWriter, fail_on: Option<Severity>) -> Void {
+    match writer.finish()? {
+        report::Output::None => {},
+        report::Output::File(path) => {
+            eprintln!();
+            eprintln!("{} Wrote the review to `{}`.", Paint::green("✅"), Paint::blue(path));
+        },
+        report::Output::Stdout(text) => {
+            println!("{}", text);
+        },
+    }
+
+    let Some(fail_on) = fail_on else {
+        return Ok(());
+    };
+
+    let failing = writer.report().findings().filter(|f| f.severity >= fail_on).count();
+
+    if failing > 0 {
+        return Err(FoundProblems(format!("The review found {} issues at or above `{}` severity.", failing, fail_on)).into());
+    }
+
+    Ok(())
+}
+
+/// Reviews the diff of the target (or returns `None` if there are no changes to review).
+async fn review_target(config: &Config, vcs: &AnyVcs, gpt: &Gpt, target: &DiffTarget, filter: &PathFilter, options: &DiffOptions, context_mode: ContextMode) -> Res<Option<Review>> {
+    eprint!("Getting diff ({}) ...", Paint::blue(target));
+    let (diff, skipped) = vcs.review_diff(target, filter, options).await?;
+    eprintln!(" {} ({} files)", Paint::green("✔️"), diff.files.len());
+
+    print_skipped(&skipped);
+
+    if diff.is_empty() {
+        return Ok(None);
+    }
+
+    let context = collect_context(config, vcs, target, &diff, context_mode).await?;
+    let guidelines = load_guidelines(config, vcs).await?;
+
+    let review = get_review(config, gpt, ReviewInput { diff: &diff, context: &context, message: None, guidelines: Some(&guidelines), focus: None }).await?;
+
+    Ok(Some(review))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn review_commits(config: &Config, vcs: &AnyVcs, gpt: &Gpt, target: &DiffTarget, filter: &PathFilter, options: &DiffOptions, context_mode: ContextMode, writer: &mut ReportWriter) -> Void {
+    let range = target.commit_range()?;
+
+    eprint!("Getting commits ({}) ...", Paint::blue(&range));
+    let commits = vcs.commits(&range).await?;
+    eprintln!(" {} ({} commits)", Paint::green("✔️"), commits.len());
+
+    if commits.is_empty() {
+        eprintln!("{} There are no commits to review.", Paint::green("✅"));
+        return Ok(());
+    }
+
+    let guidelines = load_guidelines(config, vcs).await?;
+
+    for (k, commit) in commits.iter().enumerate() {
+        eprintln!();
+        eprintln!("Commit {}/{}: {} {}", k + 1, commits.len(), Paint::yellow(&commit.short_sha), Paint::blue(&commit.subject));
+
+        let target = DiffTarget::Commit(commit.sha.clone());
+        let (diff, skipped) = vcs.review_diff(&target, filter, options).await?;
+
+        print_skipped(&skipped);
+
+        if diff.is_empty() {
+            eprintln!("{}There are no changes to review.", TAB);
+            continue;
+        }
+
+        let context = collect_context(config, vcs, &target, &diff, context_mode).await?;
+
+        let review = get_review(config, gpt, ReviewInput { diff: &diff, context: &context, message: Some(&commit.message), guidelines: Some(&guidelines), focus: None }).await?;
+        if let Some(markdown) = writer.add(Some(format!("`{}` {}", commit.short_sha, commit.subject)), review) {
+            print_markdown(&markdown);
+        }
+    }
+
+    Ok(())
+}
+
+async fn review_patches(config: &Config, confirm: bool, vcs: &AnyVcs, path: &str, filter: &PathFilter, options: &DiffOptions, writer: &mut ReportWriter) -> Void {
+    let source = if path == "-" { "stdin" } else { path };
+
+    eprint!("Reading patch ({}) ...", Paint::blue(source));
+    let patches = patch::split(&patch::read(path)?)?;
+    eprintln!(" {} ({} patches)", Paint::green("✔️"), patches.len());
+
+    // Nothing to review is not a failure (e.g., `git diff | augre review -` in CI on a clean tree).
+    if patches.is_empty() {
+        eprintln!("{} There are no changes to review.", Paint::green("✅"));
+        return Ok(());
+    }
+
+    let gpt = new_gpt(config, "review");
+    gpt.ensure(confirm).await?;
+
+    eprintln!();
+
+    let guidelines = load_guidelines(config, vcs).await?;
+    let count = patches.len();
+
+    for (k, patch) in patches.into_iter().enumerate() {
+        let title = patch.subject.as_deref().unwrap_or(source);
+
+        eprintln!();
+        eprintln!("Patch {}/{}: {}", k + 1, count, Paint::blue(title));
+
+        let (diff, skipped) = vcs::prepare(patch.diff, filter, options);
+        print_skipped(&skipped);
+
+        if diff.is_empty() {
+            eprintln!("{}There are no changes to review.", TAB);
+            continue;
+        }
+
+        let message = patch.subject.iter().chain(patch.message.iter()).cloned().collect::<Vec<_>>().join("\n\n");
+        let message = if message.is_empty() { None } else { Some(message.as_str()) };
+
+        let review = get_review(config, &gpt, ReviewInput { diff: &diff, context: &[], message, guidelines: Some(&guidelines), focus: None }).await?;
+
+        let title = if count > 1 { Some(title.to_string()) } else { None };
+
+        if let Some(markdown) = writer.add(title, review) {
+            print_markdown(&markdown);
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_review(config: &Config, gpt: &Gpt, input: ReviewInput<'_>) -> Res<Review> {
+    print_guidelines(input.guidelines, input.diff);
+
+    let passes = focus::resolve(&config.focus, &config.review_passes)?;
+
+    let review = if passes.is_empty() {
+        eprintln!("Getting review ...");
+        let review = gpt.review(input, config.max_chunk_tokens).await?;
+        eprintln!("{}", if review.interrupted { Paint::yellow("✋") } else { Paint::green("✔️") });
+
+        review
+    } else {
+        run_passes(config, gpt, input, &passes).await?
+    };
+
+    let (partial, invalid) = citation::check_review(review.value, input.diff, config.invalid_citations);
+
+    // Show what there is of a review that was stopped early (rather than adding it to the report).
+    if review.interrupted {
+        print_markdown(&partial.to_markdown());
+        return Err(Interrupted.into());
+    }
+
+    let review = partial;
+
+    if invalid > 0 {
+        eprintln!("{}{} citations do not point into the diff ({:?}).", TAB, Paint::yellow(invalid), config.invalid_citations);
+    }
+
+    Ok(review)
+}
+
+/// Runs each focused review pass on the files that it applies to, and merges their findings (tagged by pass); if a
+/// pass is stopped early, the remaining passes are skipped.
+async fn run_passes(config: &Config, gpt: &Gpt, input: ReviewInput<'_>, passes: &[FocusPass]) -> Res<Generated<Review>> {
+    let mut reviews = Vec::new();
+    let mut interrupted = false;
+
+    for pass in passes {
+        let diff = pass.filter(input.diff)?;
+
+        if diff.is_empty() {
+            eprintln!("Skipping the {} pass (no matching files).", Paint::blue(&pass.name));
+            continue;
+        }
+
+        let context = input.context.iter().filter(|c| diff.files.iter().any(|f| f.path() == c.path)).cloned().collect::<Vec<_>>();
+
+        eprintln!("Getting review ({}) ...", Paint::blue(&pass.name));
+        let review = gpt.review(ReviewInput { diff: &diff, context: &context, focus: Some(pass), ..input }, config.max_chunk_tokens).await?;
+        eprintln!("{}", if review.interrupted { Paint::yellow("✋") } else { Paint::green("✔️") });
+
+        let mut value = review.value;
+        value.tag(&pass.name);
+        reviews.push(value);
+
+        if review.interrupted {
+            interrupted = true;
+            break;
+        }
+    }
+
+    Ok(Generated { value: Review::merge(reviews), interrupted })
+}
+
+/// Reads the project's guidelines (relative to the repository root).
+async fn load_guidelines(config: &Config, vcs: &AnyVcs) -> Res<Guidelines> {
+    Guidelines::load(&repo_root(vcs).await, &config.guidelines, &config.guideline_sets)
+}
+
+/// Prints the guidelines that apply to the diff (if any); each chunk of a large diff only gets those that apply to it.
+fn print_guidelines(guidelines: Option<&Guidelines>, diff: &Diff) {
+    let files = guidelines.map(|g| g.for_files(&diff.files)).unwrap_or_default();
+
+    if !files.is_empty() {
+        eprintln!("{}Using the guidelines {}.", TAB, files.iter().map(|f| format!("`{}`", Paint::blue(&f.path))).collect::<Vec<_>>().join(", "));
+    }
+}
+
+async fn commit_msg(config: &Config, confirm: bool, args: CommitMsgArgs) -> Void {
+    eprintln!();
+
+    maybe_prepare_local(config, confirm).await?;
+
+    let vcs = AnyVcs::new(config.vcs_backend);
+    let gpt = new_gpt(config, "commit-msg");
+
+    vcs.ensure(confirm).await?;
+    gpt.ensure(confirm).await?;
+
+    eprintln!();
+
+    let style = CommitStyle {
+        conventional: args.conventional || config.commit_conventional,
+        max_subject_length: args.max_subject_length.unwrap_or(config.commit_max_subject_length),
+    };
+
+    let Some(diff) = commit_diff(config, &vcs).await? else {
+        return Err(anyhow::anyhow!("There are no changes to describe (stage them first, for git)."));
+    };
+
+    let mut message = propose_commit_message(config, &gpt, &diff, style).await?;
+
+    if args.print {
+        eprintln!();
+        println!("{}", message);
+        return Ok(());
+    }
+
+    loop {
+        eprintln!();
+        eprintln!("{}", Paint::blue(&message));
+        eprintln!();
+
+        for problem in commit::problems(&message, style) {
+            eprintln!("{}{} {}", TAB, Paint::yellow("⚠️"), problem);
+        }
+
+        if !confirm {
+            break;
+        }
+
+        let choice = Select::new().with_prompt("Commit with this message?").items(&["Accept", "Edit", "Regenerate", "Cancel"]).default(0).interact()?;
+
+        match choice {
+            0 => break,
+            1 => {
+                if let Some(edited) = Editor::new().edit(&message)? {
+                    message = edited.trim().to_string();
+                }
+            },
+            2 => message = propose_commit_message(config, &gpt, &diff, style).await?,
+            _ => return Err(anyhow::anyhow!("User cancelled the commit.")),
+        }
+    }
+
+    if message.is_empty() {
+        return Err(anyhow::anyhow!("The commit message is empty."));
+    }
+
+    vcs.commit(&message).await
+}
+
+/// Writes a proposed commit message into the message file that git passes to the `prepare-commit-msg` hook
+/// (`<file> [<source> [<sha>]]`), unless the message came from elsewhere (e.g., `-m`, a merge, or `--amend`).
+async fn prepare_commit_msg(config: &Config, confirm: bool, args: &[String]) -> Void {
+    let Some(path) = args.first() else {
+        return Err(anyhow::anyhow!("The `prepare-commit-msg` hook requires the path of the commit message file."));
+    };
+
+    if args.get(1).is_some_and(|source| source != "template") {
+        return Ok(());
+    }
+
+    let existing = std::fs::read_to_string(path)?;
+
+    // A failing `prepare-commit-msg` hook aborts the commit, so problems only produce a warning.
+    let proposed = async {
+        maybe_prepare_local(config, confirm).await?;
+
+        let vcs = AnyVcs::new(config.vcs_backend);
+        let gpt = new_gpt(config, "commit-msg");
+
+        vcs.ensure(confirm).await?;
+        gpt.ensure(confirm).await?;
+
+        let style = CommitStyle {
+            conventional: config.commit_conventional,
+            max_subject_length: config.commit_max_subject_length,
+        };
+
+        match commit_diff(config, &vcs).await? {
+            Some(diff) => propose_commit_message(config, &gpt, &diff, style).await.map(Some),
+            None => Ok(None),
+        }
+    };
+
+    match proposed.await {
+        Ok(Some(message)) => std::fs::write(path, format!("{}\n{}", message, existing))?,
+        Ok(None) => {},
+        Err(err) => eprintln!("{}: Unable to propose a commit message: {}", Paint::yellow("WARNING"), err),
+    }
+
+    Ok(())
+}
+
+/// Gets the diff that a commit would record: the staged changes (or the working copy, for a VCS without a staging
+/// area).
+async fn commit_diff(config: &Config, vcs: &AnyVcs) -> Res<Option<Diff>> {
+    let filter = path_filter(config, vcs, &[], &[]).await?;
+    let options = DiffOptions {
+        skip_generated: config.skip_generated,
+        include_untracked: false,
+        max_untracked_bytes: config.max_untracked_bytes,
+    };
+
+    let target = vcs.commit_target();
+
+    eprint!("Getting diff ({}) ...", Paint::blue(&target));
+    let (diff, skipped) = vcs.review_diff(&target, &filter, &options).await?;
+    eprintln!(" {} ({} files)", Paint::green("✔️"), diff.files.len());
+
+    print_skipped(&skipped);
+
+    Ok(if diff.is_empty() { None } else { Some(diff) })
+}
+
+/// Gets a commit message proposal, and retries once (with the problems as feedback) if it does not follow the style.
+async fn propose_commit_message(config: &Config, gpt: &Gpt, diff: &Diff, style: CommitStyle) -> Res<String> {
+    eprintln!("Getting commit message ...");
+    let mut message = gpt.commit_message(diff, style, config.max_chunk_tokens, None).await?;
+
+    let problems = commit::problems(&message, style);
+
+    if !problems.is_empty() {
+        eprintln!("{}Retrying ({}) ...", TAB, problems.join(" "));
+        message = gpt.commit_message(diff, style, config.max_chunk_tokens, Some(&problems.join("\n"))).await?;
+    }
+
+    eprintln!("{}", Paint::green("✔️"));
+
+    Ok(message)
+}
+
+async fn pr_description(config: &Config, confirm: bool, args: PrDescriptionArgs) -> Void {
+    eprintln!();
+
+    maybe_prepare_local(config, confirm).await?;
+
+    let vcs = AnyVcs::new(config.vcs_backend);
+    let gpt = new_gpt(config, "pr-description");
+
+    vcs.ensure(confirm).await?;
+    gpt.ensure(confirm).await?;
+
+    eprintln!();
+
+    let target = DiffTarget::MergeBase(args.base);
+    let range = target.commit_range()?;
+
+    eprint!("Getting commits ({}) ...", Paint::blue(&range));
+    let commits = vcs.commits(&range).await?;
+    eprintln!(" {} ({} commits)", Paint::green("✔️"), commits.len());
+
+    if commits.is_empty() {
+        return Err(anyhow::anyhow!("There are no commits to describe."));
+    }
+
+    let filter = path_filter(config, &vcs, &[], &[]).await?;
+    let options = DiffOptions {
+        skip_generated: config.skip_generated,
+        include_untracked: false,
+        max_untracked_bytes: config.max_untracked_bytes,
+    };
+
+    eprint!("Getting diff ({}) ...", Paint::blue(&target));
+    let (diff, skipped) = vcs.review_diff(&target, &filter, &options).await?;
+    eprintln!(" {} ({} files)", Paint::green("✔️"), diff.files.len());
+
+    print_skipped(&skipped);
+
+    let template = pr::read_template(args.template.as_deref(), &vcs.root().await?)?;
+
+    if let Some((path, _)) = &template {
+        eprintln!("{}Using the template `{}`.", TAB, Paint::blue(path.display()));
+    }
+
+    eprintln!("Getting description ...");
+    let description = gpt.pr_description(&commits, &diff, template.as_ref().map(|(_, text)| text.as_str()), config.max_chunk_tokens).await?;
+    eprintln!("{}", if description.interrupted { Paint::yellow("✋") } else { Paint::green("✔️") });
+
+    if description.interrupted {
+        print_markdown(description.value.trim());
+        return Err(Interrupted.into());
+    }
+
+    let description = description.value.trim().to_string();
+
+    if let Some(output) = &args.output {
+        std::fs::write(output, format!("{}\n", description))?;
+        eprintln!("{}Wrote the description to `{}`.", TAB, Paint::blue(output));
+    }
+
+    print_markdown(&description);
+
+    Ok(())
+}
+
+async fn changelog(config: &Config, confirm: bool, args: ChangelogArgs) -> Void {
+    eprintln!();
+
+    let vcs = AnyVcs::new(config.vcs_backend);
+    vcs.ensure(confirm).await?;
+
+    eprintln!();
+
+    let range = if args.range.contains("..") { args.range } else { format!("{}..HEAD", args.range) };
+    let (from, to) = range.split_once("..").map(|(from, to)| (from, to.trim_start_matches('.'))).unwrap_or_default();
+
+    eprint!("Getting commits ({}) ...", Paint::blue(&range));
+    let commits = vcs.commits(&range).await?;
+    eprintln!(" {} ({} commits)", Paint::green("✔️"), commits.len());
+
+    let Some(last) = commits.last() else {
+        return Err(anyhow::anyhow!("There are no commits to describe."));
+    };
+
+    let mut changes = commits.iter().filter_map(changelog::classify).collect::<Vec<_>>();
+    let unclassified = commits.iter().filter(|c| changelog::classify(c).is_none()).cloned().collect::<Vec<_>>();
+
+    eprintln!("{}{} commits follow Conventional Commits.", TAB, changes.len());
+
+    // Only the commits without a Conventional Commits prefix need the model.
+    if !unclassified.is_empty() {
+        maybe_prepare_local(config, confirm).await?;
+
+        let gpt = new_gpt(config, "changelog");
+        gpt.ensure(confirm).await?;
+
+        eprintln!("Classifying {} commits ...", unclassified.len());
+        let kinds = gpt.classify_commits(&unclassified).await?;
+        eprintln!("{}", Paint::green("✔️"));
+
+        changes.extend(unclassified.iter().map(|c| changelog::change(c, kinds.get(&c.short_sha).copied().unwrap_or(ChangeKind::Changed))));
+    }
+
+    // Keep the changes in commit order (oldest first).
+    changes.sort_by_key(|c| commits.iter().position(|commit| commit.short_sha == c.short_sha));
+
+    let bump = changelog::suggest_bump(&changes);
+    let next = Version::from_tag(from).map(|v| v.bump(bump));
+
+    match next {
+        Some(next) => eprintln!("{}Suggested bump: {} ({} → {}).", TAB, Paint::yellow(bump), from, Paint::blue(next)),
+        None => eprintln!("{}Suggested bump: {}.", TAB, Paint::yellow(bump)),
+    }
+
+    let version = args.version.or_else(|| Version::from_tag(to).map(|v| v.to_string())).or_else(|| next.map(|v| v.to_string()));
+    let section = changelog::render(version.as_deref(), &last.date, &changes);
+
+    if let Some(path) = &args.prepend {
+        // Only a missing changelog starts a new one: any other error must not overwrite the existing one.
+        let existing = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(anyhow::Error::msg(format!("Unable to read the changelog `{}`: {}", path, err))),
+        };
+
+        std::fs::write(path, changelog::prepend(&existing, &section))?;
+
+        eprintln!("{}Prepended the release to `{}`.", TAB, Paint::blue(path));
+    }
+
+    print_markdown(&section);
 
     Ok(())
 }
 
 async fn ask(config: &Config, confirm: bool, prompt: &str) -> Void {
-    println!();
+    eprintln!();
 
     maybe_prepare_local(config, confirm).await?;
 
-    let gpt = Gpt::new(&config.openai_endpoint, &config.openai_key, config.mode);
+    let gpt = new_gpt(config, "ask");
     gpt.ensure(confirm).await?;
 
-    println!();
+    eprintln!();
 
-    println!("Getting response ...");
-    let response = gpt.ask(prompt).await?.trim().to_string();
-    println!("{}", Paint::green("✔️"));
+    eprintln!("Getting response ...");
+    let response = gpt.ask(prompt).await?;
+    eprintln!("{}", if response.interrupted { Paint::yellow("✋") } else { Paint::green("✔️") });
 
-    println!();
+    print_markdown(response.value.trim());
 
-    let skin = MadSkin::default();
-    skin.print_text(&response);
+    if response.interrupted {
+        return Err(Interrupted.into());
+    }
+
+    Ok(())
+}
+
+async fn models(config: &Config, confirm: bool) -> Void {
+    eprintln!();
+
+    maybe_prepare_local(config, confirm).await?;
+
+    let gpt = new_gpt(config, "models");
+    gpt.ensure(confirm).await?;
+
+    eprintln!();
+
+    eprint!("Getting models ({}) ...", Paint::blue(&config.provider_url));
+    let mut models = gpt.models().await?;
+    eprintln!(" {}", Paint::green("✔️"));
+
+    models.sort();
+
+    let configured = config.settings("models").model;
+
+    eprintln!();
+
+    for model in models {
+        if configured.as_ref() == Some(&model) {
+            println!("{}{} {}", TAB, model, Paint::green("(configured)"));
+        } else {
+            println!("{}{}", TAB, model);
+        }
+    }
+
+    Ok(())
+}
+
+async fn hook(config: &Config, data_path: &str, mode: Mode, confirm: bool, command: HookCommand) -> Void {
+    if let HookCommand::Run { hook, args } = command {
+        return run_hook(config, confirm, hook, &args).await;
+    }
+
+    eprintln!();
+
+    let hooks_path = hook::hooks_path().await?;
+
+    match command {
+        HookCommand::Install { hooks } => {
+            let hooks = if hooks.is_empty() { HookKind::defaults().to_vec() } else { hooks };
+
+            for kind in hooks {
+                Hook::new(kind, &hooks_path, data_path, mode).ensure(confirm).await?;
+            }
+        },
+        HookCommand::Update => {
+            let installed = HookKind::all().into_iter().map(|kind| Hook::new(kind, &hooks_path, data_path, mode)).filter(Hook::is_installed).collect::<Vec<_>>();
+
+            if installed.is_empty() {
+                eprintln!("{}There are no hooks to update: please run `{}` first.", TAB, Paint::blue("augre hook install"));
+            }
+
+            for hook in installed {
+                hook.ensure(false).await?;
+            }
+        },
+        HookCommand::Uninstall { hooks } => {
+            let hooks = if hooks.is_empty() { HookKind::all().to_vec() } else { hooks };
+
+            for kind in hooks {
+                let hook = Hook::new(kind, &hooks_path, data_path, mode);
+
+                if !hook.is_installed() {
+                    eprintln!("{}The `{}` hook is not installed.", TAB, Paint::blue(kind));
+                    continue;
+                }
+
+                eprintln!("Removing the `{}` hook ...", Paint::blue(kind));
+                hook.make_not_present().await?;
+                eprintln!("{}", Paint::green("✔️"));
+            }
+        },
+        HookCommand::Run { .. } => unreachable!(),
+    }
+
+    Ok(())
+}
+
+async fn run_hook(config: &Config, confirm: bool, hook: HookKind, args: &[String]) -> Void {
+    if hook == HookKind::PrepareCommitMsg {
+        return prepare_commit_msg(config, confirm, args).await;
+    }
+
+    eprintln!();
+
+    maybe_prepare_local(config, confirm).await?;
+
+    let vcs = AnyVcs::new(config.vcs_backend);
+    let gpt = new_gpt(config, "review");
+
+    vcs.ensure(confirm).await?;
+    gpt.ensure(confirm).await?;
+
+    eprintln!();
+
+    let targets = match hook {
+        HookKind::PreCommit => vec![DiffTarget::Staged],
+        HookKind::PrepareCommitMsg => unreachable!(),
+        HookKind::PrePush => {
+            let remote = args.first().map(String::as_str).unwrap_or("origin");
+            hook::push_targets(remote, &std::io::read_to_string(std::io::stdin())?).await?
+        },
+    };
+
+    let filter = path_filter(config, &vcs, &[], &[]).await?;
+    let options = DiffOptions {
+        skip_generated: config.skip_generated,
+        include_untracked: false,
+        max_untracked_bytes: config.max_untracked_bytes,
+    };
+
+    let mut blocking = 0;
+
+    for target in targets {
+        let Some(review) = review_target(config, &vcs, &gpt, &target, &filter, &options, config.context).await? else {
+            continue;
+        };
+
+        print_markdown(&review.to_markdown());
+        blocking += review.count_at_least(config.hook_fail_on);
+    }
+
+    if blocking > 0 {
+        return Err(FoundProblems(format!("The review found {} issues at or above `{}` severity: please address them (or skip the `{}` hook with `--no-verify`).", blocking, config.hook_fail_on, hook)).into());
+    }
+
+    eprintln!();
+    eprintln!("{} The review found no issues at or above `{}` severity.", Paint::green("✅"), config.hook_fail_on);
 
     Ok(())
 }
@@ -143,6 +1066,227 @@ async fn stop(config: &Config, confirm: bool) -> Void {
     Ok(())
 }
 
+async fn collect_context(config: &Config, vcs: &AnyVcs, target: &DiffTarget, diff: &Diff, mode: ContextMode) -> Res<Vec<FileContext>> {
+    if mode == ContextMode::None {
+        return Ok(Vec::new());
+    }
+
+    eprint!("Getting context ...");
+
+    let mut context = Vec::new();
+    let mut tokens = 0;
+
+    for file in &diff.files {
+        let Some(contents) = vcs.file_contents(target, file.path()).await? else {
+            continue;
+        };
+
+        let Some(file_context) = context::expand(file, &contents, mode, config.max_context_file_tokens) else {
+            continue;
+        };
+
+        if tokens + file_context.tokens() > config.max_context_tokens {
+            continue;
+        }
+
+        tokens += file_context.tokens();
+        context.push(file_context);
+    }
+
+    eprintln!(" {} ({} files, ~{} tokens)", Paint::green("✔️"), context.len(), tokens);
+
+    Ok(context)
+}
+
+fn print_markdown(text: &str) {
+    println!();
+
+    let skin = MadSkin::default();
+    skin.print_text(text);
+}
+
+/// Builds the path filter, reading `.augreignore` from the repository root.
+async fn path_filter(config: &Config, vcs: &AnyVcs, include: &[String], exclude: &[String]) -> Res<PathFilter> {
+    PathFilter::new(include, exclude, &repo_root(vcs).await, &config.data_path)
+}
+
+/// Gets the repository root (or the current directory outside a repository, e.g., when reviewing a patch file).
+async fn repo_root(vcs: &AnyVcs) -> PathBuf {
+    vcs.root().await.unwrap_or_else(|_| PathBuf::from("."))
+}
+
+fn print_skipped(skipped: &[SkippedFile]) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    eprintln!("{}Skipped {} files:", TAB, skipped.len());
+
+    for file in skipped {
+        eprintln!("{}{}- `{}` ({})", TAB, TAB, Paint::blue(&file.path), file.reason);
+    }
+}
+
+/// Creates the client of the configured provider, with the model settings of the command (e.g., `review`).
+fn new_gpt(config: &Config, command: &str) -> Gpt {
+    let key = match config.provider {
+        Provider::Anthropic => &config.anthropic_key,
+        Provider::OpenAi | Provider::Ollama => &config.openai_key,
+    };
+
+    let globals = PromptVars {
+        language: config.language.clone(),
+        ..Default::default()
+    };
+
+    let prompts = Prompts::new(&config.data_path, globals);
+
+    Gpt::new(AnyProvider::new(config.provider, &config.provider_url, key), config.settings(command), config.stream, prompts, AnyVcs::new(config.vcs_backend))
+}
+
+async fn prompt(config: &Config, confirm: bool, command: PromptCommand) -> Void {
+    match command {
+        PromptCommand::Show(args) => prompt_show(config, confirm, args).await,
+    }
+}
+
+/// Renders a prompt from the repository (as the corresponding command would), without sending it.
+async fn prompt_show(config: &Config, confirm: bool, args: PromptShowArgs) -> Void {
+    eprintln!();
+
+    let (_, path) = Prompts::new(&config.data_path, PromptVars::default()).template(&args.name)?;
+
+    let vcs = AnyVcs::new(config.vcs_backend);
+    // The prompt is rendered as its command would render it (with the command's settings).
+    let gpt = new_gpt(config, if args.name == "guidelines" { "review" } else { &args.name });
+
+    vcs.ensure(confirm).await?;
+
+    eprintln!();
+
+    let user = |prompt: String| vec![ChatMessage::user(prompt)];
+
+    let messages = match args.name.as_str() {
+        "review" | "guidelines" => {
+            let target = DiffTarget::from_args(args.target, args.staged, None);
+            let filter = path_filter(config, &vcs, &[], &[]).await?;
+            let options = DiffOptions {
+                skip_generated: config.skip_generated,
+                include_untracked: config.include_untracked,
+                max_untracked_bytes: config.max_untracked_bytes,
+            };
+
+            eprint!("Getting diff ({}) ...", Paint::blue(&target));
+            let (diff, skipped) = vcs.review_diff(&target, &filter, &options).await?;
+            eprintln!(" {} ({} files)", Paint::green("✔️"), diff.files.len());
+
+            print_skipped(&skipped);
+
+            if diff.is_empty() {
+                return Err(anyhow::anyhow!("There are no changes to review."));
+            }
+
+            let context = collect_context(config, &vcs, &target, &diff, config.context).await?;
+
+            let guidelines = load_guidelines(config, &vcs).await?;
+            print_guidelines(Some(&guidelines), &diff);
+
+            let pass = args.focus.map(|name| focus::resolve(&[name], &config.review_passes)).transpose()?.and_then(|p| p.into_iter().next());
+
+            let diff = match &pass {
+                Some(pass) => pass.filter(&diff)?,
+                None => diff,
+            };
+
+            if diff.is_empty() {
+                return Err(anyhow::anyhow!("There are no changes that the pass applies to."));
+            }
+
+            gpt.review_messages(ReviewInput { diff: &diff, context: &context, message: None, guidelines: Some(&guidelines), focus: pass.as_ref() }).await?
+        },
+        "merge" => return Err(anyhow::anyhow!("The `merge` prompt is rendered from the findings of the reviews of the chunks of a large diff, so it can only be shown by running the review.")),
+        "commit-msg" => {
+            let Some(diff) = commit_diff(config, &vcs).await? else {
+                return Err(anyhow::anyhow!("There are no changes to describe (stage them first, for git)."));
+            };
+
+            let style = CommitStyle {
+                conventional: config.commit_conventional,
+                max_subject_length: config.commit_max_subject_length,
+            };
+
+            user(gpt.commit_prompt(&diff, style, config.max_chunk_tokens, None).await?)
+        },
+        "pr-description" => {
+            let target = DiffTarget::MergeBase(args.target.unwrap_or_else(|| "main".to_string()));
+            let range = target.commit_range()?;
+
+            eprint!("Getting commits ({}) ...", Paint::blue(&range));
+            let commits = vcs.commits(&range).await?;
+            eprintln!(" {} ({} commits)", Paint::green("✔️"), commits.len());
+
+            let filter = path_filter(config, &vcs, &[], &[]).await?;
+            let options = DiffOptions {
+                skip_generated: config.skip_generated,
+                include_untracked: false,
+                max_untracked_bytes: config.max_untracked_bytes,
+            };
+
+            eprint!("Getting diff ({}) ...", Paint::blue(&target));
+            let (diff, _) = vcs.review_diff(&target, &filter, &options).await?;
+            eprintln!(" {} ({} files)", Paint::green("✔️"), diff.files.len());
+
+            let template = pr::read_template(None, &vcs.root().await?)?;
+
+            user(gpt.pr_prompt(&commits, &diff, template.as_ref().map(|(_, text)| text.as_str()), config.max_chunk_tokens).await?)
+        },
+        "changelog" => {
+            let Some(range) = args.target else {
+                return Err(anyhow::anyhow!("The `changelog` prompt requires a range (e.g., `v1.2.0..v1.3.0`)."));
+            };
+
+            let range = if range.contains("..") { range } else { format!("{}..HEAD", range) };
+
+            eprint!("Getting commits ({}) ...", Paint::blue(&range));
+            let commits = vcs.commits(&range).await?;
+            eprintln!(" {} ({} commits)", Paint::green("✔️"), commits.len());
+
+            // Only the commits without a Conventional Commits prefix are sent to the model.
+            let unclassified = commits.into_iter().filter(|c| changelog::classify(c).is_none()).collect::<Vec<_>>();
+
+            user(gpt.classify_prompt(&unclassified).await?)
+        },
+        "ask" => {
+            let Some(question) = args.target else {
+                return Err(anyhow::anyhow!("The `ask` prompt requires a prompt to respond to."));
+            };
+
+            user(gpt.ask_prompt(&question).await?)
+        },
+        name => return Err(anyhow::anyhow!("Invalid prompt specified (expected one of {}): {}.", prompt::NAMES.join(", "), name)),
+    };
+
+    eprintln!();
+
+    match path {
+        Some(path) => eprintln!("{} Using the template `{}`.", Paint::green("✅"), Paint::blue(path.display())),
+        None => eprintln!("{} Using the built-in template.", Paint::green("✅")),
+    }
+
+    // Label the messages when there is more than one (e.g., the guidelines, as a system message, before a review).
+    for message in &messages {
+        println!();
+
+        if messages.len() > 1 {
+            println!("{}", Paint::yellow(format!("[{:?}]", message.role)));
+        }
+
+        println!("{}", message.content);
+    }
+
+    Ok(())
+}
+
 async fn maybe_prepare_local(config: &Config, confirm: bool) -> Void {
     if config.mode == Mode::LocalCpu || config.mode == Mode::LocalGpu {
         let docker = Docker::default();
@@ -155,4 +1299,45 @@ async fn maybe_prepare_local(config: &Config, confirm: bool) -> Void {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_errors_to_exit_codes() {
+        assert_eq!(exit_code(&FoundProblems("The review found 2 issues.".to_string()).into()), EXIT_FINDINGS);
+        assert_eq!(exit_code(&Interrupted.into()), EXIT_INTERRUPTED);
+        assert_eq!(exit_code(&anyhow::anyhow!("Unable to run `git diff`.")), EXIT_ERROR);
+
+        // The typed errors keep their code when context is added.
+        assert_eq!(exit_code(&anyhow::Error::from(Interrupted).context("While reviewing.")), EXIT_INTERRUPTED);
+    }
+
+    #[test]
+    fn fails_only_at_or_above_the_threshold() {
+        let mut writer = ReportWriter::new(ReportFormat::Json, Some(std::env::temp_dir().join("augre-exit-code-test.json").to_string_lossy().into_owned()));
+
+        let review = Review {
+            summary: None,
+            findings: vec![base::finding::Finding {
+                file: None,
+                line_range: None,
+                severity: Severity::Logic,
+                category: "logic".to_string(),
+                message: "Off by one.".to_string(),
+                suggested_fix: None,
+                guideline: None,
+                pass: None,
+            }],
+        };
+        writer.add(None, review);
+
+        assert!(finish_report(&writer, None).is_ok());
+        assert!(finish_report(&writer, Some(Severity::Runtime)).is_ok());
+        assert_eq!(exit_code(&finish_report(&writer, Some(Severity::Logic)).unwrap_err()), EXIT_FINDINGS);
+    }
+}
@@ -0,0 +1,283 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::base::types::{HasName, IsEnsurable, Res, Void};
+
+use super::llm::{self, ChatRequest, LlmProvider, Role};
+
+static NAME: &str = "anthropic_api";
+static API_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires a limit on the number of generated tokens.
+static DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic's Messages API.
+pub struct Anthropic {
+    url: String,
+    key: Option<String>,
+}
+
+impl HasName for Anthropic {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for Anthropic {
+    async fn is_present(&self) -> Res<bool> {
+        let _ = self.resolve_key()?;
+
+        Ok(true)
+    }
+
+    async fn make_present(&self) -> Void {
+        Err(anyhow::Error::msg("Cannot perform `make_present`: this should not happen."))
+    }
+}
+
+impl LlmProvider for Anthropic {
+    async fn chat(&self, request: &ChatRequest) -> Res<String> {
+        let response = self.post("messages", &self.body(request, false), request).await?;
+        let message: Message = response.json().await?;
+
+        Ok(message.content.into_iter().filter_map(|block| block.text).collect())
+    }
+
+    async fn stream(&self, request: &ChatRequest, on_delta: &mut dyn FnMut(&str)) -> Res<String> {
+        let response = self.post("messages", &self.body(request, true), request).await?;
+        let mut text = String::new();
+
+        llm::read_lines(response, |line| {
+            let Some(data) = llm::sse_data(line) else {
+                return Ok(true);
+            };
+
+            let event: Event = serde_json::from_str(data)?;
+
+            match event.kind.as_str() {
+                "content_block_delta" => {
+                    if let Some(delta) = event.delta.and_then(|d| d.text) {
+                        on_delta(&delta);
+                        text.push_str(&delta);
+                    }
+                },
+                "message_stop" => return Ok(false),
+                "error" => return Err(anyhow::Error::msg(format!("The model API returned an error: {}", event.error.unwrap_or_default()))),
+                _ => {},
+            }
+
+            Ok(true)
+        })
+        .await?;
+
+        Ok(text)
+    }
+
+    async fn models(&self) -> Res<Vec<String>> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/v1/models", self.url))
+            .header("x-api-key", self.resolve_key()?)
+            .header("anthropic-version", API_VERSION)
+            .send().await?;
+
+        let models: Models = llm::check(response).await?.json().await?;
+
+        Ok(models.data.into_iter().map(|m| m.id).collect())
+    }
+
+    async fn count_tokens(&self, request: &ChatRequest) -> Res<usize> {
+        let mut body = self.body(request, false);
+
+        // The token counting endpoint does not accept the generation options.
+        if let Some(body) = body.as_object_mut() {
+            for key in ["max_tokens", "stream", "temperature", "top_p", "stop_sequences"] {
+                body.remove(key);
+            }
+        }
+
+        let count: TokenCount = self.post("messages/count_tokens", &body, request).await?.json().await?;
+
+        Ok(count.input_tokens)
+    }
+}
+
+impl Anthropic {
+    pub fn new(url: &str, key: &Option<String>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            key: key.clone(),
+        }
+    }
+
+    fn resolve_key(&self) -> Res<&str> {
+        self.key.as_deref().ok_or_else(|| anyhow::Error::msg("Anthropic key not provided.  Please set the `anthropic_key` config value."))
+    }
+
+    /// Builds the body of a request: the Messages API takes the system prompt separately from the messages.
+    fn body(&self, request: &ChatRequest, stream: bool) -> Value {
+        let system = request.messages.iter().filter(|m| m.role == Role::System).map(|m| m.content.as_str()).collect::<Vec<_>>().join("\n\n");
+        let messages = request.messages.iter().filter(|m| m.role != Role::System).collect::<Vec<_>>();
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "stream": stream,
+        });
+
+        if !system.is_empty() {
+            body["system"] = json!(system);
+        }
+
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+
+        // The Messages API has no seed, so it is ignored.
+        if !request.stop.is_empty() {
+            body["stop_sequences"] = json!(request.stop);
+        }
+
+        body
+    }
+
+    async fn post(&self, endpoint: &str, body: &Value, request: &ChatRequest) -> Res<reqwest::Response> {
+        let response = llm::client(request.timeout)?
+            .post(format!("{}/v1/{}", self.url, endpoint))
+            .header("x-api-key", self.resolve_key()?)
+            .header("anthropic-version", API_VERSION)
+            .json(body)
+            .send().await?;
+
+        llm::check(response).await
+    }
+}
+
+// Types.
+
+#[derive(Deserialize)]
+struct Message {
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<ContentBlock>,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Models {
+    data: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct Model {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TokenCount {
+    input_tokens: usize,
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{*, super::llm::testing::{request, serve}};
+
+    fn anthropic(url: &str) -> Anthropic {
+        Anthropic::new(url, &Some("key".to_string()))
+    }
+
+    #[tokio::test]
+    async fn chats() {
+        let (url, received) = serve(200, &[br#"{"content": [{"type": "text", "text": "Hello"}, {"type": "text", "text": "!"}]}"#]).await;
+
+        assert_eq!(anthropic(&url).chat(&request()).await.unwrap(), "Hello!");
+
+        let received = received.await.unwrap();
+        assert_eq!(received.line, "POST /v1/messages HTTP/1.1");
+        assert_eq!(received.header("x-api-key"), Some("key"));
+        assert_eq!(received.header("anthropic-version"), Some(API_VERSION));
+
+        // The system prompt is sent separately from the messages.
+        assert_eq!(received.body, json!({
+            "model": "model",
+            "system": "Be brief.",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 10,
+            "stream": false,
+            "temperature": 0.5,
+            "stop_sequences": ["END"],
+        }));
+    }
+
+    #[tokio::test]
+    async fn streams_server_sent_events() {
+        // The `é` of the second delta is split across chunks.
+        let (url, _) = serve(200, &[
+            b"event: message_start\ndata: {\"type\": \"message_start\", \"message\": {}}\n\n",
+            b"event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"delta\": {\"type\": \"text_delta\", \"text\": \"Caf\"}}\n\n",
+            b"event: content_block_delta\ndata: {\"type\": \"content_block_delta\", \"delta\": {\"type\": \"text_delta\", \"text\": \"\xc3",
+            b"\xa9!\"}}\n\nevent: ping\ndata: {\"type\": \"ping\"}\n\n",
+            b"event: message_stop\ndata: {\"type\": \"message_stop\"}\n\n",
+        ]).await;
+
+        let mut deltas = Vec::new();
+        let text = anthropic(&url).stream(&request(), &mut |delta| deltas.push(delta.to_string())).await.unwrap();
+
+        assert_eq!(text, "Café!");
+        assert_eq!(deltas, vec!["Caf", "é!"]);
+    }
+
+    #[tokio::test]
+    async fn returns_stream_errors() {
+        let (url, _) = serve(200, &[b"event: error\ndata: {\"type\": \"error\", \"error\": {\"type\": \"overloaded_error\"}}\n\n"]).await;
+        let err = anthropic(&url).stream(&request(), &mut |_| {}).await.unwrap_err();
+
+        assert_eq!(err.to_string(), r#"The model API returned an error: {"type":"overloaded_error"}"#);
+    }
+
+    #[tokio::test]
+    async fn returns_error_bodies() {
+        let (url, _) = serve(400, &[br#"{"type": "error", "error": {"type": "invalid_request_error"}}"#]).await;
+        let err = anthropic(&url).chat(&request()).await.unwrap_err();
+
+        assert_eq!(err.to_string(), r#"The model API returned `400 Bad Request`: {"type": "error", "error": {"type": "invalid_request_error"}}"#);
+    }
+
+    #[tokio::test]
+    async fn lists_models() {
+        let (url, received) = serve(200, &[br#"{"data": [{"id": "claude-a"}, {"id": "claude-b"}], "has_more": false}"#]).await;
+
+        assert_eq!(anthropic(&url).models().await.unwrap(), vec!["claude-a", "claude-b"]);
+        assert_eq!(received.await.unwrap().header("x-api-key"), Some("key"));
+    }
+
+    #[tokio::test]
+    async fn counts_tokens() {
+        let (url, received) = serve(200, &[br#"{"input_tokens": 42}"#]).await;
+
+        assert_eq!(anthropic(&url).count_tokens(&request()).await.unwrap(), 42);
+
+        // The generation options are left out.
+        let received = received.await.unwrap();
+        assert_eq!(received.line, "POST /v1/messages/count_tokens HTTP/1.1");
+        assert_eq!(received.body, json!({"model": "model", "system": "Be brief.", "messages": [{"role": "user", "content": "Hi"}]}));
+    }
+}
@@ -60,7 +60,7 @@ impl IsEnsurable for Cria {
 
         let compose_path = format!("{}/docker-compose.yml", self.data_path);
 
-        println!("{}Creating `{}` using model path `{}` ... ", TAB, compose_path, path);
+        eprintln!("{}Creating `{}` using model path `{}` ... ", TAB, compose_path, path);
 
         std::fs::write(&compose_path, &compose)?;
 
@@ -81,7 +81,7 @@ impl IsEnsurable for Cria {
             }
 
             tokio::time::sleep(Duration::from_secs(10)).await;
-            println!("{}Waiting for Cria to start ... ", TAB)
+            eprintln!("{}Waiting for Cria to start ... ", TAB)
         }
 
         Err(anyhow::Error::msg("Unable to start the Cria server (timed out)."))
@@ -92,7 +92,7 @@ impl IsRemovable for Cria {
     async fn make_not_present(&self) -> Res<()> {
         let compose_path = format!("{}/docker-compose.yml", self.data_path);
 
-        println!("{}Removing `{}` ... ", TAB, compose_path);
+        eprintln!("{}Removing `{}` ... ", TAB, compose_path);
 
         Command::new("docker-compose")
             .arg("-p")
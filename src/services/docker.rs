@@ -22,7 +22,7 @@ impl IsEnsurable for Docker {
 
     async fn make_present(&self) -> Void {
         if cfg!(target_os = "windows") {
-            println!("{}{}: Please install `{}` manually on Windows.", TAB, Paint::red("✘"), Paint::blue(NAME));
+            eprintln!("{}{}: Please install `{}` manually on Windows.", TAB, Paint::red("✘"), Paint::blue(NAME));
             return Err(anyhow::anyhow!("User skipped required operation."));
         }
         
@@ -1,13 +1,29 @@
+use std::path::PathBuf;
+
 use tokio::process::Command;
 use anyhow::Context;
 use yansi::Paint;
 
-use crate::base::types::{HasName, IsEnsurable, is_binary_present, MapStatus, Res, Void, TAB};
+use crate::base::{types::{HasName, IsEnsurable, is_binary_present, run_output, run_with_input, MapStatus, Res, Void, TAB, DiffTarget, CommitInfo}, diff::Diff};
+
+use super::vcs::{self, Vcs};
 
 static NAME: &str = "git";
 
-#[derive(Default)]
-pub struct Git {}
+/// Keeps the user's git config out of the diffs: colors, external diff tools and textconv drivers would break the
+/// patch, and prefix or relative-path settings would change its paths.
+static DIFF_FLAGS: &[&str] = &["--no-color", "--no-ext-diff", "--no-textconv", "--no-relative", "--src-prefix=a/", "--dst-prefix=b/"];
+
+/// A git repository (via the `git` binary).
+pub struct Git {
+    path: String,
+}
+
+impl Default for Git {
+    fn default() -> Self {
+        Self::new(".")
+    }
+}
 
 impl HasName for Git {
     fn name(&self) -> &'static str {
@@ -22,7 +38,7 @@ impl IsEnsurable for Git {
 
     async fn make_present(&self) -> Void {
         if cfg!(target_os = "windows") {
-            println!("{}{}: Please install `{}` manually on Windows.", TAB, Paint::red("✘"), Paint::blue(NAME));
+            eprintln!("{}{}: Please install `{}` manually on Windows.", TAB, Paint::red("✘"), Paint::blue(NAME));
             return Err(anyhow::anyhow!("User skipped required operation."));
         }
 
@@ -50,19 +66,140 @@ impl IsEnsurable for Git {
     }
 }
 
+impl Vcs for Git {
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff> {
+        let (command, args) = match target {
+            DiffTarget::WorkingTree => ("diff", vec![]),
+            DiffTarget::Staged => ("diff", vec!["--staged".to_string()]),
+            DiffTarget::Commit(sha) => ("show", vec!["--format=".to_string(), "--diff-merges=first-parent".to_string(), format!("{}^{{commit}}", sha)]),
+            DiffTarget::Range(range) => ("diff", vec![range.clone()]),
+            DiffTarget::MergeBase(base) => ("diff", vec![format!("{}...HEAD", base)]),
+        };
+
+        let args = std::iter::once(command.to_string()).chain(DIFF_FLAGS.iter().map(|f| f.to_string())).chain(args).collect::<Vec<_>>();
+
+        Diff::parse(&self.run(&args).await?)
+    }
+
+    async fn untracked_files(&self) -> Res<Vec<String>> {
+        // List the whole working tree with root-relative paths (like the diff), even from a subdirectory.
+        let stdout = self.run(&["ls-files", "--others", "--exclude-standard", "--full-name", "-z", ":/"]).await?;
+
+        Ok(stdout.split('\0').filter(|p| !p.is_empty()).map(str::to_string).collect())
+    }
+
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>> {
+        let range = self.commit_range(range).await?;
+        let stdout = self.run(&["log", "--reverse", "--format=%H%x1f%h%x1f%s%x1f%cs%x1f%B%x1e", &range]).await?;
+
+        Ok(vcs::parse_commits(&stdout))
+    }
+
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>> {
+        let Some(revision) = target.new_revision() else {
+            return self.working_file(path).await;
+        };
+
+        vcs::file_at(NAME, &["-C", &self.path, "show", &format!("{}:{}", revision, path)]).await
+    }
+
+    async fn branch(&self) -> Res<Option<String>> {
+        // `symbolic-ref` fails on a detached `HEAD`.
+        Ok(self.run(&["symbolic-ref", "--short", "-q", "HEAD"]).await.ok().map(|b| b.trim().to_string()).filter(|b| !b.is_empty()))
+    }
+
+    async fn root(&self) -> Res<PathBuf> {
+        Ok(PathBuf::from(self.run(&["rev-parse", "--show-toplevel"]).await?.trim_end_matches('\n')))
+    }
+
+    async fn commit(&self, message: &str) -> Void {
+        // Runs the usual hooks (other than `prepare-commit-msg`'s edit).
+        run_with_input(NAME, &["-C", &self.path, "commit", "--file", "-"], message).await.context("Unable to commit the staged changes.")
+    }
+}
+
 impl Git {
-    pub async fn diff() -> Res<String> {
-        let output = Command::new("git")
-            .arg("diff")
-            .output().await
-            .context("Unable to run `git diff`.")?;
-
-        if !output.status.success() {
-            return Err(anyhow::Error::msg("The exit code of the `git diff` operation was not successful."));
+    /// Creates a [`Git`] for the repository at (or above) the path.
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string() }
+    }
+
+    /// Rewrites `a...b` as `<merge-base>..b`, which lists the commits on `b` since the merge-base (as the other
+    /// backends do), since `git log a...b` would also list the commits on `a`.
+    async fn commit_range(&self, range: &str) -> Res<String> {
+        let Some((from, to)) = range.split_once("...") else {
+            return Ok(range.to_string());
+        };
+
+        let (from, to) = (if from.is_empty() { "HEAD" } else { from }, if to.is_empty() { "HEAD" } else { to });
+        let base = self.run(&["merge-base", from, to]).await?;
+
+        Ok(format!("{}..{}", base.trim_end(), to))
+    }
+
+    async fn run<S>(&self, args: &[S]) -> Res<String>
+        where S: AsRef<str>
+    {
+        let args = ["-C", &self.path].into_iter().chain(args.iter().map(|a| a.as_ref())).collect::<Vec<_>>();
+
+        run_output(NAME, &args).await
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new(NAME)
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+            .args(args)
+            .status()
+            .unwrap();
+
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[tokio::test]
+    async fn ignores_the_diff_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        git(root, &["init", "-q"]);
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/notes.txt"), "one\ntwo\n").unwrap();
+        fs::write(root.join(".gitattributes"), "*.txt diff=upper\n").unwrap();
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "Root"]);
+
+        for (key, value) in [("color.ui", "always"), ("diff.noprefix", "true"), ("diff.mnemonicPrefix", "true"), ("diff.relative", "true"), ("diff.external", "false"), ("diff.upper.textconv", "tr a-z A-Z")] {
+            git(root, &["config", key, value]);
         }
 
-        let stdout = String::from_utf8(output.stdout)?;
+        fs::write(root.join("src/notes.txt"), "one\n2\n").unwrap();
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "Second"]);
+        fs::write(root.join("src/notes.txt"), "one\n2\nthree\n").unwrap();
+
+        // Run from a subdirectory, where `diff.relative` would otherwise strip `src/`.
+        let git = Git::new(&root.join("src").to_string_lossy());
+
+        for (target, added) in [(DiffTarget::WorkingTree, "three"), (DiffTarget::Commit("HEAD".to_string()), "2"), (DiffTarget::Range("HEAD~1..HEAD".to_string()), "2")] {
+            let diff = git.diff(&target).await.unwrap();
 
-        Ok(stdout)
+            assert_eq!(diff.files.len(), 1, "{:?}", target);
+            assert_eq!(diff.files[0].old_path.as_deref(), Some("src/notes.txt"));
+            assert_eq!(diff.files[0].new_path.as_deref(), Some("src/notes.txt"));
+
+            let text = diff.to_string();
+            assert!(!text.contains('\x1b'), "{:?}: {}", target, text);
+            assert!(text.contains(&format!("+{}\n", added)), "{:?}: {}", target, text);
+        }
     }
-}
\ No newline at end of file
+}
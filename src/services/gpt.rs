@@ -1,16 +1,44 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, time::Duration};
 
-use chatgpt::prelude::{ChatGPT, ModelConfiguration, ChatGPTEngine};
-use url::Url;
+use tokio::sync::OnceCell;
 
-use crate::base::types::{HasName, IsEnsurable, Mode, Res, Void};
+use crate::base::{types::{HasName, IsEnsurable, Res, Void, TAB, CommitInfo, estimate_tokens}, diff::Diff, context::FileContext, commit::{self, CommitStyle}, changelog::ChangeKind, finding::{self, Review}, live::LivePreview, interrupt, prompt::{CommitVar, PromptVars, Prompts}, focus::FocusPass, guidelines::Guidelines};
+
+use super::{vcs::{AnyVcs, Vcs}, llm::{AnyProvider, ChatMessage, ChatRequest, LlmProvider, ModelSettings}};
 
 static NAME: &str = "gpt_sdk";
+static DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+/// The inputs to a single review.
+#[derive(Debug, Clone, Copy)]
+pub struct ReviewInput<'a> {
+    pub diff: &'a Diff,
+    /// Additional context from the changed files.
+    pub context: &'a [FileContext],
+    /// The commit message (or patch description) that accompanies the diff.
+    pub message: Option<&'a str>,
+    /// The project's coding guidelines (the ones that apply to the diff, or chunk, are sent as a system message).
+    pub guidelines: Option<&'a Guidelines>,
+    /// The focused review pass (if any).
+    pub focus: Option<&'a FocusPass>,
+}
+
+/// The response of the model, which is partial if the generation was stopped early (with Ctrl-C).
+pub struct Generated<T> {
+    pub value: T,
+    pub interrupted: bool,
+}
 
 pub struct Gpt {
-    url: String,
-    key: Option<String>,
-    mode: Mode,
+    provider: AnyProvider,
+    settings: ModelSettings,
+    /// Whether responses are streamed (and shown as they arrive).
+    stream: bool,
+    prompts: Prompts,
+    /// The working copy, whose branch the prompts can use.
+    vcs: AnyVcs,
+    /// The branch (looked up once, when a prompt first uses it).
+    branch: OnceCell<Option<String>>,
 }
 
 impl HasName for Gpt {
@@ -21,9 +49,7 @@ impl HasName for Gpt {
 
 impl IsEnsurable for Gpt {
     async fn is_present(&self) -> Res<bool> {
-        let _ = self.resolve_key()?;
-        
-        Ok(true)
+        self.provider.is_present().await
     }
 
     async fn make_present(&self) -> Void {
@@ -32,98 +58,374 @@ impl IsEnsurable for Gpt {
 }
 
 impl Gpt {
-    pub async fn review(&self, diff: &str) -> Res<String> {
-        let key = self.resolve_key()?;
-
-        let url = format!("{}/v1/chat/completions", self.url);
-        let config = ModelConfiguration {
-            engine: ChatGPTEngine::Gpt4,
-            api_url: Url::from_str(&url)?,
-            timeout: Duration::from_secs(300),
-            ..Default::default()
+    /// Reviews the diff (in chunks, if it is too large); if the review is stopped early, the remaining chunks are
+    /// skipped.
+    pub async fn review(&self, input: ReviewInput<'_>, max_tokens: usize) -> Res<Generated<Review>> {
+        let max_tokens = self.chunk_budget(input.diff, max_tokens).await;
+
+        // Each chunk is sent along with the guidelines and the context of its files.
+        let chunks = input.diff.chunk_with(max_tokens, |files| {
+            let context = input.context.iter().filter(|c| files.iter().any(|f| f.path() == c.path)).map(FileContext::tokens).sum::<usize>();
+
+            input.guidelines.map_or(0, |g| g.tokens(files)) + context
+        });
+
+        if chunks.len() <= 1 {
+            let response = self.send_live(self.review_messages(input).await?, render_review).await?;
+            return Ok(Generated { value: finding::parse(&response.value), interrupted: response.interrupted });
+        }
+
+        let mut reviews = Vec::new();
+        let mut interrupted = false;
+
+        for (k, chunk) in chunks.iter().enumerate() {
+            eprintln!("{}Reviewing chunk {}/{} ({} files) ...", TAB, k + 1, chunks.len(), chunk.files.len());
+
+            let chunk_context = input.context.iter().filter(|c| chunk.files.iter().any(|f| f.path() == c.path)).cloned().collect::<Vec<_>>();
+
+            let response = self.send_live(self.review_messages(ReviewInput { diff: chunk, context: &chunk_context, ..input }).await?, render_review).await?;
+            reviews.push(finding::parse(&response.value));
+
+            if response.interrupted {
+                interrupted = true;
+                break;
+            }
+        }
+
+        // Drop the plain duplicates first, so that the model merges fewer (and smaller) findings.
+        let count = reviews.len();
+        let found = reviews.iter().map(|r| r.findings.len()).sum::<usize>();
+        let deduped = Review::merge(reviews);
+        let removed = found - deduped.findings.len();
+
+        if interrupted || deduped.findings.len() < 2 {
+            eprintln!("{}Merged {} reviews ({} duplicate findings removed).", TAB, count, removed);
+            return Ok(Generated { value: deduped, interrupted });
+        }
+
+        eprintln!("{}Merging {} reviews ({} duplicate findings removed) ...", TAB, count, removed);
+
+        let response = self.send_live(vec![ChatMessage::user(self.merge_prompt(input.diff, &deduped).await?)], render_review).await?;
+
+        // A merge that was stopped early (or that lost every finding) would drop findings, so the deduped ones are kept.
+        let value = match finding::parse_json(&response.value) {
+            Some(merged) if !response.interrupted && !merged.findings.is_empty() => merged,
+            _ => {
+                eprintln!("{}Keeping the deduped findings, since the merged review is incomplete.", TAB);
+                deduped
+            },
         };
 
-        let client = ChatGPT::new_with_config(key, config)?;
+        Ok(Generated { value, interrupted: response.interrupted })
+    }
 
-        let message = REVIEW_PROMPT.replace("{{diff}}", diff);
+    /// Proposes a commit message for the diff; the `feedback` (e.g., the problems with a previous proposal) is passed
+    /// along to the model.
+    pub async fn commit_message(&self, diff: &Diff, style: CommitStyle, max_tokens: usize, feedback: Option<&str>) -> Res<String> {
+        let response = self.send(vec![ChatMessage::user(self.commit_prompt(diff, style, max_tokens, feedback).await?)]).await?;
 
-        let response = client.send_message(message).await?;
+        Ok(commit::normalize(&response))
+    }
 
-        Ok(response.message_choices[0].message.content.clone())
+    /// Writes a Markdown pull request description for the commits and their aggregate diff, filling in the `template`
+    /// (if any).
+    pub async fn pr_description(&self, commits: &[CommitInfo], diff: &Diff, template: Option<&str>, max_tokens: usize) -> Res<Generated<String>> {
+        self.send_live(vec![ChatMessage::user(self.pr_prompt(commits, diff, template, max_tokens).await?)], str::to_string).await
     }
 
-    pub async fn ask(&self, prompt: &str) -> Res<String> {
-        let key = self.resolve_key()?;
+    /// Classifies the commits for a changelog, keyed by their short sha (commits that the model skips are left out).
+    pub async fn classify_commits(&self, commits: &[CommitInfo]) -> Res<HashMap<String, ChangeKind>> {
+        let response = self.send(vec![ChatMessage::user(self.classify_prompt(commits).await?)]).await?;
 
-        let url = format!("{}/v1/chat/completions", self.url);
-        let config = ModelConfiguration {
-            engine: ChatGPTEngine::Gpt4,
-            api_url: Url::from_str(&url)?,
-            timeout: Duration::from_secs(300),
-            ..Default::default()
+        Ok(response
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.trim().trim_start_matches(['-', '*']).split_whitespace();
+                let sha = words.next()?.trim_matches('`');
+                let kind = words.next()?.trim_matches(|c: char| !c.is_ascii_alphabetic()).parse().ok()?;
+
+                commits.iter().find(|c| c.short_sha == sha).map(|c| (c.short_sha.clone(), kind))
+            })
+            .collect())
+    }
+
+    pub async fn ask(&self, prompt: &str) -> Res<Generated<String>> {
+        self.send_live(vec![ChatMessage::user(self.ask_prompt(prompt).await?)], str::to_string).await
+    }
+
+    /// Lists the models that the provider serves.
+    pub async fn models(&self) -> Res<Vec<String>> {
+        self.provider.models().await
+    }
+
+    async fn send(&self, messages: Vec<ChatMessage>) -> Res<String> {
+        self.provider.chat(&self.request(messages)).await
+    }
+
+    /// Sends the messages, showing the response (as Markdown, through `render`) in the terminal as it streams in;
+    /// Ctrl-C stops the generation early (keeping the partial response).
+    async fn send_live(&self, messages: Vec<ChatMessage>, render: fn(&str) -> String) -> Res<Generated<String>> {
+        let preview = if self.stream { LivePreview::start(render) } else { None };
+
+        let Some(mut preview) = preview else {
+            return Ok(Generated { value: self.send(messages).await?, interrupted: false });
         };
 
-        let client = ChatGPT::new_with_config(key, config)?;
+        let request = self.request(messages);
+        let mut on_delta = |delta: &str| preview.push(delta);
 
-        let response = client.send_message(prompt.to_string()).await?;
+        let interrupted = tokio::select! {
+            result = self.provider.stream(&request, &mut on_delta) => {
+                result?;
+                false
+            },
+            _ = interrupt::pressed() => true,
+        };
+
+        Ok(Generated { value: preview.finish(), interrupted })
+    }
+
+    /// Converts the budget of the model's tokens into one of estimated tokens (which the chunker counts in), using the
+    /// provider's count of the diff's tokens.
+    async fn chunk_budget(&self, diff: &Diff, max_tokens: usize) -> usize {
+        let text = diff.to_string();
+        let estimated = estimate_tokens(&text);
 
-        Ok(response.message_choices[0].message.content.clone())
+        // A diff that fits even if the estimate is off by half does not need counting (which may be a request).
+        if estimated <= max_tokens / 2 {
+            return max_tokens;
+        }
+
+        match self.provider.count_tokens(&self.request(vec![ChatMessage::user(text)])).await {
+            Ok(counted) if counted > 0 => (max_tokens * estimated / counted).max(1),
+            // Without a count, the estimate has to do.
+            _ => max_tokens,
+        }
+    }
+
+    fn request(&self, messages: Vec<ChatMessage>) -> ChatRequest {
+        let settings = &self.settings;
+
+        ChatRequest {
+            model: settings.model.clone().unwrap_or_default(),
+            messages,
+            max_tokens: settings.max_tokens,
+            temperature: settings.temperature,
+            top_p: settings.top_p,
+            seed: settings.seed,
+            stop: settings.stop.clone().unwrap_or_default(),
+            timeout: Duration::from_secs(settings.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+        }
     }
 }
 
 impl Gpt {
-    pub fn new(url: &str, key: &Option<String>, mode: Mode) -> Self {
+    pub fn new(provider: AnyProvider, settings: ModelSettings, stream: bool, prompts: Prompts, vcs: AnyVcs) -> Self {
         Self {
-            url: url.to_string(),
-            key: key.clone(),
-            mode,
+            provider,
+            settings,
+            stream,
+            prompts,
+            vcs,
+            branch: OnceCell::new(),
         }
     }
 
-    fn resolve_key(&self) -> Res<&str> {
-        let key = if self.mode == Mode::OpenAi {
-            self.key.as_ref().ok_or(anyhow::Error::msg("OpenAI key not provided.  Please set the `openai_key` config value, or use a local mode."))?
-        } else {
-            ""
+    pub fn prompts(&self) -> &Prompts {
+        &self.prompts
+    }
+}
+
+// Prompts.
+
+impl Gpt {
+    /// Builds the messages of a review: the guidelines (if any), as a system message, and the review prompt.
+    pub async fn review_messages(&self, input: ReviewInput<'_>) -> Res<Vec<ChatMessage>> {
+        let mut messages = Vec::new();
+
+        if let Some(guidelines) = guidelines_text(input) {
+            let vars = PromptVars { guidelines: Some(guidelines), ..Default::default() };
+            messages.push(ChatMessage::system(self.render("guidelines", vars).await?));
+        }
+
+        messages.push(ChatMessage::user(self.review_prompt(input).await?));
+
+        Ok(messages)
+    }
+
+    pub async fn review_prompt(&self, input: ReviewInput<'_>) -> Res<String> {
+        let vars = PromptVars {
+            diff: input.diff.to_numbered_string(),
+            files: files(input.diff),
+            context: input.context.iter().map(|c| c.to_string()).collect::<Vec<_>>().join("\n"),
+            message: input.message.map(|m| m.trim().to_string()),
+            commit_messages: input.message.iter().map(|m| m.trim().to_string()).collect(),
+            guidelines: guidelines_text(input),
+            focus: input.focus.map(|f| f.name.clone()),
+            checklist: input.focus.map(|f| f.prompt.trim().to_string()),
+            ..Default::default()
+        };
+
+        self.render("review", vars).await
+    }
+
+    /// Builds the prompt that merges the (deduped) findings of the reviews of the chunks of the diff.
+    pub async fn merge_prompt(&self, diff: &Diff, review: &Review) -> Res<String> {
+        let vars = PromptVars {
+            files: files(diff),
+            findings: serde_json::to_string_pretty(&review.findings)?,
+            ..Default::default()
+        };
+
+        self.render("merge", vars).await
+    }
+
+    pub async fn commit_prompt(&self, diff: &Diff, style: CommitStyle, max_tokens: usize, feedback: Option<&str>) -> Res<String> {
+        let vars = PromptVars {
+            diff: truncated_diff(diff, self.chunk_budget(diff, max_tokens).await),
+            files: files(diff),
+            conventional: style.conventional,
+            types: commit::CONVENTIONAL_TYPES.iter().map(|t| t.to_string()).collect(),
+            max_subject_length: style.max_subject_length,
+            feedback: feedback.map(|f| f.trim().to_string()),
+            ..Default::default()
+        };
+
+        self.render("commit-msg", vars).await
+    }
+
+    pub async fn pr_prompt(&self, commits: &[CommitInfo], diff: &Diff, template: Option<&str>, max_tokens: usize) -> Res<String> {
+        let vars = PromptVars {
+            diff: truncated_diff(diff, self.chunk_budget(diff, max_tokens).await),
+            files: files(diff),
+            commit_messages: commits.iter().map(|c| c.message.clone()).collect(),
+            commits: commit_vars(commits),
+            template: template.map(|t| t.trim().to_string()),
+            ..Default::default()
+        };
+
+        self.render("pr-description", vars).await
+    }
+
+    pub async fn classify_prompt(&self, commits: &[CommitInfo]) -> Res<String> {
+        let vars = PromptVars {
+            commit_messages: commits.iter().map(|c| c.message.clone()).collect(),
+            commits: commit_vars(commits),
+            ..Default::default()
+        };
+
+        self.render("changelog", vars).await
+    }
+
+    pub async fn ask_prompt(&self, prompt: &str) -> Res<String> {
+        self.render("ask", PromptVars { prompt: Some(prompt.to_string()), ..Default::default() }).await
+    }
+
+    /// Renders the prompt, looking up the branch (once) if its template uses it; the branch is only a nicety, so a
+    /// working copy without one (or no working copy) is fine.
+    async fn render(&self, name: &str, vars: PromptVars) -> Res<String> {
+        let branch = match vars.branch {
+            None if self.prompts.uses(name, "branch") => self.branch.get_or_init(|| async { self.vcs.branch().await.ok().flatten() }).await.clone(),
+            branch => branch,
         };
 
-        Ok(key)
+        self.prompts.render(name, PromptVars { branch, ..vars })
+    }
+}
+
+// Helpers.
+
+/// Renders a (partial) review response as the findings parsed so far, instead of the raw JSON.
+fn render_review(response: &str) -> String {
+    let review = finding::parse(response);
+
+    // Until the first finding arrives, "no issues found" would be premature.
+    if review.findings.is_empty() {
+        return review.summary.unwrap_or_default();
     }
+
+    review.to_markdown()
 }
 
-// Statics.
+fn files(diff: &Diff) -> Vec<String> {
+    diff.files.iter().map(|f| f.path().to_string()).collect()
+}
 
-static REVIEW_PROMPT: &str = r#"
-Please perform a code review of the following diff (produced by `git diff` on my code), and provide suggestions for improvement:
+/// Renders the guidelines that apply to the diff of the review (if any).
+fn guidelines_text(input: ReviewInput<'_>) -> Option<String> {
+    input.guidelines.and_then(|g| g.render_for(&input.diff.files)).map(|g| g.trim().to_string())
+}
 
-```
-{{diff}}
-```
+fn commit_vars(commits: &[CommitInfo]) -> Vec<CommitVar> {
+    commits.iter().map(|c| CommitVar { short_sha: c.short_sha.clone(), subject: c.subject.clone(), message: c.message.clone() }).collect()
+}
 
-Please prioritize the response by impact to the code, and please split the suggestions into three categories:
-1. Suggestions that pertain to likely runtime bugs or errors.
-2. Suggestions that pertain to likely logic bugs or errors.
-3. Suggestions that pertain to likely style bugs or errors.
+/// Renders the diff, keeping only as much as fits in `max_tokens` (along with the list of all changed files) when it is
+/// too large.
+fn truncated_diff(diff: &Diff, max_tokens: usize) -> String {
+    let chunks = diff.chunk(max_tokens);
+
+    match chunks.first() {
+        Some(first) if chunks.len() > 1 => {
+            let files = diff.files.iter().map(|f| format!("- {}", f.path())).collect::<Vec<_>>().join("\n");
+            format!("{}\n(The diff was truncated; all of the changed files are:\n{})", first, files)
+        },
+        _ => diff.to_string(),
+    }
+}
 
-If possible, please also provide a suggested fix to the identified issue.  If you are unable to provide a suggested fix, please provide a reason why.
 
-The format should look like:
+// Tests.
 
-```
-1. Likely runtime bugs:
-- Some suggestion...
-- Another...
+#[cfg(test)]
+mod tests {
+    use crate::services::{git::Git, llm::{testing::serve, Provider}};
 
-2. Likely logic bugs:
-- Suggestion 1
-- Suggestion 2
-- Suggestion 3
+    use super::*;
 
-3. Likely style bugs:
-- Suggestion 1
-- Suggestion 2
-- Suggestion 3
-```
+    #[tokio::test]
+    async fn renders_the_merge_prompt() {
+        let gpt = Gpt::new(AnyProvider::new(Provider::OpenAi, "http://127.0.0.1:9", &None), ModelSettings::default(), false, Prompts::new("/nonexistent", PromptVars::default()), AnyVcs::Git(Git::new("/nonexistent")));
 
-For each relevant code snippet, please provide context about where the suggestion is relevant (e.g., `path/file.rs:30`); in addition, if a code snippet would be helpful, please provide a code snippet showing the fix.
-"#;
\ No newline at end of file
+        let diff = Diff::parse("diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+        let review = finding::parse(r#"{"findings": [{"file": "src/a.rs", "line": 1, "severity": "logic", "message": "Wrong letter."}]}"#);
+
+        let prompt = gpt.merge_prompt(&diff, &review).await.unwrap();
+
+        assert!(prompt.contains("separate parts of a single diff (produced by `git diff` on my code) to src/a.rs:"));
+        assert!(prompt.contains(r#""message": "Wrong letter.""#));
+        assert!(prompt.contains(r#""line_range": {"#));
+        assert!(prompt.ends_with("with a `summary` of a sentence or two about the whole review."));
+    }
+
+    #[tokio::test]
+    async fn budgets_chunks_in_the_providers_tokens() {
+        let gpt = |url: &str| Gpt::new(AnyProvider::new(Provider::Anthropic, url, &Some("key".to_string())), ModelSettings::default(), false, Prompts::new("/nonexistent", PromptVars::default()), AnyVcs::Git(Git::new("/nonexistent")));
+
+        let diff = Diff::parse(&format!("diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+{}\n", "b".repeat(400))).unwrap();
+        let estimated = estimate_tokens(&diff.to_string());
+
+        // The provider counts twice as many tokens as estimated, so only half as many estimated tokens fit.
+        let (url, received) = serve(200, &[format!(r#"{{"input_tokens": {}}}"#, 2 * estimated).as_bytes()]).await;
+        let max_tokens = estimated + estimated / 2;
+        assert_eq!(gpt(&url).chunk_budget(&diff, max_tokens).await, max_tokens / 2);
+        assert_eq!(received.await.unwrap().line, "POST /v1/messages/count_tokens HTTP/1.1");
+
+        // Without a count (or when the diff is small), the estimate is used.
+        assert_eq!(gpt("http://127.0.0.1:9").chunk_budget(&diff, max_tokens).await, max_tokens);
+        assert_eq!(gpt("http://127.0.0.1:9").chunk_budget(&diff, 10 * estimated).await, 10 * estimated);
+    }
+
+    #[test]
+    fn previews_the_findings_parsed_so_far() {
+        let summary = r#"{"summary": "Adds a parser.", "findings": [{"file": "src/a"#;
+        assert_eq!(render_review(summary), "Adds a parser.");
+
+        let finding = r#"{"summary": "Adds a parser.", "findings": [{"file": "src/a.rs", "line": 3, "severity": "runtime", "message": "Panics on empty input."}, {"fi"#;
+        let preview = render_review(finding);
+
+        assert!(!preview.contains('{'));
+        assert!(preview.contains("src/a.rs:3"));
+        assert!(preview.contains("Panics on empty input."));
+    }
+}
@@ -0,0 +1,196 @@
+use std::path::PathBuf;
+
+use yansi::Paint;
+
+use crate::base::{types::{HasName, IsEnsurable, is_binary_present, run_output, run_with_input, Res, Void, TAB, DiffTarget, CommitInfo}, diff::Diff};
+
+use super::vcs::{self, Vcs};
+
+static NAME: &str = "hg";
+
+/// A Mercurial working copy (via the `hg` binary).
+#[derive(Default)]
+pub struct Hg {}
+
+impl HasName for Hg {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for Hg {
+    async fn is_present(&self) -> Res<bool> {
+        is_binary_present(self).await
+    }
+
+    async fn make_present(&self) -> Void {
+        eprintln!("{}{}: Please install `{}` manually.", TAB, Paint::red("✘"), Paint::blue(NAME));
+        Err(anyhow::anyhow!("User skipped required operation."))
+    }
+}
+
+impl Vcs for Hg {
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff> {
+        let mut args = vec!["diff".to_string(), "--git".to_string()];
+
+        match target {
+            DiffTarget::WorkingTree => {},
+            DiffTarget::Staged => return Err(anyhow::Error::msg("Mercurial does not have a staging area: please review the working tree instead.")),
+            DiffTarget::Commit(rev) => args.extend(["-c".to_string(), revision(rev)]),
+            DiffTarget::Range(range) => {
+                let (from, to) = split_range(range);
+                args.extend(["-r".to_string(), from, "-r".to_string(), to]);
+            },
+            DiffTarget::MergeBase(base) => args.extend(["-r".to_string(), format!("ancestor({}, .)", revision(base)), "-r".to_string(), ".".to_string()]),
+        }
+
+        Diff::parse(&run_output(NAME, &args).await?)
+    }
+
+    async fn untracked_files(&self) -> Res<Vec<String>> {
+        let stdout = run_output(NAME, &["status", "--unknown", "--no-status", "--print0"]).await?;
+
+        Ok(stdout.split('\0').filter(|p| !p.is_empty()).map(str::to_string).collect())
+    }
+
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>> {
+        let revset = commit_revset(range);
+
+        let template = "{node}\x1f{node|short}\x1f{desc|firstline}\x1f{date|shortdate}\x1f{desc}\x1e";
+        let stdout = run_output(NAME, &["log", "-r", &revset, "--template", template]).await?;
+
+        Ok(vcs::parse_commits(&stdout))
+    }
+
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>> {
+        let revision = match target {
+            DiffTarget::WorkingTree | DiffTarget::Staged => return self.working_file(path).await,
+            DiffTarget::Commit(rev) => revision(rev),
+            DiffTarget::Range(range) => split_range(range).1,
+            DiffTarget::MergeBase(_) => ".".to_string(),
+        };
+
+        // Diff paths are relative to the root, while plain paths are relative to the current directory.
+        vcs::file_at(NAME, &["cat", "-r", &revision, &format!("path:{}", path)]).await
+    }
+
+    async fn branch(&self) -> Res<Option<String>> {
+        let branch = run_output(NAME, &["branch"]).await?;
+
+        Ok(Some(branch.trim().to_string()).filter(|b| !b.is_empty()))
+    }
+
+    async fn root(&self) -> Res<PathBuf> {
+        Ok(PathBuf::from(run_output(NAME, &["root"]).await?.trim_end_matches('\n')))
+    }
+
+    async fn commit(&self, message: &str) -> Void {
+        run_with_input(NAME, &["commit", "--logfile", "-"], message).await
+    }
+
+    fn commit_target(&self) -> DiffTarget {
+        DiffTarget::WorkingTree
+    }
+}
+
+// Helpers.
+
+/// Translates git-style revisions (e.g., `HEAD`, `HEAD~2`, or `HEAD^`) into Mercurial revsets, in which `.` is the
+/// working copy's parent (and `~n` and `^` mean the same as in git).
+///
+/// Only whole `HEAD` tokens are translated, so that names that merely contain it (e.g., `fix-HEADER`) are left alone.
+fn revision(rev: &str) -> String {
+    if rev.is_empty() || rev == "@" {
+        return ".".to_string();
+    }
+
+    let is_name = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || "_-./".contains(c));
+
+    let mut result = String::new();
+    let mut rest = 0;
+
+    for (start, _) in rev.match_indices("HEAD") {
+        let end = start + "HEAD".len();
+
+        if is_name(rev[..start].chars().next_back()) || is_name(rev[end..].chars().next()) {
+            continue;
+        }
+
+        result.push_str(&rev[rest..start]);
+        result.push('.');
+        rest = end;
+    }
+
+    result.push_str(&rev[rest..]);
+
+    result
+}
+
+/// Translates a git-style range of commits into a Mercurial revset (newest first).
+fn commit_revset(range: &str) -> String {
+    // `<rev>^!` is git's notation for the commit alone.
+    if let Some(rev) = range.strip_suffix("^!") {
+        return revision(rev);
+    }
+
+    // `a...b` lists the commits on `b` since the merge-base, as does `a..b` (git would also list those on `a`).
+    match range.split_once("..") {
+        Some((from, to)) => format!("sort(only({}, {}), rev)", revision(to.trim_start_matches('.')), revision(from)),
+        None => revision(range),
+    }
+}
+
+/// Splits a git-style range (`a..b` or `a...b`) into the revisions to diff between.
+fn split_range(range: &str) -> (String, String) {
+    if let Some((from, to)) = range.split_once("...") {
+        let to = revision(to);
+        return (format!("ancestor({}, {})", revision(from), to), to);
+    }
+
+    match range.split_once("..") {
+        Some((from, to)) => (revision(from), revision(to)),
+        None => (revision(range), ".".to_string()),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_git_revisions() {
+        assert_eq!(revision(""), ".");
+        assert_eq!(revision("@"), ".");
+        assert_eq!(revision("HEAD"), ".");
+        assert_eq!(revision("HEAD~2"), ".~2");
+        assert_eq!(revision("HEAD^"), ".^");
+        assert_eq!(revision("ancestor(HEAD, main)"), "ancestor(., main)");
+    }
+
+    #[test]
+    fn leaves_names_that_contain_head_alone() {
+        assert_eq!(revision("fix-HEADER"), "fix-HEADER");
+        assert_eq!(revision("fix-HEAD"), "fix-HEAD");
+        assert_eq!(revision("HEAD_2"), "HEAD_2");
+        assert_eq!(revision("origin/HEAD"), "origin/HEAD");
+        assert_eq!(revision("a1b2c3d"), "a1b2c3d");
+    }
+
+    #[test]
+    fn splits_git_ranges() {
+        assert_eq!(split_range("HEAD~3..HEAD"), (".~3".to_string(), ".".to_string()));
+        assert_eq!(split_range("main...HEAD"), ("ancestor(main, .)".to_string(), ".".to_string()));
+        assert_eq!(split_range("fix-HEADER..default"), ("fix-HEADER".to_string(), "default".to_string()));
+        assert_eq!(split_range("main"), ("main".to_string(), ".".to_string()));
+    }
+
+    #[test]
+    fn translates_commit_ranges() {
+        assert_eq!(commit_revset("main..HEAD"), "sort(only(., main), rev)");
+        assert_eq!(commit_revset("main...HEAD"), "sort(only(., main), rev)");
+        assert_eq!(commit_revset("HEAD^!"), ".");
+        assert_eq!(commit_revset("v1.0"), "v1.0");
+    }
+}
@@ -0,0 +1,360 @@
+use std::{path::{Path, PathBuf}, str::FromStr};
+
+use yansi::Paint;
+
+use crate::base::types::{HasName, IsEnsurable, IsRemovable, Mode, Res, Void, TAB, DiffTarget, run_output};
+
+// Statics.
+
+/// Marks the hooks that `augre` manages, so that it never overwrites (or removes) anyone else's hook.
+static MARKER: &str = "# Managed by augre";
+
+static BACKUP_SUFFIX: &str = ".augre-backup";
+
+// Types.
+
+/// The git hooks that can run a review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    /// Reviews the staged changes before each commit.
+    PreCommit,
+    /// Reviews the outgoing commits before each push.
+    PrePush,
+    /// Proposes a commit message for the staged changes.
+    PrepareCommitMsg,
+}
+
+impl HookKind {
+    pub fn all() -> [HookKind; 3] {
+        [HookKind::PreCommit, HookKind::PrePush, HookKind::PrepareCommitMsg]
+    }
+
+    /// The hooks that are installed when none are specified (the review gates).
+    pub fn defaults() -> [HookKind; 2] {
+        [HookKind::PreCommit, HookKind::PrePush]
+    }
+}
+
+impl FromStr for HookKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pre-commit" => Ok(HookKind::PreCommit),
+            "pre-push" => Ok(HookKind::PrePush),
+            "prepare-commit-msg" => Ok(HookKind::PrepareCommitMsg),
+            _ => Err(anyhow::Error::msg("Invalid hook specified (expected `pre-commit`, `pre-push`, or `prepare-commit-msg`).")),
+        }
+    }
+}
+
+impl std::fmt::Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookKind::PreCommit => write!(f, "pre-commit"),
+            HookKind::PrePush => write!(f, "pre-push"),
+            HookKind::PrepareCommitMsg => write!(f, "prepare-commit-msg"),
+        }
+    }
+}
+
+/// A git hook script that runs `augre hook run <kind>`.
+pub struct Hook {
+    kind: HookKind,
+    path: PathBuf,
+    data_path: String,
+    mode: Mode,
+}
+
+impl HasName for Hook {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            HookKind::PreCommit => "pre-commit hook",
+            HookKind::PrePush => "pre-push hook",
+            HookKind::PrepareCommitMsg => "prepare-commit-msg hook",
+        }
+    }
+}
+
+impl IsEnsurable for Hook {
+    async fn is_present(&self) -> Res<bool> {
+        // An outdated script is not "present", so that `ensure` rewrites it.
+        Ok(std::fs::read_to_string(&self.path).is_ok_and(|s| s == self.script()))
+    }
+
+    async fn make_present(&self) -> Void {
+        if self.is_foreign() {
+            let backup = self.backup_path();
+
+            // The backup is the user's own hook, so it is never overwritten.
+            if backup.exists() {
+                return Err(anyhow::Error::msg(format!("Unable to move the existing hook aside, since `{}` already exists: please merge (or remove) one of them first.", backup.display())));
+            }
+
+            eprintln!("{}Moving the existing hook to `{}` ...", TAB, Paint::blue(backup.display()));
+            std::fs::rename(&self.path, &backup)?;
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&self.path, self.script())?;
+        make_executable(&self.path)?;
+
+        Ok(())
+    }
+}
+
+impl IsRemovable for Hook {
+    async fn make_not_present(&self) -> Void {
+        std::fs::remove_file(&self.path)?;
+
+        let backup = self.backup_path();
+
+        if backup.exists() {
+            eprintln!("{}Restoring the previous hook from `{}` ...", TAB, Paint::blue(backup.display()));
+            std::fs::rename(&backup, &self.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Hook {
+    pub fn new(kind: HookKind, hooks_path: &Path, data_path: &str, mode: Mode) -> Self {
+        Self {
+            kind,
+            path: hooks_path.join(kind.to_string()),
+            data_path: data_path.to_string(),
+            mode,
+        }
+    }
+
+    /// Whether `augre` installed the hook (regardless of whether it is up to date).
+    pub fn is_installed(&self) -> bool {
+        std::fs::read_to_string(&self.path).is_ok_and(|s| s.contains(MARKER))
+    }
+
+    /// Whether a hook that `augre` did not install is in the way.
+    fn is_foreign(&self) -> bool {
+        self.path.exists() && !self.is_installed()
+    }
+
+    fn backup_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(BACKUP_SUFFIX);
+
+        PathBuf::from(path)
+    }
+
+    fn script(&self) -> String {
+        let mode = format!("{:?}", self.mode).to_ascii_lowercase();
+
+        // Only `pre-push` gets input on stdin, which both the previous hook and `augre` need.
+        let template = match self.kind {
+            HookKind::PrePush => PRE_PUSH_SCRIPT,
+            _ => HOOK_SCRIPT,
+        };
+
+        template
+            .replace("{{marker}}", MARKER)
+            .replace("{{backup_suffix}}", BACKUP_SUFFIX)
+            .replace("{{data_path}}", &self.data_path.replace('\'', "'\\''"))
+            .replace("{{mode}}", &mode)
+            .replace("{{kind}}", &self.kind.to_string())
+            .trim_start()
+            .to_string()
+    }
+}
+
+// Helpers.
+
+/// The directory that git runs hooks from (which respects `core.hooksPath`).
+pub async fn hooks_path() -> Res<PathBuf> {
+    let path = run_output("git", &["rev-parse", "--git-path", "hooks"]).await?;
+
+    Ok(PathBuf::from(path.trim()))
+}
+
+/// Resolves the outgoing changes of a push from the lines that git passes to the `pre-push` hook on stdin
+/// (`<local ref> <local sha> <remote ref> <remote sha>`).
+///
+/// A new branch is reviewed since its merge-base with the remote's default branch (or as a single commit, if the
+/// remote's default branch is unknown), and deleted branches are skipped.
+pub async fn push_targets(remote: &str, stdin: &str) -> Res<Vec<DiffTarget>> {
+    let mut targets = Vec::new();
+
+    for line in stdin.lines() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        let [_, local_sha, _, remote_sha] = fields[..] else {
+            continue;
+        };
+
+        if is_zero(local_sha) {
+            continue;
+        }
+
+        if !is_zero(remote_sha) {
+            targets.push(DiffTarget::Range(format!("{}..{}", remote_sha, local_sha)));
+            continue;
+        }
+
+        let default_branch = format!("refs/remotes/{}/HEAD", remote);
+
+        match run_output("git", &["rev-parse", "--verify", "--quiet", &default_branch]).await {
+            Ok(_) => targets.push(DiffTarget::Range(format!("{}...{}", default_branch, local_sha))),
+            Err(_) => targets.push(DiffTarget::Commit(local_sha.to_string())),
+        }
+    }
+
+    Ok(targets)
+}
+
+fn is_zero(sha: &str) -> bool {
+    sha.chars().all(|c| c == '0')
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Void {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Void {
+    Ok(())
+}
+
+// Statics.
+
+/// The hook script, which runs the hook that it replaced (if any) first.
+static HOOK_SCRIPT: &str = r#"
+#!/bin/sh
+{{marker}} (`augre hook uninstall` removes this hook, and `git commit --no-verify` or `git push --no-verify` skips it).
+if [ -x "$0{{backup_suffix}}" ]; then
+    "$0{{backup_suffix}}" "$@" || exit $?
+fi
+exec augre --data-path '{{data_path}}' --mode {{mode}} --yes hook run {{kind}} "$@"
+"#;
+
+/// The `pre-push` hook script, which passes the pushed refs (on stdin) to both the hook that it replaced and `augre`.
+static PRE_PUSH_SCRIPT: &str = r#"
+#!/bin/sh
+{{marker}} (`augre hook uninstall` removes this hook, and `git commit --no-verify` or `git push --no-verify` skips it).
+refs=$(cat)
+if [ -x "$0{{backup_suffix}}" ]; then
+    printf '%s\n' "$refs" | "$0{{backup_suffix}}" "$@" || exit $?
+fi
+printf '%s\n' "$refs" | augre --data-path '{{data_path}}' --mode {{mode}} --yes hook run {{kind}} "$@"
+"#;
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(kind: HookKind, dir: &Path) -> Hook {
+        Hook::new(kind, dir, "it's/.augre", Mode::OpenAi)
+    }
+
+    #[test]
+    fn renders_the_scripts() {
+        let dir = Path::new("hooks");
+
+        let script = hook(HookKind::PreCommit, dir).script();
+        assert!(script.starts_with("#!/bin/sh\n# Managed by augre"));
+        assert!(script.contains(r#"if [ -x "$0.augre-backup" ]; then"#));
+        assert!(script.contains(r#"exec augre --data-path 'it'\''s/.augre' --mode openai --yes hook run pre-commit "$@""#));
+
+        let script = hook(HookKind::PrePush, dir).script();
+        assert!(script.contains("refs=$(cat)"));
+        assert!(script.contains(r#"printf '%s\n' "$refs" | "$0.augre-backup" "$@" || exit $?"#));
+        assert!(script.contains(r#"printf '%s\n' "$refs" | augre --data-path 'it'\''s/.augre' --mode openai --yes hook run pre-push "$@""#));
+    }
+
+    #[tokio::test]
+    async fn backs_up_and_restores_foreign_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = hook(HookKind::PreCommit, dir.path());
+        let path = dir.path().join("pre-commit");
+        let backup = dir.path().join("pre-commit.augre-backup");
+
+        std::fs::write(&path, "#!/bin/sh\nnpx lint-staged\n").unwrap();
+
+        hook.make_present().await.unwrap();
+        assert!(hook.is_installed());
+        assert!(hook.is_present().await.unwrap());
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "#!/bin/sh\nnpx lint-staged\n");
+
+        // Reinstalling over its own hook leaves the backup alone.
+        hook.make_present().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "#!/bin/sh\nnpx lint-staged\n");
+
+        hook.make_not_present().await.unwrap();
+        assert!(!hook.is_installed());
+        assert!(!backup.exists());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "#!/bin/sh\nnpx lint-staged\n");
+    }
+
+    #[tokio::test]
+    async fn never_overwrites_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = hook(HookKind::PrePush, dir.path());
+
+        std::fs::write(dir.path().join("pre-push"), "new foreign hook").unwrap();
+        std::fs::write(dir.path().join("pre-push.augre-backup"), "old foreign hook").unwrap();
+
+        assert!(hook.make_present().await.is_err());
+        assert_eq!(std::fs::read_to_string(dir.path().join("pre-push")).unwrap(), "new foreign hook");
+        assert_eq!(std::fs::read_to_string(dir.path().join("pre-push.augre-backup")).unwrap(), "old foreign hook");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn runs_the_previous_hook_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        let log = dir.path().join("log");
+        std::fs::create_dir(&bin).unwrap();
+
+        // A fake `augre`, and a previous hook that fails on the second ref.
+        let augre = bin.join("augre");
+        std::fs::write(&augre, format!("#!/bin/sh\necho \"augre $*: $(cat)\" >> '{}'\n", log.display())).unwrap();
+        make_executable(&augre).unwrap();
+
+        let path = dir.path().join("pre-push");
+        std::fs::write(&path, format!("#!/bin/sh\nrefs=$(cat)\necho \"previous $*: $refs\" >> '{}'\ncase \"$refs\" in *fail*) exit 3;; esac\n", log.display())).unwrap();
+        make_executable(&path).unwrap();
+
+        hook(HookKind::PrePush, dir.path()).make_present().await.unwrap();
+
+        let run = |refs: &str| {
+            let search_path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+
+            let mut child = std::process::Command::new(&path)
+                .args(["origin", "url"])
+                .env("PATH", search_path)
+                .stdin(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            std::io::Write::write_all(child.stdin.as_mut().unwrap(), refs.as_bytes()).unwrap();
+            child.wait().unwrap().code()
+        };
+
+        assert_eq!(run("refs/heads/main 1 refs/heads/main 2"), Some(0));
+        assert_eq!(run("fail"), Some(3));
+
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "\
+previous origin url: refs/heads/main 1 refs/heads/main 2
+augre --data-path it's/.augre --mode openai --yes hook run pre-push origin url: refs/heads/main 1 refs/heads/main 2
+previous origin url: fail
+");
+    }
+}
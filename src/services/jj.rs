@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+
+use yansi::Paint;
+
+use crate::base::{types::{HasName, IsEnsurable, is_binary_present, run_output, Res, Void, TAB, DiffTarget, CommitInfo}, diff::Diff};
+
+use super::vcs::{self, Vcs};
+
+static NAME: &str = "jj";
+
+/// A Jujutsu working copy (via the `jj` binary).
+#[derive(Default)]
+pub struct Jj {}
+
+impl HasName for Jj {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for Jj {
+    async fn is_present(&self) -> Res<bool> {
+        is_binary_present(self).await
+    }
+
+    async fn make_present(&self) -> Void {
+        eprintln!("{}{}: Please install `{}` manually.", TAB, Paint::red("✘"), Paint::blue(NAME));
+        Err(anyhow::anyhow!("User skipped required operation."))
+    }
+}
+
+impl Vcs for Jj {
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff> {
+        let mut args = vec!["diff".to_string(), "--git".to_string()];
+
+        match target {
+            DiffTarget::WorkingTree => {},
+            DiffTarget::Staged => return Err(anyhow::Error::msg("Jujutsu does not have a staging area: please review the working copy instead.")),
+            DiffTarget::Commit(rev) => args.extend(["-r".to_string(), revision(rev)?]),
+            DiffTarget::Range(range) => {
+                let (from, to) = split_range(range)?;
+                args.extend(["--from".to_string(), from, "--to".to_string(), to]);
+            },
+            DiffTarget::MergeBase(base) => args.extend(["--from".to_string(), format!("fork_point({} | @)", revision(base)?), "--to".to_string(), "@".to_string()]),
+        }
+
+        Diff::parse(&run_output(NAME, &args).await?)
+    }
+
+    async fn untracked_files(&self) -> Res<Vec<String>> {
+        // Jujutsu tracks new files automatically, so they already appear in the working copy diff.
+        Ok(Vec::new())
+    }
+
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>> {
+        // `<rev>^!` is git's notation for the commit alone.
+        let revset = match range.split_once("..") {
+            _ if range.ends_with("^!") => revision(range.trim_end_matches("^!"))?,
+            Some((from, to)) => format!("{}..{}", revision(from)?, revision(to.trim_start_matches('.'))?),
+            None => revision(range)?,
+        };
+
+        let template = "commit_id ++ \"\x1f\" ++ commit_id.short() ++ \"\x1f\" ++ description.first_line() ++ \"\x1f\" ++ committer.timestamp().format(\"%Y-%m-%d\") ++ \"\x1f\" ++ description ++ \"\x1e\"";
+        let stdout = run_output(NAME, &["log", "--no-graph", "--reversed", "-r", &revset, "-T", template]).await?;
+
+        Ok(vcs::parse_commits(&stdout))
+    }
+
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>> {
+        let revision = match target {
+            DiffTarget::WorkingTree | DiffTarget::Staged => return self.working_file(path).await,
+            DiffTarget::Commit(rev) => revision(rev)?,
+            DiffTarget::Range(range) => split_range(range)?.1,
+            DiffTarget::MergeBase(_) => "@".to_string(),
+        };
+
+        // Diff paths are relative to the root, while plain paths are relative to the current directory.
+        vcs::file_at(NAME, &["file", "show", "-r", &revision, &root_fileset(path)]).await
+    }
+
+    async fn branch(&self) -> Res<Option<String>> {
+        // Jujutsu has no current branch, so use the closest bookmark among the working copy's ancestors (if any).
+        let bookmarks = run_output(NAME, &["log", "--no-graph", "-r", "latest(::@ & bookmarks())", "-T", "local_bookmarks.map(|b| b.name()).join(\"\\n\")"]).await?;
+
+        Ok(bookmarks.lines().next().map(|b| b.trim().to_string()).filter(|b| !b.is_empty()))
+    }
+
+    async fn root(&self) -> Res<PathBuf> {
+        Ok(PathBuf::from(run_output(NAME, &["root"]).await?.trim_end_matches('\n')))
+    }
+
+    async fn commit(&self, message: &str) -> Void {
+        // Describes the working-copy commit, and starts a new one on top of it.
+        run_output(NAME, &["commit", "--message", message]).await?;
+
+        Ok(())
+    }
+
+    fn commit_target(&self) -> DiffTarget {
+        DiffTarget::WorkingTree
+    }
+}
+
+// Helpers.
+
+/// Translates git-style revisions (e.g., `HEAD`, `abc^`, `HEAD~2`) into Jujutsu revsets (e.g., `@-`, `abc-`, `@---`).
+///
+/// Git's `HEAD` (the last commit) is the parent of Jujutsu's working-copy commit, so it maps to `@-`.  Anything that
+/// does not look like a git revision (e.g., `trunk() ~ @`) is passed along as a revset.
+fn revision(rev: &str) -> Res<String> {
+    if rev == "@" {
+        return Ok(rev.to_string());
+    }
+
+    if !rev.chars().all(|c| c.is_ascii_alphanumeric() || "-_./~^".contains(c)) {
+        return Ok(rev.to_string());
+    }
+
+    let (base, mut suffix) = rev.split_at(rev.find(['~', '^']).unwrap_or(rev.len()));
+    let mut revset = if base.is_empty() || base == "HEAD" { "@-".to_string() } else { base.to_string() };
+
+    while let Some(operator) = suffix.chars().next() {
+        let digits = suffix[1..].chars().take_while(char::is_ascii_digit).count();
+        let count = if digits == 0 { 1 } else { suffix[1..=digits].parse::<usize>()? };
+
+        match (operator, count) {
+            ('~', _) | ('^', 1) => revset.push_str(&"-".repeat(count)),
+            ('^', 0) => {},
+            ('^', _) => return Err(anyhow::Error::msg(format!("Unable to translate `{}` into a Jujutsu revset (git's `^{}` selects a parent of a merge): please use a revset instead.", rev, count))),
+            _ => return Ok(rev.to_string()),
+        }
+
+        suffix = &suffix[1 + digits..];
+    }
+
+    Ok(revset)
+}
+
+/// Splits a git-style range (`a..b` or `a...b`) into the revisions to diff between.
+fn split_range(range: &str) -> Res<(String, String)> {
+    if let Some((from, to)) = range.split_once("...") {
+        let to = revision(to)?;
+        return Ok((format!("fork_point({} | {})", revision(from)?, to), to));
+    }
+
+    match range.split_once("..") {
+        Some((from, to)) => Ok((revision(from)?, revision(to)?)),
+        None => Ok((revision(range)?, "@".to_string())),
+    }
+}
+
+/// Quotes the root-relative path as a Jujutsu fileset (e.g., `root:"src/main.rs"`).
+fn root_fileset(path: &str) -> String {
+    format!("root:\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_git_revisions() {
+        assert_eq!(revision("HEAD").unwrap(), "@-");
+        assert_eq!(revision("").unwrap(), "@-");
+        assert_eq!(revision("HEAD~2").unwrap(), "@---");
+        assert_eq!(revision("HEAD^").unwrap(), "@--");
+        assert_eq!(revision("HEAD~").unwrap(), "@--");
+        assert_eq!(revision("main^^").unwrap(), "main--");
+        assert_eq!(revision("abc123~3^").unwrap(), "abc123----");
+        assert_eq!(revision("v1.0^0").unwrap(), "v1.0");
+        assert!(revision("HEAD^2").is_err());
+    }
+
+    #[test]
+    fn passes_revsets_through() {
+        assert_eq!(revision("@").unwrap(), "@");
+        assert_eq!(revision("@-").unwrap(), "@-");
+        assert_eq!(revision("main-").unwrap(), "main-");
+        assert_eq!(revision("trunk()").unwrap(), "trunk()");
+        assert_eq!(revision("main~feature").unwrap(), "main~feature");
+        assert_eq!(revision("trunk() ~ @").unwrap(), "trunk() ~ @");
+    }
+
+    #[test]
+    fn splits_git_ranges() {
+        assert_eq!(split_range("HEAD~3..HEAD").unwrap(), ("@----".to_string(), "@-".to_string()));
+        assert_eq!(split_range("main...HEAD").unwrap(), ("fork_point(main | @-)".to_string(), "@-".to_string()));
+        assert_eq!(split_range("main").unwrap(), ("main".to_string(), "@".to_string()));
+    }
+
+    #[test]
+    fn quotes_root_filesets() {
+        assert_eq!(root_fileset("src/main.rs"), r#"root:"src/main.rs""#);
+        assert_eq!(root_fileset(r#"a "b"\c.rs"#), r#"root:"a \"b\"\\c.rs""#);
+    }
+}
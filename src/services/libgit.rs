@@ -0,0 +1,382 @@
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, DiffFindOptions, DiffFormat, DiffOptions, Index, Repository, RevparseMode, Sort, StatusOptions, Tree};
+
+use crate::base::{types::{HasName, IsEnsurable, Res, Void, DiffTarget, CommitInfo, format_date}, diff::Diff};
+
+use super::{git::Git, vcs::Vcs};
+
+static NAME: &str = "libgit2";
+
+/// An in-process git implementation (via `libgit2`), which does not need a `git` binary.
+pub struct LibGit {
+    path: PathBuf,
+}
+
+impl HasName for LibGit {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for LibGit {
+    async fn is_present(&self) -> Res<bool> {
+        Ok(self.can_open())
+    }
+
+    async fn make_present(&self) -> Void {
+        Err(anyhow::Error::msg(format!("Unable to open a git repository at `{}`.  If the repository uses features that `libgit2` does not support, set `vcs_backend = \"Cli\"`.", self.path.display())))
+    }
+}
+
+impl Vcs for LibGit {
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff> {
+        let repo = self.open()?;
+
+        let mut options = DiffOptions::new();
+        let mut diff = match target {
+            DiffTarget::WorkingTree => repo.diff_index_to_workdir(None, Some(&mut options))?,
+            DiffTarget::Staged => repo.diff_tree_to_index(head_tree(&repo)?.as_ref(), None, Some(&mut options))?,
+            DiffTarget::Commit(sha) => {
+                let commit = repo.revparse_single(sha)?.peel_to_commit()?;
+                let parent = commit.parents().next().map(|p| p.tree()).transpose()?;
+
+                repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), Some(&mut options))?
+            },
+            DiffTarget::Range(range) => {
+                let spec = repo.revparse(range)?;
+                let to = match spec.to() {
+                    Some(to) => to.clone(),
+                    None => repo.head()?.peel(git2::ObjectType::Commit)?,
+                };
+                let from = spec.from().ok_or_else(|| anyhow::Error::msg(format!("Invalid range `{}`.", range)))?;
+
+                let from_tree = if spec.mode().contains(RevparseMode::MERGE_BASE) {
+                    repo.find_commit(repo.merge_base(from.id(), to.id())?)?.tree()?
+                } else {
+                    from.peel_to_tree()?
+                };
+
+                repo.diff_tree_to_tree(Some(&from_tree), Some(&to.peel_to_tree()?), Some(&mut options))?
+            },
+            DiffTarget::MergeBase(base) => {
+                let base = repo.revparse_single(base)?.peel_to_commit()?;
+                let head = repo.head()?.peel_to_commit()?;
+                let merge_base = repo.find_commit(repo.merge_base(base.id(), head.id())?)?;
+
+                repo.diff_tree_to_tree(Some(&merge_base.tree()?), Some(&head.tree()?), Some(&mut options))?
+            },
+        };
+
+        // Match `git diff`, which detects renames by default.
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let mut text = String::new();
+
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            if matches!(line.origin(), ' ' | '+' | '-') {
+                text.push(line.origin());
+            }
+
+            text.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+
+        Diff::parse(&text)
+    }
+
+    async fn untracked_files(&self) -> Res<Vec<String>> {
+        let repo = self.open()?;
+
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true).include_ignored(false);
+
+        let statuses = repo.statuses(Some(&mut options))?;
+
+        Ok(statuses.iter().filter(|e| e.status().is_wt_new()).filter_map(|e| e.path().ok().map(str::to_string)).collect())
+    }
+
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>> {
+        let repo = self.open()?;
+
+        // `libgit2` does not understand `<sha>^!` (the commit alone).
+        if let Some(sha) = range.strip_suffix("^!") {
+            return Ok(vec![commit_info(&repo, repo.revparse_single(sha)?.peel_to_commit()?.id())?]);
+        }
+
+        let spec = repo.revparse(range)?;
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+
+        match (spec.from(), spec.to()) {
+            (Some(from), Some(to)) => {
+                walk.push(to.id())?;
+
+                if spec.mode().contains(RevparseMode::MERGE_BASE) {
+                    walk.hide(repo.merge_base(from.id(), to.id())?)?;
+                } else {
+                    walk.hide(from.id())?;
+                }
+            },
+            (Some(from), None) if spec.mode().contains(RevparseMode::RANGE) => {
+                walk.push_head()?;
+                walk.hide(from.id())?;
+            },
+            (Some(single), None) => walk.push(single.id())?,
+            _ => return Err(anyhow::Error::msg(format!("Invalid range `{}`.", range))),
+        }
+
+        walk.map(|oid| commit_info(&repo, oid?)).collect()
+    }
+
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>> {
+        let Some(revision) = target.new_revision() else {
+            return self.working_file(path).await;
+        };
+
+        let repo = self.open()?;
+
+        let blob = if revision.is_empty() {
+            let Some(entry) = repo.index()?.get_path(Path::new(path), 0) else {
+                return Ok(None);
+            };
+
+            repo.find_blob(entry.id)?
+        } else {
+            match repo.revparse_single(&format!("{}:{}", revision, path)) {
+                Ok(object) => object.peel_to_blob()?,
+                Err(_) => return Ok(None),
+            }
+        };
+
+        Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+    }
+
+    async fn branch(&self) -> Res<Option<String>> {
+        let repo = self.open()?;
+
+        // An unborn `HEAD` (i.e., before the first commit) still points at a branch.
+        let branch = match repo.head() {
+            Ok(head) if head.is_branch() => head.shorthand().ok().map(|b| b.to_string()),
+            Ok(_) => None,
+            Err(_) => repo.find_reference("HEAD")?.symbolic_target().ok().flatten().and_then(|t| t.strip_prefix("refs/heads/")).map(|b| b.to_string()),
+        };
+
+        Ok(branch)
+    }
+
+    async fn root(&self) -> Res<PathBuf> {
+        let repo = self.open()?;
+        let workdir = repo.workdir().ok_or_else(|| anyhow::Error::msg("The repository is bare, so it has no working tree."))?;
+
+        Ok(workdir.to_path_buf())
+    }
+
+    async fn commit(&self, message: &str) -> Void {
+        // Prefer the `git` binary, which runs the hooks (and signs the commit, if configured).
+        let git = Git::new(&self.path.to_string_lossy());
+
+        if git.is_present().await.unwrap_or(false) {
+            return git.commit(message).await;
+        }
+
+        let repo = self.open()?;
+        let signature = repo.signature().map_err(|e| anyhow::Error::msg(format!("Unable to determine the commit author (set `user.name` and `user.email`): {}", e.message())))?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let parent = head_commit(&repo)?;
+
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parent.iter().collect::<Vec<_>>())?;
+
+        Ok(())
+    }
+}
+
+impl LibGit {
+    pub fn new(path: &str) -> Self {
+        Self { path: PathBuf::from(path) }
+    }
+
+    /// Whether a repository can be opened at (or above) the path.
+    pub fn can_open(&self) -> bool {
+        self.open().is_ok()
+    }
+
+    fn open(&self) -> Res<Repository> {
+        let repo = Repository::discover(&self.path)?;
+
+        // Git hooks (e.g., `pre-commit` during `git commit -a`) point at a temporary index, which `libgit2` ignores.
+        if let Some(path) = std::env::var_os("GIT_INDEX_FILE") {
+            let mut index = Index::open(Path::new(&path))?;
+            repo.set_index(&mut index)?;
+        }
+
+        Ok(repo)
+    }
+}
+
+// Helpers.
+
+fn commit_info(repo: &Repository, oid: git2::Oid) -> Res<CommitInfo> {
+    let commit = repo.find_commit(oid)?;
+
+    Ok(CommitInfo {
+        sha: commit.id().to_string(),
+        short_sha: commit.as_object().short_id()?.as_str()?.to_string(),
+        subject: commit.summary()?.unwrap_or_default().to_string(),
+        date: format_date(commit.time().seconds() + i64::from(commit.time().offset_minutes()) * 60),
+        message: commit.message()?.trim().to_string(),
+    })
+}
+
+/// The tree of `HEAD` (or `None` if `HEAD` is unborn).
+fn head_tree(repo: &Repository) -> Res<Option<Tree<'_>>> {
+    Ok(head_commit(repo)?.map(|c| c.tree()).transpose()?)
+}
+
+/// The commit of `HEAD` (or `None` if `HEAD` is unborn).
+fn head_commit(repo: &Repository) -> Res<Option<Commit<'_>>> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_commit()?)),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::{IndexAddOption, Oid, Signature};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// A repository with a root commit, a second commit (with an edit, a delete, an add, and a rename), staged and
+    /// unstaged changes, and untracked (and ignored) files.
+    struct Fixture {
+        dir: TempDir,
+        root: Oid,
+        second: Oid,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let repo = Repository::init(dir.path()).unwrap();
+
+            let write = |path: &str, text: &str| {
+                let path = dir.path().join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, text).unwrap();
+            };
+
+            write(".gitignore", "*.log\n");
+            write("a.txt", "one\ntwo\nthree\n");
+            write("b.txt", "to be deleted\n");
+            write("src/lib.rs", &(1..=20).map(|n| format!("fn f{}() {{}}\n", n)).collect::<String>());
+            let root = commit(&repo, None, "Root");
+
+            write("a.txt", "one\n2\nthree\nfour\n");
+            fs::remove_file(dir.path().join("b.txt")).unwrap();
+            write("d.txt", "new\n");
+            fs::rename(dir.path().join("src/lib.rs"), dir.path().join("src/main.rs")).unwrap();
+            let second = commit(&repo, Some(root), "Second\n\nWith a body.");
+
+            write("d.txt", "new\nstaged\n");
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("d.txt")).unwrap();
+            index.write().unwrap();
+
+            write("a.txt", "one\n2\nthree\nfour\nunstaged\n");
+            write("untracked.txt", "untracked\n");
+            write("nested/untracked.rs", "fn untracked() {}\n");
+            write("ignored.log", "ignored\n");
+
+            Self { dir, root, second }
+        }
+
+        fn path(&self) -> &str {
+            self.dir.path().to_str().unwrap()
+        }
+    }
+
+    fn commit(repo: &Repository, parent: Option<Oid>, message: &str) -> Oid {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new("Test", "test@example.com", &git2::Time::new(1_700_000_000, 120)).unwrap();
+        let parents = parent.map(|p| repo.find_commit(p).unwrap()).into_iter().collect::<Vec<_>>();
+
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents.iter().collect::<Vec<_>>()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn matches_the_git_binary() {
+        let fixture = Fixture::new();
+        let (lib, git) = (LibGit::new(fixture.path()), Git::new(fixture.path()));
+
+        let targets = [
+            DiffTarget::WorkingTree,
+            DiffTarget::Staged,
+            DiffTarget::Commit(fixture.second.to_string()),
+            DiffTarget::Commit(fixture.root.to_string()),
+            DiffTarget::Range(format!("{}..{}", fixture.root, fixture.second)),
+            DiffTarget::MergeBase(fixture.root.to_string()),
+        ];
+
+        for target in &targets {
+            let expected = git.diff(target).await.unwrap();
+
+            assert!(!expected.is_empty(), "{}", target);
+            assert_eq!(lib.diff(target).await.unwrap(), expected, "{}", target);
+        }
+
+        // Both detect the rename (rather than a delete and an add).
+        let second = lib.diff(&targets[2]).await.unwrap();
+        assert!(second.files.iter().any(|f| f.is_rename && f.path() == "src/main.rs"));
+
+        let (expected, _) = git.untracked_diff(u64::MAX).await.unwrap();
+        let (actual, _) = lib.untracked_diff(u64::MAX).await.unwrap();
+
+        assert_eq!(expected.files.iter().map(|f| f.path()).collect::<Vec<_>>(), vec!["nested/untracked.rs", "untracked.txt"]);
+        assert_eq!(actual, expected);
+
+        // A branch that diverged from `HEAD` at the root commit.
+        let repo = Repository::open(fixture.path()).unwrap();
+        let root = repo.find_commit(fixture.root).unwrap();
+        let signature = Signature::new("Test", "test@example.com", &git2::Time::new(1_700_000_000, 120)).unwrap();
+        repo.commit(Some("refs/heads/side"), &signature, &signature, "Side", &root.tree().unwrap(), &[&root]).unwrap();
+
+        let ranges = [
+            "HEAD".to_string(),
+            format!("{}^!", fixture.root),
+            format!("{}..HEAD", fixture.root),
+            "side...HEAD".to_string(),
+            "HEAD...side".to_string(),
+            "side...".to_string(),
+        ];
+
+        for range in ranges {
+            assert_eq!(lib.commits(&range).await.unwrap(), git.commits(&range).await.unwrap(), "{}", range);
+        }
+
+        // A three-dot range lists only the commits since the merge-base.
+        let subjects = |commits: Vec<CommitInfo>| commits.into_iter().map(|c| c.subject).collect::<Vec<_>>();
+        assert_eq!(subjects(git.commits("side...HEAD").await.unwrap()), vec!["Second"]);
+        assert_eq!(subjects(git.commits("HEAD...side").await.unwrap()), vec!["Side"]);
+    }
+
+    #[tokio::test]
+    async fn diffs_the_root_commit_against_the_empty_tree() {
+        let fixture = Fixture::new();
+        let diff = LibGit::new(fixture.path()).diff(&DiffTarget::Commit(fixture.root.to_string())).await.unwrap();
+
+        assert_eq!(diff.files.iter().map(|f| f.path()).collect::<Vec<_>>(), vec![".gitignore", "a.txt", "b.txt", "src/lib.rs"]);
+        assert!(diff.files.iter().all(|f| f.is_new));
+    }
+}
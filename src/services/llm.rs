@@ -0,0 +1,407 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::base::types::{HasName, IsEnsurable, Res, Void, estimate_tokens};
+
+use super::{openai::OpenAi, anthropic::Anthropic, ollama::Ollama};
+
+// Types.
+
+/// Which API serves the model.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Provider {
+    /// An OpenAI-compatible chat completions API (e.g., OpenAI, or the local Cria server).
+    #[default]
+    OpenAi,
+    /// Anthropic's Messages API.
+    Anthropic,
+    /// Ollama's native API.
+    Ollama,
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::OpenAi => write!(f, "OpenAI"),
+            Provider::Anthropic => write!(f, "Anthropic"),
+            Provider::Ollama => write!(f, "Ollama"),
+        }
+    }
+}
+
+/// Who wrote a message of a chat.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+/// A single message of a chat.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: Role::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: Role::User, content: content.into() }
+    }
+}
+
+/// The settings of the model for a command (where unset settings fall back to the provider's defaults).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelSettings {
+    /// The model to use (e.g., `gpt-4`).
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// The maximum number of tokens to generate.
+    pub max_tokens: Option<u32>,
+    /// The seed for (best-effort) deterministic sampling.
+    pub seed: Option<u64>,
+    /// The sequences at which the model stops generating.
+    pub stop: Option<Vec<String>>,
+    /// How long to wait for a response.
+    pub timeout_secs: Option<u64>,
+}
+
+impl ModelSettings {
+    /// Fills in the unset settings from the `fallback`.
+    pub fn or(self, fallback: &ModelSettings) -> Self {
+        Self {
+            model: self.model.or_else(|| fallback.model.clone()),
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+            seed: self.seed.or(fallback.seed),
+            stop: self.stop.or_else(|| fallback.stop.clone()),
+            timeout_secs: self.timeout_secs.or(fallback.timeout_secs),
+        }
+    }
+}
+
+/// A request for a chat completion.
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    /// The maximum number of tokens to generate (or the provider's default).
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub seed: Option<u64>,
+    pub stop: Vec<String>,
+    pub timeout: Duration,
+}
+
+// Traits.
+
+/// An API that serves chat completions.
+pub trait LlmProvider {
+    /// Sends the request, and returns the whole response.
+    async fn chat(&self, request: &ChatRequest) -> Res<String>;
+
+    /// Sends the request, calling `on_delta` with each piece of the response as it arrives, and returns the whole response.
+    async fn stream(&self, request: &ChatRequest, on_delta: &mut dyn FnMut(&str)) -> Res<String>;
+
+    /// Lists the models that the provider serves.
+    async fn models(&self) -> Res<Vec<String>>;
+
+    /// Counts the tokens of the request's messages (estimated, unless the API can count them).
+    async fn count_tokens(&self, request: &ChatRequest) -> Res<usize> {
+        Ok(request.messages.iter().map(|m| estimate_tokens(&m.content)).sum())
+    }
+}
+
+// Helpers.
+
+/// Builds an HTTP client with the specified timeout.
+pub fn client(timeout: Duration) -> Res<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(timeout).build()?)
+}
+
+/// Returns the response if it succeeded, or an error with its status and body otherwise.
+pub async fn check(response: reqwest::Response) -> Res<reqwest::Response> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+
+    Err(anyhow::Error::msg(format!("The model API returned `{}`: {}", status, body.trim())))
+}
+
+/// Calls `on_line` with each line of the (streamed) response body until it returns `false`, or the body ends.
+pub async fn read_lines(mut response: reqwest::Response, mut on_line: impl FnMut(&str) -> Res<bool>) -> Void {
+    let mut buffer = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+
+        // Lines are split on bytes, so that multi-byte characters split across chunks are kept intact.
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<_>>();
+
+            if !on_line(String::from_utf8_lossy(&line).trim_end())? {
+                return Ok(());
+            }
+        }
+    }
+
+    if !buffer.is_empty() {
+        on_line(String::from_utf8_lossy(&buffer).trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// The data of a server-sent event line (e.g., `data: {...}`), if it is one.
+pub fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim)
+}
+
+// Dispatch.
+
+/// The [`LlmProvider`] selected by the configuration.
+pub enum AnyProvider {
+    OpenAi(OpenAi),
+    Anthropic(Anthropic),
+    Ollama(Ollama),
+}
+
+impl AnyProvider {
+    pub fn new(provider: Provider, url: &str, key: &Option<String>) -> Self {
+        match provider {
+            Provider::OpenAi => AnyProvider::OpenAi(OpenAi::new(url, key)),
+            Provider::Anthropic => AnyProvider::Anthropic(Anthropic::new(url, key)),
+            Provider::Ollama => AnyProvider::Ollama(Ollama::new(url)),
+        }
+    }
+}
+
+impl HasName for AnyProvider {
+    fn name(&self) -> &'static str {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.name(),
+            AnyProvider::Anthropic(anthropic) => anthropic.name(),
+            AnyProvider::Ollama(ollama) => ollama.name(),
+        }
+    }
+}
+
+impl IsEnsurable for AnyProvider {
+    async fn is_present(&self) -> Res<bool> {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.is_present().await,
+            AnyProvider::Anthropic(anthropic) => anthropic.is_present().await,
+            AnyProvider::Ollama(ollama) => ollama.is_present().await,
+        }
+    }
+
+    async fn make_present(&self) -> Void {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.make_present().await,
+            AnyProvider::Anthropic(anthropic) => anthropic.make_present().await,
+            AnyProvider::Ollama(ollama) => ollama.make_present().await,
+        }
+    }
+}
+
+impl LlmProvider for AnyProvider {
+    async fn chat(&self, request: &ChatRequest) -> Res<String> {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.chat(request).await,
+            AnyProvider::Anthropic(anthropic) => anthropic.chat(request).await,
+            AnyProvider::Ollama(ollama) => ollama.chat(request).await,
+        }
+    }
+
+    async fn stream(&self, request: &ChatRequest, on_delta: &mut dyn FnMut(&str)) -> Res<String> {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.stream(request, on_delta).await,
+            AnyProvider::Anthropic(anthropic) => anthropic.stream(request, on_delta).await,
+            AnyProvider::Ollama(ollama) => ollama.stream(request, on_delta).await,
+        }
+    }
+
+    async fn models(&self) -> Res<Vec<String>> {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.models().await,
+            AnyProvider::Anthropic(anthropic) => anthropic.models().await,
+            AnyProvider::Ollama(ollama) => ollama.models().await,
+        }
+    }
+
+    async fn count_tokens(&self, request: &ChatRequest) -> Res<usize> {
+        match self {
+            AnyProvider::OpenAi(openai) => openai.count_tokens(request).await,
+            AnyProvider::Anthropic(anthropic) => anthropic.count_tokens(request).await,
+            AnyProvider::Ollama(ollama) => ollama.count_tokens(request).await,
+        }
+    }
+}
+
+// Tests.
+
+/// A mock HTTP server for the provider tests.
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::time::Duration;
+
+    use serde_json::Value;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener, task::JoinHandle};
+
+    use super::{ChatMessage, ChatRequest};
+
+    /// A request received by the mock server.
+    pub struct Received {
+        /// The request line (e.g., `POST /v1/chat/completions HTTP/1.1`).
+        pub line: String,
+        /// The headers (with lowercase names).
+        pub headers: Vec<(String, String)>,
+        /// The JSON body (or `null`).
+        pub body: Value,
+    }
+
+    impl Received {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Serves a single request on a local port, responding with the status and the body; the body is sent in the
+    /// specified chunks (with a pause after each), so that the client reads them separately.
+    ///
+    /// Returns the URL of the server, and the request that it receives.
+    pub async fn serve(status: u16, chunks: &[&[u8]]) -> (String, JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let chunks = chunks.iter().map(|c| c.to_vec()).collect::<Vec<_>>();
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+
+            let head_end = loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+
+                if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break end;
+                }
+            };
+
+            let head = String::from_utf8(request[..head_end].to_vec()).unwrap();
+            let mut lines = head.split("\r\n");
+            let line = lines.next().unwrap().to_string();
+            let headers = lines.filter_map(|l| l.split_once(':')).map(|(n, v)| (n.trim().to_lowercase(), v.trim().to_string())).collect::<Vec<_>>();
+
+            let length = headers.iter().find(|(n, _)| n == "content-length").map_or(0, |(_, v)| v.parse::<usize>().unwrap());
+
+            while request.len() < head_end + 4 + length {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+
+            let body = serde_json::from_slice(&request[head_end + 4..]).unwrap_or(Value::Null);
+
+            let head = format!("HTTP/1.1 {} Mock\r\ncontent-type: application/json\r\ntransfer-encoding: chunked\r\nconnection: close\r\n\r\n", status);
+            socket.write_all(head.as_bytes()).await.unwrap();
+
+            for chunk in chunks {
+                socket.write_all(format!("{:x}\r\n", chunk.len()).as_bytes()).await.unwrap();
+                socket.write_all(&chunk).await.unwrap();
+                socket.write_all(b"\r\n").await.unwrap();
+                socket.flush().await.unwrap();
+
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+
+            socket.write_all(b"0\r\n\r\n").await.unwrap();
+
+            Received { line, headers, body }
+        });
+
+        (url, handle)
+    }
+
+    /// A request with a system and a user message.
+    pub fn request() -> ChatRequest {
+        ChatRequest {
+            model: "model".to_string(),
+            messages: vec![ChatMessage::system("Be brief."), ChatMessage::user("Hi")],
+            max_tokens: Some(10),
+            temperature: Some(0.5),
+            top_p: None,
+            seed: None,
+            stop: vec!["END".to_string()],
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{testing::serve, *};
+
+    #[tokio::test]
+    async fn reads_lines_split_across_chunks() {
+        // The `é` (`\xc3\xa9`) is split across two chunks, and so is the second line.
+        let (url, _) = serve(200, &[b"caf\xc3", b"\xa9\nsec", b"ond\n", b"last"]).await;
+        let mut lines = Vec::new();
+
+        read_lines(reqwest::get(&url).await.unwrap(), |line| {
+            lines.push(line.to_string());
+            Ok(true)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(lines, vec!["café", "second", "last"]);
+    }
+
+    #[tokio::test]
+    async fn stops_reading_lines_when_asked() {
+        let (url, _) = serve(200, &[b"one\ntwo\n", b"three\n"]).await;
+        let mut lines = Vec::new();
+
+        read_lines(reqwest::get(&url).await.unwrap(), |line| {
+            lines.push(line.to_string());
+            Ok(line != "two")
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn checks_for_error_statuses() {
+        let (url, _) = serve(429, &[b"{\"error\": \"rate limited\"}\n"]).await;
+        let err = check(reqwest::get(&url).await.unwrap()).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "The model API returned `429 Too Many Requests`: {\"error\": \"rate limited\"}");
+
+        let (url, _) = serve(200, &[b"ok"]).await;
+        assert_eq!(check(reqwest::get(&url).await.unwrap()).await.unwrap().text().await.unwrap(), "ok");
+    }
+
+    #[test]
+    fn parses_sse_data() {
+        assert_eq!(sse_data("data: {\"a\": 1}"), Some("{\"a\": 1}"));
+        assert_eq!(sse_data("data:[DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data("event: message_start"), None);
+    }
+}
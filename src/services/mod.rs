@@ -1,5 +1,14 @@
+pub mod vcs;
 pub mod git;
+pub mod libgit;
+pub mod hg;
+pub mod jj;
 pub mod gpt;
+pub mod llm;
+pub mod openai;
+pub mod anthropic;
+pub mod ollama;
 pub mod docker;
 pub mod model;
-pub mod cria;
\ No newline at end of file
+pub mod cria;
+pub mod hook;
\ No newline at end of file
@@ -20,7 +20,7 @@ impl IsEnsurable for Model {
     async fn is_present(&self) -> Res<bool> {
         let path = self.resolve_path()?;
 
-        Ok(std::fs::try_exists(path)?)
+        Ok(std::fs::exists(path)?)
     }
 
     async fn make_present(&self) -> Res<()> {
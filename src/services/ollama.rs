@@ -0,0 +1,207 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::base::types::{HasName, IsEnsurable, Res, Void};
+
+use super::llm::{self, ChatRequest, LlmProvider};
+
+static NAME: &str = "ollama_api";
+
+/// Ollama's native API.
+pub struct Ollama {
+    url: String,
+}
+
+impl HasName for Ollama {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for Ollama {
+    async fn is_present(&self) -> Res<bool> {
+        Ok(true)
+    }
+
+    async fn make_present(&self) -> Void {
+        Err(anyhow::Error::msg("Cannot perform `make_present`: this should not happen."))
+    }
+}
+
+impl LlmProvider for Ollama {
+    async fn chat(&self, request: &ChatRequest) -> Res<String> {
+        let response: ChatResponse = self.post(request, false).await?.json().await?;
+
+        Ok(response.message.map(|m| m.content).unwrap_or_default())
+    }
+
+    async fn stream(&self, request: &ChatRequest, on_delta: &mut dyn FnMut(&str)) -> Res<String> {
+        let response = self.post(request, true).await?;
+        let mut text = String::new();
+
+        // The native API streams newline-delimited JSON objects (rather than server-sent events).
+        llm::read_lines(response, |line| {
+            if line.trim().is_empty() {
+                return Ok(true);
+            }
+
+            let chunk: ChatResponse = serde_json::from_str(line)?;
+
+            if let Some(error) = chunk.error {
+                return Err(anyhow::Error::msg(format!("The model API returned an error: {}", error)));
+            }
+
+            if let Some(message) = chunk.message.filter(|m| !m.content.is_empty()) {
+                on_delta(&message.content);
+                text.push_str(&message.content);
+            }
+
+            Ok(!chunk.done)
+        })
+        .await?;
+
+        Ok(text)
+    }
+
+    async fn models(&self) -> Res<Vec<String>> {
+        let response = reqwest::Client::new().get(format!("{}/api/tags", self.url)).send().await?;
+        let tags: Tags = llm::check(response).await?.json().await?;
+
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+}
+
+impl Ollama {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn post(&self, request: &ChatRequest, stream: bool) -> Res<reqwest::Response> {
+        let mut body = json!({
+            "model": request.model,
+            "messages": request.messages,
+            "stream": stream,
+        });
+
+        // The generation options go in a separate object (with Ollama's names).
+        let mut options = serde_json::Map::new();
+
+        if let Some(max_tokens) = request.max_tokens {
+            options.insert("num_predict".to_string(), json!(max_tokens));
+        }
+
+        if let Some(temperature) = request.temperature {
+            options.insert("temperature".to_string(), json!(temperature));
+        }
+
+        if let Some(top_p) = request.top_p {
+            options.insert("top_p".to_string(), json!(top_p));
+        }
+
+        if let Some(seed) = request.seed {
+            options.insert("seed".to_string(), json!(seed));
+        }
+
+        if !request.stop.is_empty() {
+            options.insert("stop".to_string(), json!(request.stop));
+        }
+
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+
+        let response = llm::client(request.timeout)?.post(format!("{}/api/chat", self.url)).json(&body).send().await?;
+
+        llm::check(response).await
+    }
+}
+
+// Types.
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: Option<Message>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct Tags {
+    models: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct Model {
+    name: String,
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{*, super::llm::testing::{request, serve}};
+
+    #[tokio::test]
+    async fn chats() {
+        let (url, received) = serve(200, &[br#"{"message": {"role": "assistant", "content": "Hello!"}, "done": true}"#]).await;
+
+        assert_eq!(Ollama::new(&url).chat(&request()).await.unwrap(), "Hello!");
+
+        // The generation options are sent with Ollama's names.
+        let received = received.await.unwrap();
+        assert_eq!(received.line, "POST /api/chat HTTP/1.1");
+        assert_eq!(received.body, json!({
+            "model": "model",
+            "messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hi"}],
+            "stream": false,
+            "options": {"num_predict": 10, "temperature": 0.5, "stop": ["END"]},
+        }));
+    }
+
+    #[tokio::test]
+    async fn streams_newline_delimited_json() {
+        // The `é` of the second message is split across chunks, and nothing after `done` is read.
+        let (url, _) = serve(200, &[
+            b"{\"message\": {\"role\": \"assistant\", \"content\": \"Caf\"}, \"done\": false}\n{\"message\": {\"role\": \"assistant\", \"content\": \"\xc3",
+            b"\xa9!\"}, \"done\": false}\n",
+            b"{\"message\": {\"role\": \"assistant\", \"content\": \"\"}, \"done\": true}\n{\"message\": {\"role\": \"assistant\", \"content\": \"ignored\"}}\n",
+        ]).await;
+
+        let mut deltas = Vec::new();
+        let text = Ollama::new(&url).stream(&request(), &mut |delta| deltas.push(delta.to_string())).await.unwrap();
+
+        assert_eq!(text, "Café!");
+        assert_eq!(deltas, vec!["Caf", "é!"]);
+    }
+
+    #[tokio::test]
+    async fn returns_errors() {
+        let (url, _) = serve(404, &[br#"{"error": "model 'model' not found"}"#]).await;
+        let err = Ollama::new(&url).chat(&request()).await.unwrap_err();
+
+        assert_eq!(err.to_string(), r#"The model API returned `404 Not Found`: {"error": "model 'model' not found"}"#);
+
+        let (url, _) = serve(200, &[b"{\"error\": \"out of memory\"}\n"]).await;
+        let err = Ollama::new(&url).stream(&request(), &mut |_| {}).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "The model API returned an error: out of memory");
+    }
+
+    #[tokio::test]
+    async fn lists_models() {
+        let (url, received) = serve(200, &[br#"{"models": [{"name": "llama3:8b"}, {"name": "qwen2.5-coder:7b"}]}"#]).await;
+
+        assert_eq!(Ollama::new(&url).models().await.unwrap(), vec!["llama3:8b", "qwen2.5-coder:7b"]);
+        assert_eq!(received.await.unwrap().line, "GET /api/tags HTTP/1.1");
+    }
+}
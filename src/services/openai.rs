@@ -0,0 +1,230 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::base::types::{HasName, IsEnsurable, Res, Void};
+
+use super::llm::{self, ChatRequest, LlmProvider};
+
+static NAME: &str = "openai_api";
+
+/// An OpenAI-compatible chat completions API (e.g., OpenAI, or the local Cria server).
+pub struct OpenAi {
+    url: String,
+    key: Option<String>,
+}
+
+impl HasName for OpenAi {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+}
+
+impl IsEnsurable for OpenAi {
+    async fn is_present(&self) -> Res<bool> {
+        // Only OpenAI itself needs a key (local and self-hosted servers usually do not).
+        if self.key.is_none() && self.url.contains("api.openai.com") {
+            return Err(anyhow::Error::msg("OpenAI key not provided.  Please set the `openai_key` config value, or use a local mode."));
+        }
+
+        Ok(true)
+    }
+
+    async fn make_present(&self) -> Void {
+        Err(anyhow::Error::msg("Cannot perform `make_present`: this should not happen."))
+    }
+}
+
+impl LlmProvider for OpenAi {
+    async fn chat(&self, request: &ChatRequest) -> Res<String> {
+        let response = self.post(request, false).await?;
+        let completion: Completion = response.json().await?;
+
+        let choice = completion.choices.into_iter().next().ok_or_else(|| anyhow::Error::msg("The model returned no choices."))?;
+
+        Ok(choice.message.content.unwrap_or_default())
+    }
+
+    async fn stream(&self, request: &ChatRequest, on_delta: &mut dyn FnMut(&str)) -> Res<String> {
+        let response = self.post(request, true).await?;
+        let mut text = String::new();
+
+        llm::read_lines(response, |line| {
+            let Some(data) = llm::sse_data(line) else {
+                return Ok(true);
+            };
+
+            if data == "[DONE]" {
+                return Ok(false);
+            }
+
+            let chunk: CompletionChunk = serde_json::from_str(data)?;
+
+            if let Some(delta) = chunk.choices.into_iter().next().and_then(|c| c.delta.content) {
+                on_delta(&delta);
+                text.push_str(&delta);
+            }
+
+            Ok(true)
+        })
+        .await?;
+
+        Ok(text)
+    }
+
+    async fn models(&self) -> Res<Vec<String>> {
+        let mut builder = reqwest::Client::new().get(format!("{}/v1/models", self.url));
+
+        if let Some(key) = &self.key {
+            builder = builder.bearer_auth(key);
+        }
+
+        let models: Models = llm::check(builder.send().await?).await?.json().await?;
+
+        Ok(models.data.into_iter().map(|m| m.id).collect())
+    }
+}
+
+impl OpenAi {
+    pub fn new(url: &str, key: &Option<String>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            key: key.clone(),
+        }
+    }
+
+    async fn post(&self, request: &ChatRequest, stream: bool) -> Res<reqwest::Response> {
+        let mut body = json!({
+            "model": request.model,
+            "messages": request.messages,
+            "stream": stream,
+        });
+
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+
+        if let Some(seed) = request.seed {
+            body["seed"] = json!(seed);
+        }
+
+        if !request.stop.is_empty() {
+            body["stop"] = json!(request.stop);
+        }
+
+        let mut builder = llm::client(request.timeout)?.post(format!("{}/v1/chat/completions", self.url)).json(&body);
+
+        if let Some(key) = &self.key {
+            builder = builder.bearer_auth(key);
+        }
+
+        llm::check(builder.send().await?).await
+    }
+}
+
+// Types.
+
+#[derive(Deserialize)]
+struct Completion {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize)]
+struct CompletionChunk {
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: Message,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Models {
+    data: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct Model {
+    id: String,
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{*, super::llm::testing::{request, serve}};
+
+    #[tokio::test]
+    async fn chats() {
+        let (url, received) = serve(200, &[br#"{"choices": [{"message": {"role": "assistant", "content": "Hello!"}}]}"#]).await;
+        let openai = OpenAi::new(&format!("{}/", url), &Some("key".to_string()));
+
+        assert_eq!(openai.chat(&request()).await.unwrap(), "Hello!");
+
+        let received = received.await.unwrap();
+        assert_eq!(received.line, "POST /v1/chat/completions HTTP/1.1");
+        assert_eq!(received.header("authorization"), Some("Bearer key"));
+        assert_eq!(received.body, json!({
+            "model": "model",
+            "messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hi"}],
+            "stream": false,
+            "max_tokens": 10,
+            "temperature": 0.5,
+            "stop": ["END"],
+        }));
+    }
+
+    #[tokio::test]
+    async fn streams_server_sent_events() {
+        // The `é` of the second delta is split across chunks, and nothing after `[DONE]` is read.
+        let (url, received) = serve(200, &[
+            b"data: {\"choices\": [{\"delta\": {\"role\": \"assistant\"}}]}\n\n",
+            b"data: {\"choices\": [{\"delta\": {\"content\": \"Caf\"}}]}\n\ndata: {\"choices\": [{\"delta\": {\"content\": \"\xc3",
+            b"\xa9!\"}}]}\n\n",
+            b"data: [DONE]\n\ndata: {\"choices\": [{\"delta\": {\"content\": \"ignored\"}}]}\n\n",
+        ]).await;
+
+        let mut deltas = Vec::new();
+        let text = OpenAi::new(&url, &None).stream(&request(), &mut |delta| deltas.push(delta.to_string())).await.unwrap();
+
+        assert_eq!(text, "Café!");
+        assert_eq!(deltas, vec!["Caf", "é!"]);
+        assert_eq!(received.await.unwrap().body["stream"], json!(true));
+    }
+
+    #[tokio::test]
+    async fn returns_error_bodies() {
+        let (url, _) = serve(401, &[br#"{"error": {"message": "Invalid API key."}}"#]).await;
+        let err = OpenAi::new(&url, &Some("bad".to_string())).chat(&request()).await.unwrap_err();
+
+        assert_eq!(err.to_string(), r#"The model API returned `401 Unauthorized`: {"error": {"message": "Invalid API key."}}"#);
+    }
+
+    #[tokio::test]
+    async fn lists_models() {
+        let (url, received) = serve(200, &[br#"{"object": "list", "data": [{"id": "gpt-4o"}, {"id": "gpt-4o-mini"}]}"#]).await;
+
+        assert_eq!(OpenAi::new(&url, &None).models().await.unwrap(), vec!["gpt-4o", "gpt-4o-mini"]);
+        assert_eq!(received.await.unwrap().line, "GET /v1/models HTTP/1.1");
+    }
+}
@@ -0,0 +1,358 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::base::{types::{HasName, IsEnsurable, Res, Void, DiffTarget, CommitInfo}, diff::{Diff, FileDiff}, filter::{self, PathFilter, SkippedFile, SkipReason}};
+
+use super::{git::Git, libgit::LibGit, hg::Hg, jj::Jj};
+
+// Types.
+
+/// The options that control which files make it into a review diff.
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Whether to skip binary files, lockfiles, generated files, and minified files.
+    pub skip_generated: bool,
+    /// Whether to include untracked (and not ignored) files when reviewing the working tree.
+    pub include_untracked: bool,
+    /// The maximum size of an untracked file to include.
+    pub max_untracked_bytes: u64,
+}
+
+/// Which implementation is used to read the repository.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum VcsBackend {
+    /// The in-process implementation, falling back to the `git` binary if the repository cannot be opened.
+    #[default]
+    Auto,
+    /// The `git` binary.
+    Cli,
+    /// The in-process implementation (`libgit2`).
+    Lib,
+}
+
+// Traits.
+
+/// A version control system that can produce the diffs, commits, and file contents to review.
+pub trait Vcs {
+    /// Gets the diff of the target.
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff>;
+
+    /// Gets the paths (relative to the [`Vcs::root`]) of the untracked files that are not ignored.
+    async fn untracked_files(&self) -> Res<Vec<String>>;
+
+    /// Lists the commits in the specified range, oldest first.
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>>;
+
+    /// Gets the contents of the specified file on the "new" side of the target (or `None` if it does not exist).
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>>;
+
+    /// Gets the name of the current branch (or `None` if there is none, e.g., on a detached `HEAD`).
+    async fn branch(&self) -> Res<Option<String>>;
+
+    /// Gets the root directory of the working copy (which the paths in diffs are relative to).
+    async fn root(&self) -> Res<PathBuf>;
+
+    /// Records the changes of the [`Vcs::commit_target`] as a new commit with the specified message.
+    async fn commit(&self, message: &str) -> Void;
+
+    /// The changes that [`Vcs::commit`] records: the staged changes, for a VCS with a staging area.
+    fn commit_target(&self) -> DiffTarget {
+        DiffTarget::Staged
+    }
+
+    /// Reads the working copy version of the specified file (or `None` if it does not exist).
+    async fn working_file(&self, path: &str) -> Res<Option<String>> {
+        let root = self.root().await?;
+
+        Ok(std::fs::read(root.join(path)).ok().map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    /// Synthesizes "new file" diffs for the untracked files, skipping those larger than `max_bytes` (or unreadable).
+    async fn untracked_diff(&self, max_bytes: u64) -> Res<(Diff, Vec<SkippedFile>)> {
+        let root = self.root().await?;
+
+        let mut diff = Diff::default();
+        let mut skipped = Vec::new();
+
+        for path in self.untracked_files().await? {
+            let full_path = root.join(&path);
+
+            let metadata = match std::fs::metadata(&full_path) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    skipped.push(SkippedFile { path, reason: SkipReason::Unreadable(err.to_string()) });
+                    continue;
+                },
+            };
+
+            if !metadata.is_file() {
+                continue;
+            }
+
+            if metadata.len() > max_bytes {
+                skipped.push(SkippedFile { path, reason: SkipReason::TooLarge });
+                continue;
+            }
+
+            match std::fs::read(&full_path) {
+                Ok(contents) => diff.files.push(FileDiff::new_file(&path, &contents)),
+                Err(err) => skipped.push(SkippedFile { path, reason: SkipReason::Unreadable(err.to_string()) }),
+            }
+        }
+
+        Ok((diff, skipped))
+    }
+
+    /// Gets the diff of the target, and removes the files that should not be reviewed (along with the reason).
+    async fn review_diff(&self, target: &DiffTarget, filter: &PathFilter, options: &DiffOptions) -> Res<(Diff, Vec<SkippedFile>)> {
+        let mut diff = self.diff(target).await?;
+        let mut skipped = Vec::new();
+
+        if options.include_untracked && *target == DiffTarget::WorkingTree {
+            let (untracked, too_large) = self.untracked_diff(options.max_untracked_bytes).await?;
+
+            diff.files.extend(untracked.files);
+            skipped.extend(too_large);
+        }
+
+        let (diff, filtered) = prepare(diff, filter, options);
+        skipped.extend(filtered);
+
+        Ok((diff, skipped))
+    }
+}
+
+// Helpers.
+
+/// Removes the files that should not be reviewed from an existing diff (e.g., one read from a patch file).
+pub fn prepare(diff: Diff, filter: &PathFilter, options: &DiffOptions) -> (Diff, Vec<SkippedFile>) {
+    let (diff, mut skipped) = filter.apply(diff);
+
+    if !options.skip_generated {
+        return (diff, skipped);
+    }
+
+    let (diff, generated) = filter::skip_generated(diff);
+    skipped.extend(generated);
+
+    (diff, skipped)
+}
+
+/// Parses the commits printed by a `log` command as records separated by `\x1e`, with the sha, short sha, subject,
+/// date, and message separated by `\x1f`.
+pub(crate) fn parse_commits(stdout: &str) -> Vec<CommitInfo> {
+    stdout
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(5, '\x1f');
+
+            Some(CommitInfo {
+                sha: fields.next().filter(|s| !s.is_empty())?.to_string(),
+                short_sha: fields.next()?.to_string(),
+                subject: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                message: fields.next()?.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Runs a command that prints a file at a revision (e.g., `hg cat`), and returns the file (or `None` if the command
+/// fails, e.g., because the file does not exist at that revision).
+pub(crate) async fn file_at(program: &str, args: &[&str]) -> Res<Option<String>> {
+    let output = Command::new(program)
+        .args(args)
+        .output().await
+        .with_context(|| format!("Unable to run `{} {}`.", program, args.join(" ")))?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+// Dispatch.
+
+/// The [`Vcs`] in use for the current directory.
+pub enum AnyVcs {
+    Git(Git),
+    LibGit(LibGit),
+    Hg(Hg),
+    Jj(Jj),
+}
+
+impl AnyVcs {
+    /// Detects the [`Vcs`] of the current directory (from the closest `.jj`, `.hg`, or `.git` directory).
+    ///
+    /// The `backend` selects the git implementation.  Jujutsu is preferred over git for colocated repositories.
+    pub fn new(backend: VcsBackend) -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default();
+
+        for dir in cwd.ancestors() {
+            if dir.join(".jj").is_dir() {
+                return AnyVcs::Jj(Jj::default());
+            }
+
+            if dir.join(".hg").is_dir() {
+                return AnyVcs::Hg(Hg::default());
+            }
+
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+
+        match backend {
+            VcsBackend::Cli => AnyVcs::Git(Git::default()),
+            VcsBackend::Lib => AnyVcs::LibGit(LibGit::new(".")),
+            VcsBackend::Auto => {
+                let lib = LibGit::new(".");
+
+                if lib.can_open() {
+                    AnyVcs::LibGit(lib)
+                } else {
+                    AnyVcs::Git(Git::default())
+                }
+            },
+        }
+    }
+}
+
+impl HasName for AnyVcs {
+    fn name(&self) -> &'static str {
+        match self {
+            AnyVcs::Git(git) => git.name(),
+            AnyVcs::LibGit(lib) => lib.name(),
+            AnyVcs::Hg(hg) => hg.name(),
+            AnyVcs::Jj(jj) => jj.name(),
+        }
+    }
+}
+
+impl IsEnsurable for AnyVcs {
+    async fn is_present(&self) -> Res<bool> {
+        match self {
+            AnyVcs::Git(git) => git.is_present().await,
+            AnyVcs::LibGit(lib) => lib.is_present().await,
+            AnyVcs::Hg(hg) => hg.is_present().await,
+            AnyVcs::Jj(jj) => jj.is_present().await,
+        }
+    }
+
+    async fn make_present(&self) -> Void {
+        match self {
+            AnyVcs::Git(git) => git.make_present().await,
+            AnyVcs::LibGit(lib) => lib.make_present().await,
+            AnyVcs::Hg(hg) => hg.make_present().await,
+            AnyVcs::Jj(jj) => jj.make_present().await,
+        }
+    }
+}
+
+impl Vcs for AnyVcs {
+    async fn diff(&self, target: &DiffTarget) -> Res<Diff> {
+        match self {
+            AnyVcs::Git(git) => git.diff(target).await,
+            AnyVcs::LibGit(lib) => lib.diff(target).await,
+            AnyVcs::Hg(hg) => hg.diff(target).await,
+            AnyVcs::Jj(jj) => jj.diff(target).await,
+        }
+    }
+
+    async fn untracked_files(&self) -> Res<Vec<String>> {
+        match self {
+            AnyVcs::Git(git) => git.untracked_files().await,
+            AnyVcs::LibGit(lib) => lib.untracked_files().await,
+            AnyVcs::Hg(hg) => hg.untracked_files().await,
+            AnyVcs::Jj(jj) => jj.untracked_files().await,
+        }
+    }
+
+    async fn commits(&self, range: &str) -> Res<Vec<CommitInfo>> {
+        match self {
+            AnyVcs::Git(git) => git.commits(range).await,
+            AnyVcs::LibGit(lib) => lib.commits(range).await,
+            AnyVcs::Hg(hg) => hg.commits(range).await,
+            AnyVcs::Jj(jj) => jj.commits(range).await,
+        }
+    }
+
+    async fn file_contents(&self, target: &DiffTarget, path: &str) -> Res<Option<String>> {
+        match self {
+            AnyVcs::Git(git) => git.file_contents(target, path).await,
+            AnyVcs::LibGit(lib) => lib.file_contents(target, path).await,
+            AnyVcs::Hg(hg) => hg.file_contents(target, path).await,
+            AnyVcs::Jj(jj) => jj.file_contents(target, path).await,
+        }
+    }
+
+    async fn branch(&self) -> Res<Option<String>> {
+        match self {
+            AnyVcs::Git(git) => git.branch().await,
+            AnyVcs::LibGit(lib) => lib.branch().await,
+            AnyVcs::Hg(hg) => hg.branch().await,
+            AnyVcs::Jj(jj) => jj.branch().await,
+        }
+    }
+
+    async fn root(&self) -> Res<PathBuf> {
+        match self {
+            AnyVcs::Git(git) => git.root().await,
+            AnyVcs::LibGit(lib) => lib.root().await,
+            AnyVcs::Hg(hg) => hg.root().await,
+            AnyVcs::Jj(jj) => jj.root().await,
+        }
+    }
+
+    async fn commit(&self, message: &str) -> Void {
+        match self {
+            AnyVcs::Git(git) => git.commit(message).await,
+            AnyVcs::LibGit(lib) => lib.commit(message).await,
+            AnyVcs::Hg(hg) => hg.commit(message).await,
+            AnyVcs::Jj(jj) => jj.commit(message).await,
+        }
+    }
+
+    fn commit_target(&self) -> DiffTarget {
+        match self {
+            AnyVcs::Git(git) => git.commit_target(),
+            AnyVcs::LibGit(lib) => lib.commit_target(),
+            AnyVcs::Hg(hg) => hg.commit_target(),
+            AnyVcs::Jj(jj) => jj.commit_target(),
+        }
+    }
+}
+
+// Tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commit_records() {
+        let stdout = "aaaa1111\x1faaaa\x1fAdd a thing\x1f2024-01-02\x1fAdd a thing\n\nWith details.\n\x1e\nbbbb2222\x1fbbbb\x1fFix\x1f2024-01-03\x1fFix\x1fsplit\x1e\n";
+        let commits = parse_commits(stdout);
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].sha, "aaaa1111");
+        assert_eq!(commits[0].short_sha, "aaaa");
+        assert_eq!(commits[0].subject, "Add a thing");
+        assert_eq!(commits[0].date, "2024-01-02");
+        assert_eq!(commits[0].message, "Add a thing\n\nWith details.");
+
+        // The message is the last field, so it may contain anything (even the separator).
+        assert_eq!(commits[1].sha, "bbbb2222");
+        assert_eq!(commits[1].message, "Fix\x1fsplit");
+    }
+
+    #[test]
+    fn parses_no_commits() {
+        assert!(parse_commits("").is_empty());
+        assert!(parse_commits("\n").is_empty());
+    }
+}